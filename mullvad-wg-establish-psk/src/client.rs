@@ -28,12 +28,21 @@ error_chain! {
 
 quick_main!(run);
 
+/// Algorithms to establish keys with, most preferred first. The first one the server accepts is
+/// used.
+static ALGORITHM_PREFERENCES: &[OqsKexAlg] = &[
+    OqsKexAlg::MlKem1024,
+    OqsKexAlg::FrodoKem976Shake,
+    OqsKexAlg::MlKem768,
+    OqsKexAlg::FrodoKem640Shake,
+    OqsKexAlg::MlKem512,
+];
+
 fn run() -> Result<()> {
     env_logger::init().unwrap();
     let server_uri = parse_command_line();
-    let algs = [OqsKexAlg::RlweNewhope];
 
-    let keys = establish_quantum_safe_keys(&server_uri, &algs)?;
+    let keys = establish_quantum_safe_keys(&server_uri, ALGORITHM_PREFERENCES)?;
     let psk = generate_psk(&keys);
 
     println!("{}", psk);
@@ -70,8 +79,8 @@ fn parse_command_line() -> String {
 
 fn establish_quantum_safe_keys(
     server_uri: &str,
-    algorithms: &[OqsKexAlg],
+    preferences: &[OqsKexAlg],
 ) -> Result<Vec<SharedKey>> {
     let mut client = OqsKexClient::new(server_uri)?;
-    Ok(client.kex(algorithms)?)
+    Ok(client.kex_negotiated(preferences, 1)?.keys)
 }
@@ -16,14 +16,23 @@ pub struct Settings {
 lazy_static! {
     static ref ALGORITHMS: HashMap<&'static str, OqsKexAlg> = {
         let mut m = HashMap::new();
-        m.insert("bcns15", OqsKexAlg::RlweBcns15);
-        m.insert("newhope", OqsKexAlg::RlweNewhope);
-        m.insert("msrln16", OqsKexAlg::RlweMsrln16);
-        m.insert("sidhcln16", OqsKexAlg::SidhCln16);
-        m.insert("sidhcln16_compressed", OqsKexAlg::SidhCln16Compressed);
-        m.insert("mcbits", OqsKexAlg::CodeMcbits);
-        m.insert("ntru", OqsKexAlg::Ntru);
-        m.insert("kyber", OqsKexAlg::MlweKyber);
+        m.insert("mlkem512", OqsKexAlg::MlKem512);
+        m.insert("mlkem768", OqsKexAlg::MlKem768);
+        m.insert("mlkem1024", OqsKexAlg::MlKem1024);
+        m.insert("frodokem640aes", OqsKexAlg::FrodoKem640Aes);
+        m.insert("frodokem640shake", OqsKexAlg::FrodoKem640Shake);
+        m.insert("frodokem976aes", OqsKexAlg::FrodoKem976Aes);
+        m.insert("frodokem976shake", OqsKexAlg::FrodoKem976Shake);
+        m.insert("frodokem1344aes", OqsKexAlg::FrodoKem1344Aes);
+        m.insert("frodokem1344shake", OqsKexAlg::FrodoKem1344Shake);
+        m.insert("mceliece348864", OqsKexAlg::ClassicMcEliece348864);
+        m.insert("mceliece460896", OqsKexAlg::ClassicMcEliece460896);
+        m.insert("mceliece6688128", OqsKexAlg::ClassicMcEliece6688128);
+        m.insert("mceliece6960119", OqsKexAlg::ClassicMcEliece6960119);
+        m.insert("mceliece8192128", OqsKexAlg::ClassicMcEliece8192128);
+        m.insert("hqc128", OqsKexAlg::Hqc128);
+        m.insert("hqc192", OqsKexAlg::Hqc192);
+        m.insert("hqc256", OqsKexAlg::Hqc256);
         m
     };
 }
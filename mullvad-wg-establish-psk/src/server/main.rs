@@ -18,7 +18,7 @@ extern crate mullvad_wg_establish_psk;
 
 use error_chain::ChainedError;
 
-use oqs_kex_rpc::SharedKey;
+use oqs_kex_rpc::{ExchangedKeys, SharedKey};
 
 use std::result::Result as StdResult;
 use std::path::{Path, PathBuf};
@@ -52,7 +52,9 @@ fn main() {
     env_logger::init().unwrap();
     let settings = cli::parse_arguments();
     let on_kex_script = settings.on_kex_script;
-    let on_kex = move |meta: KexMetadata, keys: Vec<SharedKey>| on_kex(meta, &keys, &on_kex_script);
+    let on_kex = move |meta: KexMetadata, exchanged: ExchangedKeys| {
+        on_kex(meta, &exchanged.keys, &on_kex_script)
+    };
 
     let server = oqs_kex_rpc::server::start(
         settings.listen_addr,
@@ -0,0 +1,189 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use oqs::kex::{AliceMsg, AliceState, OqsKexAlg};
+use oqs::rand::{OqsRand, OqsRandAlg};
+use oqs::sig::PublicKey;
+
+use super::frame::{Frame, Header, HEADER_LEN, INVALID_FRAME};
+use client::{alice_0, alice_1, authenticate, check_response, server_error_kind,
+             transcript_hash, ErrorKind, Result, ResultExt, MAX_RESPONSE_LEN};
+use confirm::{self, Role};
+use transcript::ExchangedKeys;
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+
+
+/// A key exchange client speaking the binary protocol. Works like an [`OqsKexClient`], and fails
+/// with the same errors.
+///
+/// The connection to the server is kept open between exchanges. If it fails, the next exchange
+/// connects again.
+///
+/// [`OqsKexClient`]: ../client/struct.OqsKexClient.html
+pub struct BinaryKexClient {
+    address: SocketAddr,
+    stream: Option<TcpStream>,
+    rand: OqsRandAlg,
+    server_key: Option<PublicKey>,
+}
+
+impl BinaryKexClient {
+    /// Connects to a server speaking the binary protocol at `address`.
+    pub fn connect(address: SocketAddr) -> Result<Self> {
+        let stream = TcpStream::connect(address).chain_err(|| ErrorKind::RpcError)?;
+        Ok(BinaryKexClient {
+            address,
+            stream: Some(stream),
+            rand: OqsRandAlg::default(),
+            server_key: None,
+        })
+    }
+
+    /// Connects to the given address and returns a client instance that authenticates the
+    /// server, like [`OqsKexClient::with_server_key`] does.
+    ///
+    /// [`OqsKexClient::with_server_key`]:
+    /// ../client/struct.OqsKexClient.html#method.with_server_key
+    pub fn with_server_key(address: SocketAddr, server_key: PublicKey) -> Result<Self> {
+        let mut client = Self::connect(address)?;
+        client.server_key = Some(server_key);
+        Ok(client)
+    }
+
+    /// Pins the server's public key, like [`with_server_key`](#method.with_server_key) does.
+    pub fn set_server_key(&mut self, server_key: PublicKey) {
+        self.server_key = Some(server_key);
+    }
+
+    /// Configure which PRNG algorithm this client should use to source its entropy.
+    pub fn set_rand(&mut self, rand: OqsRandAlg) {
+        self.rand = rand;
+    }
+
+    /// Performs a full key exchange with all the algorithms in `algs` at the same time. See
+    /// [`OqsKexClient::kex`](../client/struct.OqsKexClient.html#method.kex).
+    pub fn kex(&mut self, algs: &[OqsKexAlg]) -> Result<ExchangedKeys> {
+        let rand = OqsRand::new(self.rand).chain_err(|| ErrorKind::OqsError)?;
+        let alices = alice_0(&rand, algs)?;
+        let response = match self.request(&Frame::KexRequest(alice_msgs(&alices)))? {
+            Frame::KexResponse(response) => response,
+            _ => bail!(ErrorKind::InvalidResponse),
+        };
+        check_response(&alices, &response.bob_msgs)?;
+        let transcript_hash = transcript_hash(&alices, &response.bob_msgs);
+        authenticate(
+            self.server_key.as_ref(),
+            &rand,
+            &transcript_hash,
+            response.signature.as_ref(),
+        )?;
+        let keys = alice_1(&rand, alices, &response.bob_msgs)?;
+        Ok(ExchangedKeys {
+            keys,
+            transcript_hash,
+        })
+    }
+
+    /// Performs a full key exchange followed by a key confirmation round. See
+    /// [`OqsKexClient::kex_with_confirmation`].
+    ///
+    /// [`OqsKexClient::kex_with_confirmation`]:
+    /// ../client/struct.OqsKexClient.html#method.kex_with_confirmation
+    pub fn kex_with_confirmation(&mut self, algs: &[OqsKexAlg]) -> Result<ExchangedKeys> {
+        let rand = OqsRand::new(self.rand).chain_err(|| ErrorKind::OqsError)?;
+        let alices = alice_0(&rand, algs)?;
+        let response = match self.request(&Frame::ConfirmedKexRequest(alice_msgs(&alices)))? {
+            Frame::UnconfirmedKexResponse(response) => response,
+            _ => bail!(ErrorKind::InvalidResponse),
+        };
+        check_response(&alices, &response.bob_msgs)?;
+        let transcript_hash = transcript_hash(&alices, &response.bob_msgs);
+        authenticate(
+            self.server_key.as_ref(),
+            &rand,
+            &transcript_hash,
+            response.signature.as_ref(),
+        )?;
+        let keys = alice_1(&rand, alices, &response.bob_msgs)?;
+
+        ensure!(
+            confirm::verify(
+                Role::Server,
+                &keys,
+                &transcript_hash,
+                &response.server_mac,
+            ),
+            ErrorKind::ConfirmationError
+        );
+        let confirmation = Frame::ConfirmRequest {
+            session_id: response.session_id,
+            client_mac: confirm::mac(Role::Client, &keys, &transcript_hash),
+        };
+        match self.request(&confirmation)? {
+            Frame::ConfirmResponse => Ok(ExchangedKeys {
+                keys,
+                transcript_hash,
+            }),
+            _ => bail!(ErrorKind::InvalidResponse),
+        }
+    }
+
+    /// Sends `request` and returns the server's response. Error frames are returned as errors.
+    fn request(&mut self, request: &Frame) -> Result<Frame> {
+        if self.stream.is_none() {
+            let stream = TcpStream::connect(self.address).chain_err(|| ErrorKind::RpcError)?;
+            self.stream = Some(stream);
+        }
+        let response = Self::send(self.stream.as_mut().unwrap(), request);
+        match response {
+            Ok(Frame::Error { code, violation }) => {
+                // The server closes the connection after frames it can't decode.
+                if code == INVALID_FRAME {
+                    self.stream = None;
+                }
+                bail!(server_error_kind(code, violation))
+            }
+            Ok(response) => Ok(response),
+            Err(e) => {
+                self.stream = None;
+                Err(e)
+            }
+        }
+    }
+
+    fn send(stream: &mut TcpStream, request: &Frame) -> Result<Frame> {
+        stream
+            .write_all(&request.encode())
+            .chain_err(|| ErrorKind::RpcError)?;
+        let mut header = [0; HEADER_LEN];
+        stream
+            .read_exact(&mut header)
+            .chain_err(|| ErrorKind::RpcError)?;
+        let header = Header::decode(&header).ok_or(ErrorKind::InvalidResponse)?;
+        ensure!(
+            header.len as usize <= MAX_RESPONSE_LEN,
+            ErrorKind::InvalidResponse
+        );
+        let mut payload = Vec::new();
+        Read::by_ref(stream)
+            .take(u64::from(header.len))
+            .read_to_end(&mut payload)
+            .chain_err(|| ErrorKind::RpcError)?;
+        ensure!(payload.len() == header.len as usize, ErrorKind::RpcError);
+        Frame::decode(header.frame_type, &payload).ok_or_else(|| ErrorKind::InvalidResponse.into())
+    }
+}
+
+fn alice_msgs(alices: &[AliceState]) -> Vec<AliceMsg> {
+    alices
+        .iter()
+        .map(|alice| alice.get_alice_msg().clone())
+        .collect()
+}
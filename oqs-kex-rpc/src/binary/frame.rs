@@ -0,0 +1,464 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Encoding and decoding of the frames of the binary protocol. See the module documentation of
+//! `binary` for the format.
+
+use oqs::kex::{AliceMsg, BobMsg, OqsKexAlg};
+use oqs::sig::Signature;
+
+use messages::{ConstraintViolation, KexResponse, UnconfirmedKex};
+use tags::{kex_alg_from_tag, kex_alg_tag, sig_alg_from_tag, sig_alg_tag};
+
+/// The protocol version this crate speaks. Frames with any other version are rejected.
+pub const VERSION: u8 = 1;
+
+/// Length of the header in front of every frame.
+pub const HEADER_LEN: usize = 6;
+
+/// Error code of the error frame answering a frame that can't be decoded. Same as the JSON-RPC
+/// `invalid_request` code.
+pub const INVALID_FRAME: i64 = -32600;
+
+/// Error code of the error frame reporting a failure without an error code of its own. Same as
+/// the JSON-RPC `internal_error` code.
+pub const INTERNAL_ERROR: i64 = -32603;
+
+const KEX_REQUEST: u8 = 0x01;
+const CONFIRMED_KEX_REQUEST: u8 = 0x02;
+const CONFIRM_REQUEST: u8 = 0x03;
+const KEX_RESPONSE: u8 = 0x81;
+const UNCONFIRMED_KEX_RESPONSE: u8 = 0x82;
+const CONFIRM_RESPONSE: u8 = 0x83;
+const ERROR_RESPONSE: u8 = 0xff;
+
+
+/// A frame of the binary protocol.
+#[derive(Debug, Clone)]
+pub enum Frame {
+    /// Alice's messages, to exchange keys with.
+    KexRequest(Vec<AliceMsg>),
+    /// Alice's messages, to exchange keys with followed by a key confirmation.
+    ConfirmedKexRequest(Vec<AliceMsg>),
+    /// The client's key confirmation MAC for a confirmed exchange.
+    ConfirmRequest { session_id: u64, client_mac: Vec<u8> },
+    /// Reply to a `KexRequest`.
+    KexResponse(KexResponse),
+    /// Reply to a `ConfirmedKexRequest`.
+    UnconfirmedKexResponse(UnconfirmedKex),
+    /// Reply to a `ConfirmRequest`.
+    ConfirmResponse,
+    /// Reply to any request that failed.
+    Error {
+        code: i64,
+        violation: Option<ConstraintViolation>,
+    },
+}
+
+/// The header in front of every frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    pub frame_type: u8,
+    pub len: u32,
+}
+
+impl Header {
+    /// Parses a header. Returns `None` if the header is for another protocol version.
+    pub fn decode(data: &[u8; HEADER_LEN]) -> Option<Self> {
+        let mut reader = Reader { data: &data[..] };
+        if reader.u8()? != VERSION {
+            return None;
+        }
+        Some(Header {
+            frame_type: reader.u8()?,
+            len: reader.u32()?,
+        })
+    }
+}
+
+impl Frame {
+    /// Returns an error frame without a constraint violation.
+    pub fn error(code: i64) -> Self {
+        Frame::Error {
+            code,
+            violation: None,
+        }
+    }
+
+    /// Returns the frame, header included, as bytes to send.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut payload = Vec::new();
+        let frame_type = match *self {
+            Frame::KexRequest(ref alice_msgs) => {
+                write_alice_msgs(&mut payload, alice_msgs);
+                KEX_REQUEST
+            }
+            Frame::ConfirmedKexRequest(ref alice_msgs) => {
+                write_alice_msgs(&mut payload, alice_msgs);
+                CONFIRMED_KEX_REQUEST
+            }
+            Frame::ConfirmRequest {
+                session_id,
+                ref client_mac,
+            } => {
+                write_u64(&mut payload, session_id);
+                write_bytes(&mut payload, client_mac);
+                CONFIRM_REQUEST
+            }
+            Frame::KexResponse(ref response) => {
+                write_bob_msgs(&mut payload, &response.bob_msgs);
+                write_signature(&mut payload, response.signature.as_ref());
+                KEX_RESPONSE
+            }
+            Frame::UnconfirmedKexResponse(ref response) => {
+                write_u64(&mut payload, response.session_id);
+                write_bob_msgs(&mut payload, &response.bob_msgs);
+                write_signature(&mut payload, response.signature.as_ref());
+                write_bytes(&mut payload, &response.server_mac);
+                UNCONFIRMED_KEX_RESPONSE
+            }
+            Frame::ConfirmResponse => CONFIRM_RESPONSE,
+            Frame::Error {
+                code,
+                ref violation,
+            } => {
+                write_u64(&mut payload, code as u64);
+                write_violation(&mut payload, violation.as_ref());
+                ERROR_RESPONSE
+            }
+        };
+
+        let mut frame = Vec::with_capacity(HEADER_LEN + payload.len());
+        frame.push(VERSION);
+        frame.push(frame_type);
+        write_u32(&mut frame, payload.len() as u32);
+        frame.extend_from_slice(&payload);
+        frame
+    }
+
+    /// Decodes the payload of a frame of type `frame_type`. Returns `None` if the payload is
+    /// malformed or the frame type is unknown.
+    pub fn decode(frame_type: u8, payload: &[u8]) -> Option<Self> {
+        let mut reader = Reader { data: payload };
+        let frame = match frame_type {
+            KEX_REQUEST => Frame::KexRequest(reader.alice_msgs()?),
+            CONFIRMED_KEX_REQUEST => Frame::ConfirmedKexRequest(reader.alice_msgs()?),
+            CONFIRM_REQUEST => Frame::ConfirmRequest {
+                session_id: reader.u64()?,
+                client_mac: reader.bytes()?.to_vec(),
+            },
+            KEX_RESPONSE => Frame::KexResponse(KexResponse {
+                bob_msgs: reader.bob_msgs()?,
+                signature: reader.signature()?,
+            }),
+            UNCONFIRMED_KEX_RESPONSE => Frame::UnconfirmedKexResponse(UnconfirmedKex {
+                session_id: reader.u64()?,
+                bob_msgs: reader.bob_msgs()?,
+                signature: reader.signature()?,
+                server_mac: reader.bytes()?.to_vec(),
+            }),
+            CONFIRM_RESPONSE => Frame::ConfirmResponse,
+            ERROR_RESPONSE => Frame::Error {
+                code: reader.u64()? as i64,
+                violation: reader.violation()?,
+            },
+            _ => return None,
+        };
+        if reader.data.is_empty() {
+            Some(frame)
+        } else {
+            None
+        }
+    }
+}
+
+
+fn write_u16(buf: &mut Vec<u8>, value: u16) {
+    buf.extend_from_slice(&value.to_be_bytes());
+}
+
+fn write_u32(buf: &mut Vec<u8>, value: u32) {
+    buf.extend_from_slice(&value.to_be_bytes());
+}
+
+fn write_u64(buf: &mut Vec<u8>, value: u64) {
+    buf.extend_from_slice(&value.to_be_bytes());
+}
+
+fn write_bytes(buf: &mut Vec<u8>, data: &[u8]) {
+    write_u32(buf, data.len() as u32);
+    buf.extend_from_slice(data);
+}
+
+fn write_alice_msgs(buf: &mut Vec<u8>, alice_msgs: &[AliceMsg]) {
+    write_u16(buf, alice_msgs.len() as u16);
+    for msg in alice_msgs {
+        buf.push(kex_alg_tag(msg.algorithm()));
+        write_bytes(buf, msg.data());
+    }
+}
+
+fn write_bob_msgs(buf: &mut Vec<u8>, bob_msgs: &[BobMsg]) {
+    write_u16(buf, bob_msgs.len() as u16);
+    for msg in bob_msgs {
+        buf.push(kex_alg_tag(msg.algorithm()));
+        write_bytes(buf, msg.data());
+    }
+}
+
+fn write_signature(buf: &mut Vec<u8>, signature: Option<&Signature>) {
+    match signature {
+        Some(signature) => {
+            buf.push(sig_alg_tag(signature.algorithm()));
+            write_bytes(buf, signature.data());
+        }
+        None => buf.push(0),
+    }
+}
+
+fn write_violation(buf: &mut Vec<u8>, violation: Option<&ConstraintViolation>) {
+    use messages::ConstraintViolation::*;
+    match violation {
+        None => buf.push(0),
+        Some(&DisallowedAlgorithm(algorithm)) => {
+            buf.push(1);
+            buf.push(kex_alg_tag(algorithm));
+        }
+        Some(&TooManyAlgorithms { count, max }) => {
+            buf.push(2);
+            write_u64(buf, count as u64);
+            write_u64(buf, max as u64);
+        }
+        Some(&TooManyOccurrences {
+            algorithm,
+            count,
+            max,
+        }) => {
+            buf.push(3);
+            buf.push(kex_alg_tag(algorithm));
+            write_u64(buf, count as u64);
+            write_u64(buf, max as u64);
+        }
+        Some(&TooExpensive { cost, max }) => {
+            buf.push(4);
+            write_u64(buf, cost);
+            write_u64(buf, max);
+        }
+        Some(&ConfirmationRequired) => buf.push(5),
+    }
+}
+
+
+/// Reads the fields of a payload front to back. Every method returns `None` if the payload ends
+/// before the field does, or the field is invalid.
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.data.len() < len {
+            return None;
+        }
+        let (field, rest) = self.data.split_at(len);
+        self.data = rest;
+        Some(field)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|field| field[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        let mut bytes = [0; 2];
+        bytes.copy_from_slice(self.take(2)?);
+        Some(u16::from_be_bytes(bytes))
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.take(4)?);
+        Some(u32::from_be_bytes(bytes))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Some(u64::from_be_bytes(bytes))
+    }
+
+    fn usize(&mut self) -> Option<usize> {
+        let value = self.u64()?;
+        if value > usize::MAX as u64 {
+            return None;
+        }
+        Some(value as usize)
+    }
+
+    fn bytes(&mut self) -> Option<&'a [u8]> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+
+    fn kex_alg(&mut self) -> Option<OqsKexAlg> {
+        kex_alg_from_tag(self.u8()?)
+    }
+
+    fn alice_msgs(&mut self) -> Option<Vec<AliceMsg>> {
+        let count = self.u16()?;
+        (0..count)
+            .map(|_| {
+                let algorithm = self.kex_alg()?;
+                Some(AliceMsg::from_bytes(algorithm, self.bytes()?))
+            })
+            .collect()
+    }
+
+    fn bob_msgs(&mut self) -> Option<Vec<BobMsg>> {
+        let count = self.u16()?;
+        (0..count)
+            .map(|_| {
+                let algorithm = self.kex_alg()?;
+                Some(BobMsg::from_bytes(algorithm, self.bytes()?))
+            })
+            .collect()
+    }
+
+    /// Reads an optional signature. Returns `Some(None)` if there is no signature.
+    fn signature(&mut self) -> Option<Option<Signature>> {
+        match self.u8()? {
+            0 => Some(None),
+            tag => {
+                let algorithm = sig_alg_from_tag(tag)?;
+                Some(Some(Signature::from_bytes(algorithm, self.bytes()?)))
+            }
+        }
+    }
+
+    /// Reads an optional constraint violation. Returns `Some(None)` if there is no violation.
+    fn violation(&mut self) -> Option<Option<ConstraintViolation>> {
+        use messages::ConstraintViolation::*;
+        let violation = match self.u8()? {
+            0 => return Some(None),
+            1 => DisallowedAlgorithm(self.kex_alg()?),
+            2 => TooManyAlgorithms {
+                count: self.usize()?,
+                max: self.usize()?,
+            },
+            3 => TooManyOccurrences {
+                algorithm: self.kex_alg()?,
+                count: self.usize()?,
+                max: self.usize()?,
+            },
+            4 => TooExpensive {
+                cost: self.u64()?,
+                max: self.u64()?,
+            },
+            5 => ConfirmationRequired,
+            _ => return None,
+        };
+        Some(Some(violation))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oqs::kex::OqsKexAlg::*;
+
+    /// Encodes and decodes `frame`, and returns the decoded frame.
+    fn roundtrip(frame: &Frame) -> Frame {
+        let encoded = frame.encode();
+        let mut header = [0; HEADER_LEN];
+        header.copy_from_slice(&encoded[..HEADER_LEN]);
+        let header = Header::decode(&header).unwrap();
+        let payload = &encoded[HEADER_LEN..];
+        assert_eq!(header.len as usize, payload.len());
+        Frame::decode(header.frame_type, payload).unwrap()
+    }
+
+    #[test]
+    fn kex_request_roundtrip() {
+        let alice_msgs = vec![
+            AliceMsg::from_bytes(MlKem768, &[1, 2, 3]),
+            AliceMsg::from_bytes(Hqc256, &[4, 5]),
+        ];
+        match roundtrip(&Frame::KexRequest(alice_msgs.clone())) {
+            Frame::KexRequest(decoded) => assert_eq!(decoded, alice_msgs),
+            other => panic!("Unexpected frame: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn unconfirmed_kex_response_roundtrip() {
+        let response = UnconfirmedKex {
+            session_id: 0x0102_0304_0506_0708,
+            bob_msgs: vec![BobMsg::from_bytes(FrodoKem640Shake, &[9; 100])],
+            signature: Some(Signature::from_bytes(::oqs::sig::OqsSigAlg::Falcon512, &[7; 10])),
+            server_mac: vec![6; 32],
+        };
+        match roundtrip(&Frame::UnconfirmedKexResponse(response.clone())) {
+            Frame::UnconfirmedKexResponse(decoded) => {
+                assert_eq!(decoded.session_id, response.session_id);
+                assert_eq!(decoded.bob_msgs, response.bob_msgs);
+                assert_eq!(decoded.signature, response.signature);
+                assert_eq!(decoded.server_mac, response.server_mac);
+            }
+            other => panic!("Unexpected frame: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn error_roundtrip() {
+        let violation = ConstraintViolation::TooManyOccurrences {
+            algorithm: ClassicMcEliece348864,
+            count: 3,
+            max: 2,
+        };
+        let frame = Frame::Error {
+            code: ::error_codes::CONSTRAINT_ERROR,
+            violation: Some(violation.clone()),
+        };
+        match roundtrip(&frame) {
+            Frame::Error { code, violation: decoded } => {
+                assert_eq!(code, ::error_codes::CONSTRAINT_ERROR);
+                assert_eq!(decoded, Some(violation));
+            }
+            other => panic!("Unexpected frame: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn decode_other_version() {
+        let mut header = [VERSION + 1, KEX_REQUEST, 0, 0, 0, 0];
+        assert_eq!(Header::decode(&header), None);
+        header[0] = VERSION;
+        assert_eq!(
+            Header::decode(&header),
+            Some(Header {
+                frame_type: KEX_REQUEST,
+                len: 0,
+            })
+        );
+    }
+
+    #[test]
+    fn decode_malformed_payload() {
+        let payload = &Frame::KexRequest(vec![AliceMsg::from_bytes(MlKem768, &[1, 2, 3])])
+            .encode()[HEADER_LEN..];
+        assert!(Frame::decode(KEX_REQUEST, &payload[..payload.len() - 1]).is_none());
+        let mut trailing = payload.to_vec();
+        trailing.push(0);
+        assert!(Frame::decode(KEX_REQUEST, &trailing).is_none());
+        assert!(Frame::decode(0x42, payload).is_none());
+        let mut unknown_alg = payload.to_vec();
+        unknown_alg[2] = 0xee;
+        assert!(Frame::decode(KEX_REQUEST, &unknown_alg).is_none());
+    }
+}
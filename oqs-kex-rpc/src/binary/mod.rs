@@ -0,0 +1,126 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A compact binary protocol for the key exchange, as an alternative to JSON-RPC over HTTP.
+//!
+//! The messages are sent as raw bytes instead of JSON arrays of numbers, and there is no HTTP
+//! around them. The server shares `ServerConstraints` and the `on_kex` callback with the JSON-RPC
+//! server, and answers with the same [`error_codes`](../error_codes/index.html). Start it with
+//! [`start`] or [`start_async`], and exchange keys with it using a [`BinaryKexClient`].
+//!
+//! A client can send any number of requests over one connection. The server answers them one at
+//! a time, in order. It closes connections on which no complete request arrives within 60
+//! seconds, or whose client doesn't receive a response within 60 seconds.
+//!
+//! # Wire format
+//!
+//! Every frame starts with a six byte header: the protocol version (currently 1) as one byte, the
+//! frame type as one byte and the length of the payload as a big endian `u32`. All integers are
+//! big endian. Byte strings are encoded as a `u32` length followed by the bytes. A list of
+//! messages is a `u16` count followed by the algorithm tag (one byte) and the byte string of every
+//! message. A signature is a signature algorithm tag followed by the byte string, or a single
+//! zero byte if there is no signature.
+//!
+//! | Type   | Frame                      | Payload                                           |
+//! |--------|----------------------------|---------------------------------------------------|
+//! | `0x01` | Key exchange               | Alice's messages                                  |
+//! | `0x02` | Key exchange with confirm. | Alice's messages                                  |
+//! | `0x03` | Confirmation               | `u64` session id, client MAC                      |
+//! | `0x81` | Key exchange reply         | Bob's messages, signature                         |
+//! | `0x82` | Unconfirmed reply          | `u64` session id, Bob's messages, signature, MAC  |
+//! | `0x83` | Confirmation reply         | Empty                                             |
+//! | `0xff` | Error                      | `i64` error code, constraint violation            |
+//!
+//! Frames that can't be decoded, have another version, or a payload larger than the server's
+//! `max_request_size` (16 MiB if it has none), are answered with an error frame with code `-32600`
+//! after which the server closes the connection.
+//!
+//! There is no equivalent of the JSON-RPC `capabilities` method, so clients have to know which
+//! algorithms the server accepts.
+//!
+//! [`start`]: fn.start.html
+//! [`start_async`]: fn.start_async.html
+//! [`BinaryKexClient`]: struct.BinaryKexClient.html
+
+use futures::IntoFuture;
+
+use server::{AsyncCallback, Metadata, Result, Server, ServerConstraints, SyncCallback};
+use transcript::ExchangedKeys;
+
+use std::net::SocketAddr;
+use std::result::Result as StdResult;
+
+mod client;
+mod frame;
+mod server;
+
+pub use self::client::BinaryKexClient;
+pub(crate) use self::server::BinaryServer;
+
+
+/// Computes the metadata handed to `on_kex` together with the keys exchanged over a connection.
+/// Called once for every connection, with the address of the client.
+pub trait MetaExtractor<M: Metadata>: Send + Sync + 'static {
+    /// Returns the metadata of the exchanges done over a connection from `peer_addr`.
+    fn read_metadata(&self, peer_addr: &SocketAddr) -> M;
+}
+
+impl<M, F> MetaExtractor<M> for F
+where
+    M: Metadata,
+    F: Fn(&SocketAddr) -> M + Send + Sync + 'static,
+{
+    fn read_metadata(&self, peer_addr: &SocketAddr) -> M {
+        (*self)(peer_addr)
+    }
+}
+
+/// Tries to start a server speaking the binary protocol, bound to `addr`.
+///
+/// Works like [`server::start`](../server/fn.start.html), except that `meta_extractor` gets the
+/// address of the client instead of an HTTP request. `max_request_size` limits the size of the
+/// payload of a frame, and defaults to 16 MiB. The server can't use TLS, so starting it fails if
+/// `constraints` holds a TLS configuration.
+///
+/// All connections are served on one thread, but `on_kex` is called on the worker pool, so a slow
+/// `on_kex` only takes up a worker thread until it returns. Use
+/// [`start_async`](fn.start_async.html) for callbacks that have to wait for I/O.
+pub fn start<ME, M, E, F>(
+    addr: SocketAddr,
+    meta_extractor: ME,
+    on_kex: F,
+    constraints: ServerConstraints,
+) -> Result<Server>
+where
+    M: Metadata + Sync,
+    ME: MetaExtractor<M>,
+    E: ::std::error::Error + Send + 'static,
+    F: Fn(M, ExchangedKeys) -> StdResult<(), E> + Send + Sync + 'static,
+{
+    ::server::start_binary_server(addr, meta_extractor, SyncCallback(on_kex), constraints)
+}
+
+/// Tries to start a server speaking the binary protocol, bound to `addr`, with an asynchronous
+/// `on_kex` callback. Works like [`server::start_async`](../server/fn.start_async.html), see
+/// [`start`](fn.start.html).
+pub fn start_async<ME, M, R, F>(
+    addr: SocketAddr,
+    meta_extractor: ME,
+    on_kex: F,
+    constraints: ServerConstraints,
+) -> Result<Server>
+where
+    M: Metadata + Sync,
+    ME: MetaExtractor<M>,
+    R: IntoFuture<Item = ()>,
+    R::Future: Send + 'static,
+    R::Error: ::std::error::Error + Send + 'static,
+    F: Fn(M, ExchangedKeys) -> R + Send + Sync + 'static,
+{
+    ::server::start_binary_server(addr, meta_extractor, AsyncCallback(on_kex), constraints)
+}
@@ -0,0 +1,260 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use futures::{future, Future, Stream};
+use futures::future::Loop;
+use tokio_core::net::{TcpListener, TcpStream};
+use tokio_core::reactor::Handle;
+use tokio_io::io::{read_exact, read_to_end, write_all};
+
+use super::MetaExtractor;
+use super::frame::{Frame, Header, HEADER_LEN, INTERNAL_ERROR, INVALID_FRAME};
+use listener::{with_timeout, ListenerThread};
+use rate_limit::ClientId;
+use server::{error_code, Error, ErrorKind, KexCallback, Metadata, OqsKexRpcServer, RequestMeta,
+             ServerFuture};
+
+use error_chain::ChainedError;
+
+use std::io::{self, Read};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How long the server waits for a client to send the next request in full, before closing the
+/// connection.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How long the server waits for a client to receive a response, before closing the connection.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The largest payload accepted when the server has no `max_request_size`. Room for the public
+/// keys of all algorithms, including every Classic McEliece variant, in one request.
+const DEFAULT_MAX_PAYLOAD_LEN: usize = 16 * 1024 * 1024;
+
+type ConnectionFuture = Box<dyn Future<Item = Loop<(), TcpStream>, Error = io::Error>>;
+
+
+/// A server speaking the binary protocol. Serves the connections on its own thread, and stops
+/// when dropped.
+pub(crate) struct BinaryServer {
+    address: SocketAddr,
+    thread: ListenerThread,
+}
+
+impl BinaryServer {
+    pub fn start<M, ME, C>(
+        addr: &SocketAddr,
+        server: OqsKexRpcServer<M, C>,
+        meta_extractor: ME,
+        max_payload_len: Option<usize>,
+    ) -> io::Result<Self>
+    where
+        M: Metadata + Sync,
+        ME: MetaExtractor<M>,
+        C: KexCallback<M>,
+    {
+        let addr = *addr;
+        let server = Arc::new(server);
+        let meta_extractor = Arc::new(meta_extractor);
+
+        let (thread, address) = ListenerThread::spawn("oqs-kex-rpc-binary", move |handle| {
+            let listener = TcpListener::bind(&addr, handle)?;
+            let address = listener.local_addr()?;
+            let serve = serve(listener, handle, server, meta_extractor, max_payload_len);
+            Ok((address, serve))
+        })?;
+        Ok(BinaryServer { address, thread })
+    }
+
+    pub fn address(&self) -> &SocketAddr {
+        &self.address
+    }
+
+    /// Blocks until the server has stopped.
+    pub fn wait(self) {
+        self.thread.wait()
+    }
+}
+
+/// Returns a future serving the connections accepted on `listener`.
+fn serve<M, ME, C>(
+    listener: TcpListener,
+    handle: &Handle,
+    server: Arc<OqsKexRpcServer<M, C>>,
+    meta_extractor: Arc<ME>,
+    max_payload_len: Option<usize>,
+) -> Box<dyn Future<Item = (), Error = ()>>
+where
+    M: Metadata + Sync,
+    ME: MetaExtractor<M>,
+    C: KexCallback<M>,
+{
+    let handle = handle.clone();
+    let connections = listener
+        .incoming()
+        .for_each(move |(stream, peer_addr)| {
+            let meta = RequestMeta {
+                meta: meta_extractor.read_metadata(&peer_addr),
+                client: ClientId::from_ip(peer_addr.ip()),
+            };
+            let connection =
+                serve_connection(stream, &handle, server.clone(), meta, max_payload_len)
+                    .map_err(move |error| {
+                        debug!("Error on connection from {}: {}", peer_addr, error)
+                    });
+            handle.spawn(connection);
+            Ok(())
+        })
+        .map_err(|error| error!("Unable to accept binary protocol connections: {}", error));
+    Box::new(connections)
+}
+
+/// Returns a future answering the requests sent over `stream`, one at a time, until the client
+/// closes the connection, takes longer than `REQUEST_TIMEOUT` to send a request or longer than
+/// `RESPONSE_TIMEOUT` to receive a response.
+fn serve_connection<M, C>(
+    stream: TcpStream,
+    handle: &Handle,
+    server: Arc<OqsKexRpcServer<M, C>>,
+    meta: RequestMeta<M>,
+    max_payload_len: Option<usize>,
+) -> Box<dyn Future<Item = (), Error = io::Error>>
+where
+    M: Metadata + Sync,
+    C: KexCallback<M>,
+{
+    let handle = handle.clone();
+    let connection = future::loop_fn(stream, move |stream| {
+        let server = server.clone();
+        let meta = meta.clone();
+        let handle = handle.clone();
+        let request = with_timeout(read_request(stream, max_payload_len), REQUEST_TIMEOUT, &handle);
+        request
+            .and_then(move |(stream, request)| -> ConnectionFuture {
+                match request {
+                    Some(request) => Box::new(
+                        handle_request(&server, meta, request)
+                            .and_then(move |response| write_response(stream, response, &handle))
+                            .map(Loop::Continue),
+                    ),
+                    None => reject(stream, &handle),
+                }
+            })
+            .or_else(|error| match error.kind() {
+                io::ErrorKind::UnexpectedEof | io::ErrorKind::TimedOut => Ok(Loop::Break(())),
+                _ => Err(error),
+            })
+    });
+    Box::new(connection)
+}
+
+/// Returns a future reading the next request frame from `stream`. Resolves to `None` if the frame
+/// is invalid, and fails with `UnexpectedEof` if the client closes the connection.
+fn read_request(
+    stream: TcpStream,
+    max_payload_len: Option<usize>,
+) -> Box<dyn Future<Item = (TcpStream, Option<Frame>), Error = io::Error>> {
+    Box::new(
+        read_exact(stream, [0; HEADER_LEN]).and_then(move |(stream, header)| {
+            let header = match Header::decode(&header) {
+                Some(header) if is_within_limit(&header, max_payload_len) => header,
+                _ => return future::Either::A(future::ok((stream, None))),
+            };
+            let payload = read_to_end(stream.take(u64::from(header.len)), Vec::new());
+            future::Either::B(payload.and_then(move |(stream, payload)| {
+                let stream = stream.into_inner();
+                if payload.len() != header.len as usize {
+                    return Err(io::ErrorKind::UnexpectedEof.into());
+                }
+                Ok((stream, Frame::decode(header.frame_type, &payload)))
+            }))
+        }),
+    )
+}
+
+fn is_within_limit(header: &Header, max_payload_len: Option<usize>) -> bool {
+    header.len as usize <= max_payload_len.unwrap_or(DEFAULT_MAX_PAYLOAD_LEN)
+}
+
+/// Returns a future writing `response` to `stream`, failing with `TimedOut` if the client doesn't
+/// receive it within `RESPONSE_TIMEOUT`.
+fn write_response(
+    stream: TcpStream,
+    response: Frame,
+    handle: &Handle,
+) -> Box<dyn Future<Item = TcpStream, Error = io::Error>> {
+    let write = write_all(stream, response.encode()).map(|(stream, _)| stream);
+    with_timeout(write, RESPONSE_TIMEOUT, handle)
+}
+
+/// Answers a frame that can't be decoded and closes the connection, since the stream can't be
+/// trusted to be at the start of a frame anymore.
+fn reject(stream: TcpStream, handle: &Handle) -> ConnectionFuture {
+    let response = Frame::error(INVALID_FRAME);
+    Box::new(write_response(stream, response, handle).map(|_| Loop::Break(())))
+}
+
+/// Returns a future resolving to the response to `request`.
+fn handle_request<M, C>(
+    server: &OqsKexRpcServer<M, C>,
+    meta: RequestMeta<M>,
+    request: Frame,
+) -> Box<dyn Future<Item = Frame, Error = io::Error>>
+where
+    M: Metadata + Sync,
+    C: KexCallback<M>,
+{
+    let (response, operation): (ServerFuture<Frame>, _) = match request {
+        Frame::KexRequest(alice_msgs) => (
+            Box::new(
+                server
+                    .perform_exchange(meta, alice_msgs)
+                    .map(Frame::KexResponse),
+            ),
+            "key exchange",
+        ),
+        Frame::ConfirmedKexRequest(alice_msgs) => (
+            Box::new(
+                server
+                    .begin_confirmed_exchange(meta, alice_msgs)
+                    .map(Frame::UnconfirmedKexResponse),
+            ),
+            "key exchange",
+        ),
+        Frame::ConfirmRequest {
+            session_id,
+            client_mac,
+        } => (
+            Box::new(
+                server
+                    .finish_confirmed_exchange(meta.client, session_id, &client_mac)
+                    .map(|()| Frame::ConfirmResponse),
+            ),
+            "key confirmation",
+        ),
+        _ => return Box::new(future::ok(Frame::error(INVALID_FRAME))),
+    };
+    Box::new(response.or_else(move |e| {
+        error!("Error during {}: {}", operation, e.display_chain());
+        Ok(error_frame(&e))
+    }))
+}
+
+/// Maps an error to the error frame sent to the client. Like for JSON-RPC, only the error code and
+/// the constraint violation are sent.
+fn error_frame(error: &Error) -> Frame {
+    let violation = match *error.kind() {
+        ErrorKind::ConstraintError(ref violation) => Some(violation.clone()),
+        _ => None,
+    };
+    Frame::Error {
+        code: error_code(error).unwrap_or(INTERNAL_ERROR),
+        violation,
+    }
+}
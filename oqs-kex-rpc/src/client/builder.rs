@@ -0,0 +1,135 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use oqs::rand::OqsRandAlg;
+use oqs::sig::PublicKey;
+
+use super::{rpc, ErrorKind, FailoverClient, HttpTransport, OqsKexClient, Result, ResultExt,
+            RetryPolicy, Timeouts, Transport, WebSocketTransport};
+#[cfg(feature = "tls")]
+use tls::ClientTlsConfig;
+
+#[cfg(unix)]
+use std::path::Path;
+use std::time::Duration;
+
+/// Builds an [`OqsKexClient`] with timeouts, a retry policy and the other client settings.
+/// Created with [`OqsKexClient::builder`].
+///
+/// Without a connect timeout or a request timeout, requests can wait for an unresponsive server
+/// forever.
+///
+/// [`OqsKexClient`]: struct.OqsKexClient.html
+/// [`OqsKexClient::builder`]: struct.OqsKexClient.html#method.builder
+#[derive(Clone)]
+pub struct OqsKexClientBuilder {
+    timeouts: Timeouts,
+    retry_policy: RetryPolicy,
+    rand: OqsRandAlg,
+    server_key: Option<PublicKey>,
+}
+
+impl OqsKexClientBuilder {
+    pub(crate) fn new() -> Self {
+        OqsKexClientBuilder {
+            timeouts: Timeouts::default(),
+            retry_policy: RetryPolicy::default(),
+            rand: OqsRandAlg::default(),
+            server_key: None,
+        }
+    }
+
+    /// Max time to wait for a connection to the server to be established.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.timeouts.connect = Some(timeout);
+        self
+    }
+
+    /// Max time a request may take once connected, from sending it until the whole response is
+    /// read. The request fails after this time even if the server is still sending data.
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.timeouts.request = Some(timeout);
+        self
+    }
+
+    /// How failed requests are retried. No requests are retried by default.
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Which PRNG algorithm the client should use to source its entropy.
+    pub fn rand(mut self, rand: OqsRandAlg) -> Self {
+        self.rand = rand;
+        self
+    }
+
+    /// Pins the server's public key, see
+    /// [`OqsKexClient::with_server_key`](struct.OqsKexClient.html#method.with_server_key).
+    pub fn server_key(mut self, server_key: PublicKey) -> Self {
+        self.server_key = Some(server_key);
+        self
+    }
+
+    /// Returns a client sending its requests to the given `http` address.
+    pub fn build(self, server_uri: &str) -> Result<OqsKexClient> {
+        let transport =
+            HttpTransport::http(server_uri, self.timeouts).chain_err(|| ErrorKind::RpcError)?;
+        Ok(self.build_with_transport(transport))
+    }
+
+    /// Returns a client exchanging keys with the first of the given `http` addresses that
+    /// responds, see [`FailoverClient`](struct.FailoverClient.html). All servers get the
+    /// settings of this builder.
+    pub fn build_failover(self, server_uris: &[&str]) -> Result<FailoverClient> {
+        ensure!(!server_uris.is_empty(), ErrorKind::RpcError);
+        let clients = server_uris
+            .iter()
+            .map(|server_uri| self.clone().build(server_uri))
+            .collect::<Result<_>>()?;
+        Ok(FailoverClient::new(clients))
+    }
+
+    /// Returns a client sending its requests to the given `https` address, see
+    /// [`OqsKexClient::with_tls`](struct.OqsKexClient.html#method.with_tls). Requires the `tls`
+    /// feature.
+    #[cfg(feature = "tls")]
+    pub fn build_tls(self, server_uri: &str, tls_config: &ClientTlsConfig) -> Result<OqsKexClient> {
+        let transport = HttpTransport::https(server_uri, tls_config, self.timeouts)
+            .chain_err(|| ErrorKind::RpcError)?;
+        Ok(self.build_with_transport(transport))
+    }
+
+    /// Returns a client sending its requests to a server listening on the Unix domain socket at
+    /// `path`. The connect timeout does not apply to Unix domain sockets.
+    #[cfg(unix)]
+    pub fn build_unix_socket<P: AsRef<Path>>(self, path: P) -> OqsKexClient {
+        let transport = HttpTransport::unix(path.as_ref(), self.timeouts);
+        self.build_with_transport(transport)
+    }
+
+    /// Returns a client sending its requests over a WebSocket connection to the given `ws`
+    /// address, see
+    /// [`OqsKexClient::with_websocket`](struct.OqsKexClient.html#method.with_websocket).
+    pub fn build_websocket(self, server_uri: &str) -> Result<OqsKexClient<WebSocketTransport>> {
+        let transport = WebSocketTransport::new(server_uri, self.timeouts)
+            .chain_err(|| ErrorKind::RpcError)?;
+        Ok(self.build_with_transport(transport))
+    }
+
+    /// Returns a client sending its requests over `transport`. The timeouts are not applied to
+    /// it, they are up to the transport.
+    pub fn build_with_transport<T: Transport>(self, transport: T) -> OqsKexClient<T> {
+        OqsKexClient {
+            rpc_client: rpc::OqsKexRpcClient::new(transport),
+            rand: self.rand,
+            server_key: self.server_key,
+            retry_policy: self.retry_policy,
+        }
+    }
+}
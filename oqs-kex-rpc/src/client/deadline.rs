@@ -0,0 +1,132 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The request timeout of the blocking transports. A timeout set directly on a socket applies to
+//! every single read and write, so a server trickling out its response would never time out.
+//! Instead the request gets a deadline, and every read and write only gets the time remaining
+//! until it.
+
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+use std::time::{Duration, Instant};
+
+/// Returns the deadline of a request with `timeout` starting now.
+pub fn after(timeout: Option<Duration>) -> Option<Instant> {
+    timeout.map(|timeout| Instant::now() + timeout)
+}
+
+/// A socket with read and write timeouts.
+pub trait Socket: Read + Write {
+    fn set_timeout(&self, timeout: Option<Duration>) -> io::Result<()>;
+}
+
+impl Socket for TcpStream {
+    fn set_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.set_read_timeout(timeout)?;
+        self.set_write_timeout(timeout)
+    }
+}
+
+#[cfg(unix)]
+impl Socket for UnixStream {
+    fn set_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.set_read_timeout(timeout)?;
+        self.set_write_timeout(timeout)
+    }
+}
+
+/// A socket failing every read and write after a deadline.
+pub struct DeadlineStream<S> {
+    socket: S,
+    deadline: Option<Instant>,
+}
+
+impl<S: Socket> DeadlineStream<S> {
+    pub fn new(socket: S, deadline: Option<Instant>) -> Self {
+        DeadlineStream { socket, deadline }
+    }
+
+    /// Replaces the deadline, for the next request over the same connection.
+    pub fn set_deadline(&mut self, deadline: Option<Instant>) {
+        self.deadline = deadline;
+    }
+
+    /// Sets the socket's timeout to the time remaining until the deadline. Fails if the deadline
+    /// has passed.
+    fn update_timeout(&self) -> io::Result<()> {
+        let remaining = match self.deadline {
+            Some(deadline) => {
+                let now = Instant::now();
+                if now >= deadline {
+                    return Err(io::Error::new(io::ErrorKind::TimedOut, "Request timed out"));
+                }
+                Some(deadline - now)
+            }
+            None => None,
+        };
+        self.socket.set_timeout(remaining)
+    }
+}
+
+impl<S: Socket> Read for DeadlineStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.update_timeout()?;
+        self.socket.read(buf)
+    }
+}
+
+impl<S: Socket> Write for DeadlineStream<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.update_timeout()?;
+        self.socket.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.update_timeout()?;
+        self.socket.flush()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread;
+
+    #[test]
+    fn fails_after_deadline() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            // Trickle out bytes faster than any single read times out.
+            for _ in 0..20 {
+                if stream.write_all(b"x").is_err() {
+                    break;
+                }
+                thread::sleep(Duration::from_millis(20));
+            }
+        });
+
+        let socket = TcpStream::connect(address).unwrap();
+        let deadline = after(Some(Duration::from_millis(100)));
+        let mut stream = DeadlineStream::new(socket, deadline);
+        let mut response = Vec::new();
+        let error = stream.read_to_end(&mut response).unwrap_err();
+        assert!(matches!(
+            error.kind(),
+            io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+        ));
+        assert!(response.len() < 20);
+        drop(stream);
+        server.join().unwrap();
+    }
+}
@@ -0,0 +1,114 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use oqs::kex::OqsKexAlg;
+use oqs::sig::PublicKey;
+
+use super::{ErrorKind, HttpTransport, OqsKexClient, Result, Transport};
+use transcript::ExchangedKeys;
+
+/// A key exchange client with an ordered list of servers, created with
+/// [`OqsKexClientBuilder::build_failover`] or from a client per server with [`new`].
+///
+/// Every exchange is first tried with the first server. If it fails with an [`RpcError`] or an
+/// [`InvalidResponse`], it is tried with the next server, and so on. Other errors are returned
+/// directly, since another server would most likely fail the same way. Every server's client
+/// retries according to its own [`RetryPolicy`] before the next server is tried.
+///
+/// [`OqsKexClientBuilder::build_failover`]:
+/// struct.OqsKexClientBuilder.html#method.build_failover
+/// [`new`]: #method.new
+/// [`RpcError`]: enum.ErrorKind.html#variant.RpcError
+/// [`InvalidResponse`]: enum.ErrorKind.html#variant.InvalidResponse
+/// [`RetryPolicy`]: struct.RetryPolicy.html
+pub struct FailoverClient<T: Transport = HttpTransport> {
+    clients: Vec<OqsKexClient<T>>,
+}
+
+/// The result of a key exchange done by a [`FailoverClient`](struct.FailoverClient.html).
+#[derive(Debug, PartialEq, Eq)]
+pub struct FailoverKeys {
+    /// The exchanged keys.
+    pub keys: ExchangedKeys,
+    /// Position of the server the keys were exchanged with, in the list of servers the client
+    /// was created with.
+    pub server: usize,
+}
+
+impl<T: Transport> FailoverClient<T> {
+    /// Creates a client trying the servers `clients` send their requests to, in order. All
+    /// exchanges fail with an [`RpcError`](enum.ErrorKind.html#variant.RpcError) if `clients`
+    /// is empty.
+    pub fn new(clients: Vec<OqsKexClient<T>>) -> Self {
+        FailoverClient { clients }
+    }
+
+    /// Pins the public key of all servers. Use [`new`](#method.new) with clients having their
+    /// own pinned keys if the servers sign with different keys.
+    pub fn set_server_key(&mut self, server_key: PublicKey) {
+        for client in &mut self.clients {
+            client.set_server_key(server_key.clone());
+        }
+    }
+
+    /// Performs a key exchange like [`OqsKexClient::kex`], failing over to the next server if
+    /// needed.
+    ///
+    /// [`OqsKexClient::kex`]: struct.OqsKexClient.html#method.kex
+    pub fn kex(&mut self, algs: &[OqsKexAlg]) -> Result<FailoverKeys> {
+        self.failover(|client| client.kex(algs))
+    }
+
+    /// Performs a key exchange like [`OqsKexClient::kex_with_confirmation`], failing over to the
+    /// next server if needed.
+    ///
+    /// [`OqsKexClient::kex_with_confirmation`]:
+    /// struct.OqsKexClient.html#method.kex_with_confirmation
+    pub fn kex_with_confirmation(&mut self, algs: &[OqsKexAlg]) -> Result<FailoverKeys> {
+        self.failover(|client| client.kex_with_confirmation(algs))
+    }
+
+    /// Performs a key exchange like [`OqsKexClient::kex_negotiated`], failing over to the next
+    /// server if needed. The algorithms are negotiated with the server the keys are exchanged
+    /// with.
+    ///
+    /// [`OqsKexClient::kex_negotiated`]: struct.OqsKexClient.html#method.kex_negotiated
+    pub fn kex_negotiated(
+        &mut self,
+        preferences: &[OqsKexAlg],
+        max: usize,
+    ) -> Result<FailoverKeys> {
+        self.failover(|client| client.kex_negotiated(preferences, max))
+    }
+
+    /// Calls `exchange` with the client of every server in turn, until it succeeds or fails with
+    /// an error another server won't help against.
+    fn failover<F>(&mut self, mut exchange: F) -> Result<FailoverKeys>
+    where
+        F: FnMut(&mut OqsKexClient<T>) -> Result<ExchangedKeys>,
+    {
+        let mut last_error = None;
+        for (server, client) in self.clients.iter_mut().enumerate() {
+            match exchange(client) {
+                Ok(keys) => return Ok(FailoverKeys { keys, server }),
+                Err(error) => {
+                    if !should_fail_over(error.kind()) {
+                        return Err(error);
+                    }
+                    debug!("Failing over after error from server {}: {}", server, error);
+                    last_error = Some(error);
+                }
+            }
+        }
+        Err(last_error.unwrap_or_else(|| ErrorKind::RpcError.into()))
+    }
+}
+
+fn should_fail_over(error: &ErrorKind) -> bool {
+    matches!(*error, ErrorKind::RpcError | ErrorKind::InvalidResponse)
+}
@@ -0,0 +1,145 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A minimal blocking HTTP client, shared by the plain HTTP, HTTPS and Unix domain socket
+//! transports. Every request is sent over a new connection as an HTTP/1.0 request, so the
+//! response ends when the server closes the connection.
+
+use httparse;
+
+use super::MAX_RESPONSE_LEN;
+use super::rpc::{ErrorKind, Result, ResultExt};
+
+use std::io::{Read, Write};
+use std::str;
+
+const MAX_HEADERS: usize = 32;
+
+/// Sends `body` as a JSON POST request to `path` over `stream`, and returns the body of the
+/// response. `host` is the value of the `Host` header. Fails if the response is larger than
+/// `MAX_RESPONSE_LEN`.
+pub fn post<S>(mut stream: S, host: &str, path: &str, body: &[u8]) -> Result<Vec<u8>>
+where
+    S: Read + Write,
+{
+    let mut response = Vec::new();
+    write!(
+        stream,
+        "POST {} HTTP/1.0\r\nHost: {}\r\nContent-Type: application/json\r\n\
+         Content-Length: {}\r\n\r\n",
+        path,
+        host,
+        body.len()
+    ).and_then(|_| stream.write_all(body))
+        .and_then(|_| stream.flush())
+        .and_then(|_| {
+            Read::by_ref(&mut stream)
+                .take(MAX_RESPONSE_LEN as u64 + 1)
+                .read_to_end(&mut response)
+        })
+        .chain_err(|| ErrorKind::TransportError)?;
+    ensure!(
+        response.len() <= MAX_RESPONSE_LEN,
+        ErrorKind::TransportError
+    );
+    parse_response(&response)
+}
+
+/// Returns the body of a successful HTTP response.
+fn parse_response(response: &[u8]) -> Result<Vec<u8>> {
+    let mut headers = [httparse::EMPTY_HEADER; MAX_HEADERS];
+    let mut parsed = httparse::Response::new(&mut headers);
+    let body_start = match parsed.parse(response) {
+        Ok(httparse::Status::Complete(len)) => len,
+        _ => bail!(ErrorKind::TransportError),
+    };
+    match parsed.code {
+        Some(200) => (),
+        Some(status) => bail!(ErrorKind::HttpStatusError(status)),
+        None => bail!(ErrorKind::TransportError),
+    }
+
+    let body = &response[body_start..];
+    let content_length = parsed
+        .headers
+        .iter()
+        .find(|header| header.name.eq_ignore_ascii_case("Content-Length"))
+        .map(|header| {
+            str::from_utf8(header.value)
+                .ok()
+                .and_then(|value| value.trim().parse::<usize>().ok())
+                .ok_or(ErrorKind::TransportError)
+        });
+    match content_length {
+        Some(content_length) => {
+            let content_length = content_length?;
+            ensure!(body.len() >= content_length, ErrorKind::TransportError);
+            Ok(body[..content_length].to_vec())
+        }
+        None => Ok(body.to_vec()),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::rpc::Error;
+    use std::io;
+
+    /// A server answering with an endless response.
+    struct EndlessStream;
+
+    impl Read for EndlessStream {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            io::repeat(b'x').read(buf)
+        }
+    }
+
+    impl Write for EndlessStream {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn reject_too_large_response() {
+        assert!(post(EndlessStream, "localhost", "/", b"{}").is_err());
+    }
+
+    #[test]
+    fn parse_body_after_headers() {
+        let response = b"HTTP/1.0 200 OK\r\nContent-Type: application/json\r\n\r\n{}";
+        assert_eq!(parse_response(response).unwrap(), b"{}");
+    }
+
+    #[test]
+    fn parse_body_up_to_content_length() {
+        let response = b"HTTP/1.0 200 OK\r\nContent-Length: 2\r\n\r\n{}garbage";
+        assert_eq!(parse_response(response).unwrap(), b"{}");
+    }
+
+    #[test]
+    fn parse_truncated_body() {
+        let response = b"HTTP/1.0 200 OK\r\nContent-Length: 10\r\n\r\n{}";
+        assert!(parse_response(response).is_err());
+    }
+
+    #[test]
+    fn parse_http_error() {
+        let response = b"HTTP/1.0 413 Payload Too Large\r\n\r\n";
+        match parse_response(response) {
+            Err(Error(ErrorKind::HttpStatusError(413), _)) => (),
+            other => panic!("Unexpected result: {:?}", other.map(|_| ())),
+        }
+    }
+}
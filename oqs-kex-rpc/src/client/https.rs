@@ -0,0 +1,140 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A blocking HTTPS transport for the RPC client, using rustls.
+
+use futures::{self, Future};
+use rustls::{ClientConfig, ClientSession, Session};
+
+use super::Timeouts;
+use super::deadline::{self, DeadlineStream};
+use super::http;
+use super::tcp;
+use super::rpc::{Error, ErrorKind, Result, ResultExt};
+use tls::ClientTlsConfig;
+
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::sync::Arc;
+
+use jsonrpc_http_server::hyper::Uri;
+
+const DEFAULT_PORT: u16 = 443;
+
+/// Sends JSON-RPC requests to one server over HTTPS.
+pub struct HttpsHandle {
+    host: String,
+    port: u16,
+    path: String,
+    config: Arc<ClientConfig>,
+    timeouts: Timeouts,
+    next_id: u64,
+}
+
+impl HttpsHandle {
+    pub fn new(server_uri: &str, tls_config: &ClientTlsConfig, timeouts: Timeouts) -> Result<Self> {
+        let uri: Uri = server_uri.parse().chain_err(|| ErrorKind::RpcInitError)?;
+        ensure!(uri.scheme() == Some("https"), ErrorKind::RpcInitError);
+        let host = uri.host().ok_or(ErrorKind::RpcInitError)?;
+        Ok(HttpsHandle {
+            host: host.to_owned(),
+            port: uri.port().unwrap_or(DEFAULT_PORT),
+            path: uri.path().to_owned(),
+            config: tls_config.config.clone(),
+            timeouts,
+            next_id: 0,
+        })
+    }
+
+    pub fn get_next_id(&mut self) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    pub fn send(
+        &self,
+        json_data: Vec<u8>,
+    ) -> Box<dyn Future<Item = Vec<u8>, Error = Error> + Send> {
+        Box::new(futures::future::result(self.post(&json_data)))
+    }
+
+    fn post(&self, body: &[u8]) -> Result<Vec<u8>> {
+        let socket = tcp::connect(&self.host, self.port, &self.timeouts)?;
+        let socket = DeadlineStream::new(socket, deadline::after(self.timeouts.request));
+        let session = ClientSession::new(&self.config, &self.host);
+        let stream = TlsStream::new(session, socket);
+        let host = format!("{}:{}", self.host, self.port);
+        http::post(stream, &host, &self.path, body)
+    }
+}
+
+
+/// A blocking TLS connection. The deadline is enforced on the socket, so it covers every socket
+/// read a single read from the connection may need.
+struct TlsStream {
+    session: ClientSession,
+    socket: DeadlineStream<TcpStream>,
+    eof: bool,
+}
+
+impl TlsStream {
+    fn new(session: ClientSession, socket: DeadlineStream<TcpStream>) -> Self {
+        TlsStream {
+            session,
+            socket,
+            eof: false,
+        }
+    }
+
+    /// Sends all TLS records the session has queued.
+    fn write_tls(&mut self) -> io::Result<()> {
+        while self.session.wants_write() {
+            self.session.write_tls(&mut self.socket)?;
+        }
+        Ok(())
+    }
+}
+
+impl Read for TlsStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            self.write_tls()?;
+            match self.session.read(buf) {
+                Ok(0) => (),
+                Ok(len) => return Ok(len),
+                // The server sent close_notify.
+                Err(ref e) if e.kind() == io::ErrorKind::ConnectionAborted => return Ok(0),
+                Err(e) => return Err(e),
+            }
+            if self.eof {
+                return Ok(0);
+            }
+            if self.session.read_tls(&mut self.socket)? == 0 {
+                self.eof = true;
+            }
+            self.session
+                .process_new_packets()
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        }
+    }
+}
+
+impl Write for TlsStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = self.session.write(buf)?;
+        self.write_tls()?;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.session.flush()?;
+        self.write_tls()
+    }
+}
+
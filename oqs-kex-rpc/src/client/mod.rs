@@ -7,42 +7,156 @@
 // except according to those terms.
 
 use oqs;
-use oqs::kex::{AliceMsg, BobMsg, OqsKex, OqsKexAlg, OqsKexAlice, SharedKey};
+use oqs::kex::{batch, AliceMsg, AliceState, BobMsg, OqsKexAlg, SharedKey};
 use oqs::rand::{OqsRand, OqsRandAlg};
+use oqs::sig::{OqsSig, PublicKey, Signature};
 
-use jsonrpc_client_http::HttpHandle;
+use confirm::{self, Role};
+use error_codes;
+use messages::{Capabilities, ConstraintViolation, KexResponse};
+use transcript::{self, ExchangedKeys, Transcript, TranscriptHash};
 
+use jsonrpc_client_core;
+use serde_json;
+
+#[cfg(feature = "tls")]
+use tls::ClientTlsConfig;
+
+#[cfg(unix)]
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+mod builder;
+mod deadline;
+mod failover;
+mod http;
+#[cfg(feature = "tls")]
+mod https;
+mod retry;
 mod rpc;
+mod tcp;
+mod transport;
+#[cfg(unix)]
+mod unix;
+mod websocket;
+
+pub use self::builder::OqsKexClientBuilder;
+pub use self::failover::{FailoverClient, FailoverKeys};
+pub use self::retry::RetryPolicy;
+pub use self::transport::{HttpTransport, LoopbackTransport, Transport, WebSocketTransport};
 
 error_chain! {
     errors {
-        /// There was an error in the network communication or on the key exchange server.
+        /// There was an error in the network communication, a request timed out, or the server
+        /// failed for a reason not covered by the other error kinds.
         RpcError { description("RPC client returned an error") }
+        /// The server rejected the request since it does not meet the server's constraints.
+        ConstraintError(violation: ConstraintViolation) {
+            description("Request does not meet the server's constraints")
+            display("Request does not meet the server's constraints: {}", violation)
+        }
+        /// The cryptographic operations in `oqs` failed on the server.
+        ServerOqsError { description("OQS returned an error on the server") }
+        /// The server failed to handle the exchanged keys.
+        CallbackError { description("Server failed to handle the exchanged keys") }
+        /// The server is too busy to perform the key exchange. It can be retried later.
+        ServerBusy { description("Server is too busy to perform the key exchange") }
+        /// The client has used up its rate limit on the server. It can be retried later.
+        RateLimited { description("Client exceeded its rate limit on the server") }
         /// The server responded, but the returned messages don't match our request.
         InvalidResponse { description("RPC response is syntactically valid but unexpected") }
         /// There was an error in the cryptographic operations in `oqs`.
         OqsError { description("OQS returned an error") }
+        /// The server's key confirmation MAC did not match the keys computed by the client, or the
+        /// server rejected the client's confirmation.
+        ConfirmationError { description("Key confirmation failed") }
+        /// The server did not prove that it holds the secret key matching the pinned public key.
+        AuthenticationError { description("Unable to authenticate server") }
+        /// None of the preferred algorithms are accepted by the server.
+        NegotiationError { description("No mutually supported algorithms") }
     }
 }
 
-/// The key exchange client.
-pub struct OqsKexClient {
-    rpc_client: rpc::OqsKexRpcClient<HttpHandle>,
+/// Max size of a response the client reads from a server. A server sending more is treated as a
+/// transport failure instead of being read into memory.
+pub(crate) const MAX_RESPONSE_LEN: usize = 16 * 1024 * 1024;
+
+/// The key exchange client. Sends its requests over the transport `T`, which is HTTP unless the
+/// client is created with [`with_transport`](#method.with_transport).
+pub struct OqsKexClient<T: Transport = HttpTransport> {
+    rpc_client: rpc::OqsKexRpcClient<T>,
     rand: OqsRandAlg,
+    server_key: Option<PublicKey>,
+    retry_policy: RetryPolicy,
+}
+
+/// The timeouts of the transports created by the client.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Timeouts {
+    pub connect: Option<Duration>,
+    pub request: Option<Duration>,
 }
 
-impl OqsKexClient {
+impl OqsKexClient<HttpTransport> {
+    /// Returns a builder for a client with timeouts, retries or other settings. The clients
+    /// created with the other constructors have no timeouts and don't retry failed requests.
+    pub fn builder() -> OqsKexClientBuilder {
+        OqsKexClientBuilder::new()
+    }
+
     /// Connects to the given address and returns a client instance.
     pub fn new(server_uri: &str) -> Result<Self> {
-        let rpc_client =
-            rpc::OqsKexRpcClient::connect(server_uri).chain_err(|| ErrorKind::RpcError)?;
+        Self::builder().build(server_uri)
+    }
 
-        let client = OqsKexClient {
-            rpc_client,
-            rand: OqsRandAlg::default(),
-        };
+    /// Connects to the given `https` address and returns a client instance. The server's
+    /// certificate has to be signed by one of the CA certificates in `tls_config`. Requires the
+    /// `tls` feature.
+    #[cfg(feature = "tls")]
+    pub fn with_tls(server_uri: &str, tls_config: &ClientTlsConfig) -> Result<Self> {
+        Self::builder().build_tls(server_uri, tls_config)
+    }
+
+    /// Returns a client instance sending its requests to a server listening on the Unix domain
+    /// socket at `path`. The socket is connected to for every request, so this does not fail if
+    /// the server is not running yet.
+    #[cfg(unix)]
+    pub fn with_unix_socket<P: AsRef<Path>>(path: P) -> Self {
+        Self::builder().build_unix_socket(path)
+    }
+
+    /// Connects to the given address and returns a client instance that authenticates the
+    /// server. Every exchange has to be signed by the secret key belonging to `server_key`,
+    /// otherwise it fails with an [`AuthenticationError`].
+    ///
+    /// [`AuthenticationError`]: enum.ErrorKind.html#variant.AuthenticationError
+    pub fn with_server_key(server_uri: &str, server_key: PublicKey) -> Result<Self> {
+        Self::builder().server_key(server_key).build(server_uri)
+    }
+}
 
-        Ok(client)
+impl OqsKexClient<WebSocketTransport> {
+    /// Returns a client instance sending its requests over a WebSocket connection to the given
+    /// `ws` address. The connection is opened by the first request, and then used for all
+    /// requests, so several exchanges can be done over it.
+    pub fn with_websocket(server_uri: &str) -> Result<Self> {
+        OqsKexClient::builder().build_websocket(server_uri)
+    }
+}
+
+impl<T: Transport> OqsKexClient<T> {
+    /// Returns a client instance sending its requests over `transport`. Use a
+    /// [`LoopbackTransport`] to exchange keys with a server in the same process.
+    ///
+    /// [`LoopbackTransport`]: struct.LoopbackTransport.html
+    pub fn with_transport(transport: T) -> Self {
+        OqsKexClient::builder().build_with_transport(transport)
+    }
+
+    /// Pins the server's public key, like [`with_server_key`](#method.with_server_key) does.
+    pub fn set_server_key(&mut self, server_key: PublicKey) {
+        self.server_key = Some(server_key);
     }
 
     /// Configure which PRNG algorithm this client should use to source its entropy.
@@ -50,65 +164,278 @@ impl OqsKexClient {
         self.rand = rand;
     }
 
+    /// Configure how failed requests are retried, like
+    /// [`OqsKexClientBuilder::retry_policy`](struct.OqsKexClientBuilder.html#method.retry_policy)
+    /// does.
+    pub fn set_retry_policy(&mut self, retry_policy: RetryPolicy) {
+        self.retry_policy = retry_policy;
+    }
+
+    /// Asks the server which algorithms it accepts and what limits it puts on key exchange
+    /// requests.
+    pub fn capabilities(&mut self) -> Result<Capabilities> {
+        self.retry(|client| {
+            client
+                .rpc_client
+                .capabilities()
+                .call()
+                .map_err(rpc_error)
+        })
+    }
+
     /// Performs a full key exchange with all the algorithms in `algs` at the same time.
     ///
     /// This will compute Alice's message for each given algorithm, and send them in one RPC
     /// call to the server. The server will then compute the corresponding shared keys and Bob's
     /// messages. Then the server return Bob's messages and this client finally computes
-    /// the shared keys and returns them.
+    /// the shared keys and returns them. The computations for the different algorithms are
+    /// performed in parallel. Failed exchanges are retried according to the client's
+    /// [`RetryPolicy`], with new Alice messages for every attempt.
     ///
-    /// The returned vector has the same length as `algs` and the [`SharedKey`] at position `n`
-    /// corresponds to the [`OqsKexAlg`] at position `n` in `algs`.
+    /// The returned keys have the same length as `algs` and the [`SharedKey`] at position `n`
+    /// corresponds to the [`OqsKexAlg`] at position `n` in `algs`. They are returned together
+    /// with the transcript hash over all exchanged messages, see [`ExchangedKeys`].
     ///
-    /// [`SharedKey`]: struct.SharedKey.html
-    /// [`OqsKexAlg`]: struct.OqsKexAlg.html
-    pub fn kex(&mut self, algs: &[OqsKexAlg]) -> Result<Vec<SharedKey>> {
-        let rand = OqsRand::new(self.rand).chain_err(|| ErrorKind::OqsError)?;
-        let kexs = Self::init_kex(&rand, algs)?;
-        let alice_kexs = Self::alice_0(&kexs)?;
-        let bob_msgs = self.perform_rpc(&alice_kexs)?;
-        ensure!(
-            alice_kexs.len() == bob_msgs.len(),
-            ErrorKind::InvalidResponse
-        );
-        for (alice_kex, bob_msg) in alice_kexs.iter().zip(bob_msgs.iter()) {
-            ensure!(
-                alice_kex.algorithm() == bob_msg.algorithm(),
-                ErrorKind::InvalidResponse
-            )
+    /// [`SharedKey`]: ../struct.SharedKey.html
+    /// [`OqsKexAlg`]: ../enum.OqsKexAlg.html
+    /// [`ExchangedKeys`]: ../transcript/struct.ExchangedKeys.html
+    /// [`RetryPolicy`]: struct.RetryPolicy.html
+    pub fn kex(&mut self, algs: &[OqsKexAlg]) -> Result<ExchangedKeys> {
+        self.retry(|client| client.try_kex(algs))
+    }
+
+    /// Performs a full key exchange with at most `max` of the algorithms from `preferences` that
+    /// the server accepts.
+    ///
+    /// `preferences` is ordered from most to least preferred. The server's [`capabilities`] are
+    /// fetched first, and the algorithms to use are picked with [`Capabilities::select`], from the
+    /// front of `preferences`. Pass one as `max` to use only the most preferred algorithm. If the
+    /// server requires key confirmation, the exchange is done with
+    /// [`kex_with_confirmation`](#method.kex_with_confirmation). Use
+    /// [`SharedKey::algorithm`] to see which algorithms the returned keys were exchanged with.
+    ///
+    /// [`capabilities`]: #method.capabilities
+    /// [`Capabilities::select`]: ../struct.Capabilities.html#method.select
+    /// [`SharedKey::algorithm`]: ../struct.SharedKey.html#method.algorithm
+    pub fn kex_negotiated(
+        &mut self,
+        preferences: &[OqsKexAlg],
+        max: usize,
+    ) -> Result<ExchangedKeys> {
+        let capabilities = self.capabilities()?;
+        let algs = capabilities.select(preferences, max);
+        ensure!(!algs.is_empty(), ErrorKind::NegotiationError);
+        if capabilities.require_confirmation {
+            self.kex_with_confirmation(&algs)
+        } else {
+            self.kex(&algs)
         }
-        Self::alice_1(alice_kexs, &bob_msgs)
     }
 
-    fn init_kex<'r>(rand: &'r OqsRand, algs: &[OqsKexAlg]) -> Result<Vec<OqsKex<'r>>> {
-        algs.iter()
-            .map(|alg| OqsKex::new(&rand, *alg))
-            .collect::<oqs::kex::Result<_>>()
-            .chain_err(|| ErrorKind::OqsError)
+    /// Performs a full key exchange like [`kex`](#method.kex), followed by a key confirmation
+    /// round.
+    ///
+    /// Together with Bob's messages the server returns a MAC over all exchanged messages, keyed
+    /// by its shared keys. The client verifies it and replies with a MAC of its own in a second
+    /// RPC call. The server only hands the keys to its `on_kex` callback after verifying the
+    /// client's MAC. So when this method returns successfully, both sides are known to hold the
+    /// same keys.
+    ///
+    /// The [`RetryPolicy`] applies to the two RPC calls separately. A failed first call is retried
+    /// with new Alice messages, since the server does not hand the keys to `on_kex` before they
+    /// are confirmed. A failed confirmation is retried with the same exchange, so the server calls
+    /// `on_kex` at most once. If a confirmation reached the server but its response was lost, the
+    /// retry fails with a [`ConfirmationError`], since every exchange can only be confirmed once.
+    ///
+    /// [`RetryPolicy`]: struct.RetryPolicy.html
+    /// [`ConfirmationError`]: enum.ErrorKind.html#variant.ConfirmationError
+    pub fn kex_with_confirmation(&mut self, algs: &[OqsKexAlg]) -> Result<ExchangedKeys> {
+        let (exchanged_keys, session_id) =
+            self.retry(|client| client.try_begin_kex_with_confirmation(algs))?;
+        let client_mac = confirm::mac(
+            Role::Client,
+            &exchanged_keys.keys,
+            &exchanged_keys.transcript_hash,
+        );
+        self.retry(|client| {
+            client
+                .rpc_client
+                .confirm(session_id, &client_mac)
+                .call()
+                .map_err(rpc_error)
+        })?;
+        Ok(exchanged_keys)
     }
 
-    fn alice_0<'a, 'r>(kexs: &'a [OqsKex<'r>]) -> Result<Vec<OqsKexAlice<'a, 'r>>> {
-        kexs.iter()
-            .map(|kex| kex.alice_0())
-            .collect::<oqs::kex::Result<_>>()
-            .chain_err(|| ErrorKind::OqsError)
+    /// Calls `attempt` until it succeeds, or fails with an error the retry policy does not retry.
+    fn retry<R, F>(&mut self, mut attempt: F) -> Result<R>
+    where
+        F: FnMut(&mut Self) -> Result<R>,
+    {
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            match attempt(self) {
+                Err(ref error) if self.retry_policy.should_retry(attempts, error.kind()) => {
+                    let backoff = self.retry_policy.backoff(attempts - 1);
+                    debug!("Retrying in {:?} after error: {}", backoff, error);
+                    thread::sleep(backoff);
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Performs the first RPC call of a key exchange with confirmation, and checks the server's
+    /// MAC. Returns the keys together with the session ID to confirm them with.
+    fn try_begin_kex_with_confirmation(
+        &mut self,
+        algs: &[OqsKexAlg],
+    ) -> Result<(ExchangedKeys, u64)> {
+        let rand = OqsRand::new(self.rand).chain_err(|| ErrorKind::OqsError)?;
+        let alices = alice_0(&rand, algs)?;
+        let response = {
+            let alice_msgs: Vec<&AliceMsg> =
+                alices.iter().map(AliceState::get_alice_msg).collect();
+            self.rpc_client
+                .kex_with_confirmation(&alice_msgs)
+                .call()
+                .map_err(rpc_error)?
+        };
+        check_response(&alices, &response.bob_msgs)?;
+        let transcript_hash = transcript_hash(&alices, &response.bob_msgs);
+        authenticate(
+            self.server_key.as_ref(),
+            &rand,
+            &transcript_hash,
+            response.signature.as_ref(),
+        )?;
+        let keys = alice_1(&rand, alices, &response.bob_msgs)?;
+
+        ensure!(
+            confirm::verify(
+                Role::Server,
+                &keys,
+                &transcript_hash,
+                &response.server_mac,
+            ),
+            ErrorKind::ConfirmationError
+        );
+        let exchanged_keys = ExchangedKeys {
+            keys,
+            transcript_hash,
+        };
+        Ok((exchanged_keys, response.session_id))
     }
 
-    fn alice_1(alice_kexs: Vec<OqsKexAlice>, bob_msgs: &[BobMsg]) -> Result<Vec<SharedKey>> {
-        alice_kexs
-            .into_iter()
-            .zip(bob_msgs)
-            .map(|(alice_kex, bob_msg)| alice_kex.alice_1(&bob_msg))
-            .collect::<oqs::kex::Result<_>>()
-            .chain_err(|| ErrorKind::OqsError)
+    fn try_kex(&mut self, algs: &[OqsKexAlg]) -> Result<ExchangedKeys> {
+        let rand = OqsRand::new(self.rand).chain_err(|| ErrorKind::OqsError)?;
+        let alices = alice_0(&rand, algs)?;
+        let response = self.perform_rpc(&alices)?;
+        check_response(&alices, &response.bob_msgs)?;
+        let transcript_hash = transcript_hash(&alices, &response.bob_msgs);
+        authenticate(
+            self.server_key.as_ref(),
+            &rand,
+            &transcript_hash,
+            response.signature.as_ref(),
+        )?;
+        let keys = alice_1(&rand, alices, &response.bob_msgs)?;
+        Ok(ExchangedKeys {
+            keys,
+            transcript_hash,
+        })
     }
 
-    fn perform_rpc(&mut self, alice_kexs: &[OqsKexAlice]) -> Result<Vec<BobMsg>> {
-        let alice_msgs: Vec<&AliceMsg> =
-            alice_kexs.iter().map(OqsKexAlice::get_alice_msg).collect();
+    fn perform_rpc(&mut self, alices: &[AliceState]) -> Result<KexResponse> {
+        let alice_msgs: Vec<&AliceMsg> = alices.iter().map(AliceState::get_alice_msg).collect();
         self.rpc_client
             .kex(&alice_msgs)
             .call()
-            .chain_err(|| ErrorKind::RpcError)
+            .map_err(rpc_error)
+    }
+}
+
+pub(crate) fn transcript_hash(alices: &[AliceState], bob_msgs: &[BobMsg]) -> TranscriptHash {
+    let alice_msgs: Vec<&AliceMsg> = alices.iter().map(AliceState::get_alice_msg).collect();
+    Transcript::from_msgs(&alice_msgs, bob_msgs).hash()
+}
+
+/// Verifies the server's signature over the transcript hash, if the client has a pinned server
+/// key.
+pub(crate) fn authenticate(
+    server_key: Option<&PublicKey>,
+    rand: &OqsRand,
+    transcript_hash: &TranscriptHash,
+    signature: Option<&Signature>,
+) -> Result<()> {
+    if let Some(server_key) = server_key {
+        let signature = signature.ok_or(ErrorKind::AuthenticationError)?;
+        let sig = OqsSig::new(rand, server_key.algorithm()).chain_err(|| ErrorKind::OqsError)?;
+        let message = transcript::signed_message(transcript_hash);
+        sig.verify(server_key, &message, signature)
+            .chain_err(|| ErrorKind::AuthenticationError)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn check_response(alices: &[AliceState], bob_msgs: &[BobMsg]) -> Result<()> {
+    ensure!(alices.len() == bob_msgs.len(), ErrorKind::InvalidResponse);
+    for (alice, bob_msg) in alices.iter().zip(bob_msgs.iter()) {
+        ensure!(
+            alice.algorithm() == bob_msg.algorithm(),
+            ErrorKind::InvalidResponse
+        )
+    }
+    Ok(())
+}
+
+pub(crate) fn alice_0(rand: &OqsRand, algs: &[OqsKexAlg]) -> Result<Vec<AliceState>> {
+    batch::alice_0(rand, algs)
+        .into_iter()
+        .collect::<oqs::kex::Result<_>>()
+        .chain_err(|| ErrorKind::OqsError)
+}
+
+pub(crate) fn alice_1(
+    rand: &OqsRand,
+    alices: Vec<AliceState>,
+    bob_msgs: &[BobMsg],
+) -> Result<Vec<SharedKey>> {
+    batch::alice_1(rand, alices, bob_msgs)
+        .into_iter()
+        .collect::<oqs::kex::Result<_>>()
+        .chain_err(|| ErrorKind::OqsError)
+}
+
+/// Turns an error from an RPC call into an `Error`, with the error kind given by the error code
+/// the server responded with.
+fn rpc_error(error: jsonrpc_client_core::Error) -> Error {
+    let kind = match *error.kind() {
+        jsonrpc_client_core::ErrorKind::JsonRpcError(ref json_error) => {
+            let violation = json_error
+                .data
+                .clone()
+                .and_then(|data| serde_json::from_value(data).ok());
+            server_error_kind(json_error.code.code(), violation)
+        }
+        _ => ErrorKind::RpcError,
+    };
+    Error::with_chain(error, kind)
+}
+
+/// Returns the error kind for an error code the server responded with. `violation` is the
+/// constraint violation sent together with a `CONSTRAINT_ERROR`.
+pub(crate) fn server_error_kind(code: i64, violation: Option<ConstraintViolation>) -> ErrorKind {
+    match code {
+        error_codes::CONSTRAINT_ERROR => violation
+            .map(ErrorKind::ConstraintError)
+            .unwrap_or(ErrorKind::InvalidResponse),
+        error_codes::OQS_ERROR => ErrorKind::ServerOqsError,
+        error_codes::CALLBACK_ERROR => ErrorKind::CallbackError,
+        error_codes::CONFIRMATION_ERROR => ErrorKind::ConfirmationError,
+        error_codes::BUSY_ERROR => ErrorKind::ServerBusy,
+        error_codes::RATE_LIMIT_ERROR => ErrorKind::RateLimited,
+        _ => ErrorKind::RpcError,
     }
 }
@@ -0,0 +1,113 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use super::ErrorKind;
+
+use std::time::Duration;
+
+/// How an [`OqsKexClient`] retries requests that fail with errors that may go away by
+/// themselves: [`RpcError`], including timeouts, [`ServerBusy`] and [`RateLimited`].
+///
+/// Failed key exchanges are retried from the start, with new Alice messages, except for the
+/// confirmation of [`OqsKexClient::kex_with_confirmation`], which is retried on its own. The
+/// client waits `initial_backoff` before the first retry, and twice as long before every
+/// following retry, but never longer than `max_backoff`.
+///
+/// [`OqsKexClient`]: struct.OqsKexClient.html
+/// [`OqsKexClient::kex_with_confirmation`]:
+/// struct.OqsKexClient.html#method.kex_with_confirmation
+/// [`RpcError`]: enum.ErrorKind.html#variant.RpcError
+/// [`ServerBusy`]: enum.ErrorKind.html#variant.ServerBusy
+/// [`RateLimited`]: enum.ErrorKind.html#variant.RateLimited
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Max number of attempts, including the first one. Zero is treated as one.
+    pub max_attempts: u32,
+    /// Time to wait before the first retry.
+    pub initial_backoff: Duration,
+    /// Max time to wait between two attempts.
+    pub max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Creates a policy making at most `max_attempts` attempts, with backoff starting at
+    /// `initial_backoff` and capped at 30 seconds.
+    pub fn new(max_attempts: u32, initial_backoff: Duration) -> Self {
+        RetryPolicy {
+            max_attempts,
+            initial_backoff,
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+
+    /// Returns a policy that never retries. This is the default.
+    pub fn never() -> Self {
+        Self::new(1, Duration::from_secs(0))
+    }
+
+    /// Returns whether another attempt should be made after `attempts` attempts failed with
+    /// `error`.
+    pub(crate) fn should_retry(&self, attempts: u32, error: &ErrorKind) -> bool {
+        attempts < self.max_attempts && is_transient(error)
+    }
+
+    /// Returns how long to wait before retry number `retry`, counted from zero.
+    pub(crate) fn backoff(&self, retry: u32) -> Duration {
+        1u32.checked_shl(retry)
+            .and_then(|factor| self.initial_backoff.checked_mul(factor))
+            .map_or(self.max_backoff, |backoff| backoff.min(self.max_backoff))
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::never()
+    }
+}
+
+fn is_transient(error: &ErrorKind) -> bool {
+    matches!(
+        *error,
+        ErrorKind::RpcError | ErrorKind::ServerBusy | ErrorKind::RateLimited
+    )
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_up_to_max() {
+        let policy = RetryPolicy {
+            max_attempts: 10,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(500),
+        };
+        assert_eq!(policy.backoff(0), Duration::from_millis(100));
+        assert_eq!(policy.backoff(1), Duration::from_millis(200));
+        assert_eq!(policy.backoff(2), Duration::from_millis(400));
+        assert_eq!(policy.backoff(3), Duration::from_millis(500));
+        assert_eq!(policy.backoff(100), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn retry_transient_errors_only() {
+        let policy = RetryPolicy::new(3, Duration::from_millis(1));
+        assert!(policy.should_retry(1, &ErrorKind::ServerBusy));
+        assert!(policy.should_retry(2, &ErrorKind::RpcError));
+        assert!(!policy.should_retry(3, &ErrorKind::RpcError));
+        assert!(!policy.should_retry(1, &ErrorKind::AuthenticationError));
+        assert!(!policy.should_retry(1, &ErrorKind::InvalidResponse));
+    }
+
+    #[test]
+    fn never_retry_by_default() {
+        assert!(!RetryPolicy::default().should_retry(1, &ErrorKind::ServerBusy));
+    }
+}
@@ -6,25 +6,26 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use jsonrpc_client_http::{HttpHandle, HttpTransport};
-use oqs::kex::{AliceMsg, BobMsg};
+use messages::{Capabilities, KexResponse, UnconfirmedKex};
+use oqs::kex::AliceMsg;
 
 error_chain! {
     errors {
         RpcInitError { description("RPC client could not be initialized") }
+        /// Sending a request or receiving its response failed.
+        TransportError { description("Error in the RPC transport") }
+        /// The server responded with an HTTP status other than 200.
+        HttpStatusError(status: u16) {
+            description("Server responded with an HTTP error")
+            display("Server responded with HTTP status {}", status)
+        }
     }
 }
 
 jsonrpc_client!(pub struct OqsKexRpcClient {
-    pub fn kex(&mut self, alice_msgs: &[&AliceMsg]) -> RpcRequest<Vec<BobMsg>>;
+    pub fn kex(&mut self, alice_msgs: &[&AliceMsg]) -> RpcRequest<KexResponse>;
+    pub fn kex_with_confirmation(&mut self, alice_msgs: &[&AliceMsg])
+        -> RpcRequest<UnconfirmedKex>;
+    pub fn confirm(&mut self, session_id: u64, client_mac: &[u8]) -> RpcRequest<()>;
+    pub fn capabilities(&mut self) -> RpcRequest<Capabilities>;
 });
-
-impl OqsKexRpcClient<HttpHandle> {
-    pub fn connect(server_uri: &str) -> Result<Self> {
-        let transport = HttpTransport::new().chain_err(|| ErrorKind::RpcInitError)?;
-        let transport_handle = transport
-            .handle(server_uri)
-            .chain_err(|| ErrorKind::RpcInitError)?;
-        Ok(OqsKexRpcClient::new(transport_handle))
-    }
-}
@@ -0,0 +1,91 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Connecting to servers with the client's timeouts, and a blocking plain HTTP transport using
+//! them.
+
+use futures::{self, Future};
+
+use super::Timeouts;
+use super::deadline::{self, DeadlineStream};
+use super::http;
+use super::rpc::{Error, ErrorKind, Result, ResultExt};
+
+use std::io;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use jsonrpc_http_server::hyper::Uri;
+
+const DEFAULT_PORT: u16 = 80;
+
+/// Connects to `host`. Tries every address `host` resolves to, each within the connect timeout.
+pub fn connect(host: &str, port: u16, timeouts: &Timeouts) -> Result<TcpStream> {
+    match timeouts.connect {
+        Some(timeout) => connect_timeout(host, port, timeout),
+        None => TcpStream::connect((host, port)),
+    }.chain_err(|| ErrorKind::TransportError)
+}
+
+fn connect_timeout(host: &str, port: u16, timeout: Duration) -> io::Result<TcpStream> {
+    let mut last_error = None;
+    for addr in (host, port).to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, timeout) {
+            Ok(stream) => return Ok(stream),
+            Err(error) => last_error = Some(error),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidInput, "Host resolved to no addresses")
+    }))
+}
+
+
+/// Sends JSON-RPC requests to one server over plain HTTP, connecting for every request.
+pub struct TcpHandle {
+    host: String,
+    port: u16,
+    path: String,
+    timeouts: Timeouts,
+    next_id: u64,
+}
+
+impl TcpHandle {
+    pub fn new(server_uri: &str, timeouts: Timeouts) -> Result<Self> {
+        let uri: Uri = server_uri.parse().chain_err(|| ErrorKind::RpcInitError)?;
+        ensure!(uri.scheme() == Some("http"), ErrorKind::RpcInitError);
+        let host = uri.host().ok_or(ErrorKind::RpcInitError)?;
+        Ok(TcpHandle {
+            host: host.to_owned(),
+            port: uri.port().unwrap_or(DEFAULT_PORT),
+            path: uri.path().to_owned(),
+            timeouts,
+            next_id: 0,
+        })
+    }
+
+    pub fn get_next_id(&mut self) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    pub fn send(
+        &self,
+        json_data: Vec<u8>,
+    ) -> Box<dyn Future<Item = Vec<u8>, Error = Error> + Send> {
+        Box::new(futures::future::result(self.post(&json_data)))
+    }
+
+    fn post(&self, body: &[u8]) -> Result<Vec<u8>> {
+        let socket = connect(&self.host, self.port, &self.timeouts)?;
+        let stream = DeadlineStream::new(socket, deadline::after(self.timeouts.request));
+        let host = format!("{}:{}", self.host, self.port);
+        http::post(stream, &host, &self.path, body)
+    }
+}
@@ -0,0 +1,163 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The transports the client can send its JSON-RPC requests over.
+
+use futures::{self, Future};
+
+use super::Timeouts;
+use super::rpc::{Error, ErrorKind, Result, ResultExt};
+#[cfg(feature = "tls")]
+use super::https::HttpsHandle;
+use super::tcp::TcpHandle;
+#[cfg(unix)]
+use super::unix::UnixHandle;
+use super::websocket::WebSocketHandle;
+use server::{LoopbackServer, Metadata};
+#[cfg(feature = "tls")]
+use tls::ClientTlsConfig;
+
+#[cfg(unix)]
+use std::path::Path;
+use std::str;
+
+pub use jsonrpc_client_core::Transport;
+
+
+/// The transport clients use unless created with another one. Sends the requests over HTTP, HTTPS
+/// or a Unix domain socket, depending on how the client was created.
+pub struct HttpTransport(Handle);
+
+enum Handle {
+    Tcp(TcpHandle),
+    #[cfg(feature = "tls")]
+    Https(HttpsHandle),
+    #[cfg(unix)]
+    Unix(UnixHandle),
+}
+
+impl HttpTransport {
+    pub(crate) fn http(server_uri: &str, timeouts: Timeouts) -> Result<Self> {
+        let transport_handle = TcpHandle::new(server_uri, timeouts)?;
+        Ok(HttpTransport(Handle::Tcp(transport_handle)))
+    }
+
+    #[cfg(feature = "tls")]
+    pub(crate) fn https(
+        server_uri: &str,
+        tls_config: &ClientTlsConfig,
+        timeouts: Timeouts,
+    ) -> Result<Self> {
+        let transport_handle = HttpsHandle::new(server_uri, tls_config, timeouts)?;
+        Ok(HttpTransport(Handle::Https(transport_handle)))
+    }
+
+    #[cfg(unix)]
+    pub(crate) fn unix(path: &Path, timeouts: Timeouts) -> Self {
+        HttpTransport(Handle::Unix(UnixHandle::new(path, timeouts)))
+    }
+}
+
+impl Transport for HttpTransport {
+    type Future = Box<dyn Future<Item = Vec<u8>, Error = Error> + Send>;
+    type Error = Error;
+
+    fn get_next_id(&mut self) -> u64 {
+        match self.0 {
+            Handle::Tcp(ref mut handle) => handle.get_next_id(),
+            #[cfg(feature = "tls")]
+            Handle::Https(ref mut handle) => handle.get_next_id(),
+            #[cfg(unix)]
+            Handle::Unix(ref mut handle) => handle.get_next_id(),
+        }
+    }
+
+    fn send(&self, json_data: Vec<u8>) -> Self::Future {
+        match self.0 {
+            Handle::Tcp(ref handle) => handle.send(json_data),
+            #[cfg(feature = "tls")]
+            Handle::Https(ref handle) => handle.send(json_data),
+            #[cfg(unix)]
+            Handle::Unix(ref handle) => handle.send(json_data),
+        }
+    }
+}
+
+
+/// Sends the requests over one WebSocket connection, which is kept open between requests. If the
+/// connection fails, the next request connects again. See the [`websocket`] module.
+///
+/// [`websocket`]: ../websocket/index.html
+pub struct WebSocketTransport(WebSocketHandle);
+
+impl WebSocketTransport {
+    pub(crate) fn new(server_uri: &str, timeouts: Timeouts) -> Result<Self> {
+        WebSocketHandle::new(server_uri, timeouts).map(WebSocketTransport)
+    }
+}
+
+impl Transport for WebSocketTransport {
+    type Future = Box<dyn Future<Item = Vec<u8>, Error = Error> + Send>;
+    type Error = Error;
+
+    fn get_next_id(&mut self) -> u64 {
+        self.0.get_next_id()
+    }
+
+    fn send(&self, json_data: Vec<u8>) -> Self::Future {
+        self.0.send(json_data)
+    }
+}
+
+
+/// Passes the requests directly to a [`LoopbackServer`] in the same process, without any
+/// serialization to the network. Lets code using a client be tested without starting a real
+/// server.
+///
+/// [`LoopbackServer`]: ../server/struct.LoopbackServer.html
+pub struct LoopbackTransport<M: Metadata> {
+    server: LoopbackServer<M>,
+    meta: M,
+    next_id: u64,
+}
+
+impl<M: Metadata + Sync> LoopbackTransport<M> {
+    /// Creates a transport sending its requests to `server`. `meta` is the metadata the server
+    /// hands to `on_kex` together with the keys exchanged over this transport, in place of what a
+    /// `MetaExtractor` would have computed.
+    pub fn new(server: &LoopbackServer<M>, meta: M) -> Self {
+        LoopbackTransport {
+            server: server.clone(),
+            meta,
+            next_id: 0,
+        }
+    }
+}
+
+impl<M: Metadata + Sync> Transport for LoopbackTransport<M> {
+    type Future = futures::future::FutureResult<Vec<u8>, Error>;
+    type Error = Error;
+
+    fn get_next_id(&mut self) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    fn send(&self, json_data: Vec<u8>) -> Self::Future {
+        let response = str::from_utf8(&json_data)
+            .chain_err(|| ErrorKind::TransportError)
+            .and_then(|request| {
+                self.server
+                    .handle_request(request, self.meta.clone())
+                    .ok_or_else(|| ErrorKind::TransportError.into())
+            })
+            .map(String::into_bytes);
+        futures::future::result(response)
+    }
+}
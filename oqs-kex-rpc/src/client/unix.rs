@@ -0,0 +1,55 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A blocking transport for the RPC client, sending requests over a Unix domain socket.
+
+use futures::{self, Future};
+
+use super::Timeouts;
+use super::deadline::{self, DeadlineStream};
+use super::http;
+use super::rpc::{Error, ErrorKind, Result, ResultExt};
+
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+
+/// Sends JSON-RPC requests to one server over a Unix domain socket.
+pub struct UnixHandle {
+    path: PathBuf,
+    timeouts: Timeouts,
+    next_id: u64,
+}
+
+impl UnixHandle {
+    pub fn new(path: &Path, timeouts: Timeouts) -> Self {
+        UnixHandle {
+            path: path.to_owned(),
+            timeouts,
+            next_id: 0,
+        }
+    }
+
+    pub fn get_next_id(&mut self) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    pub fn send(
+        &self,
+        json_data: Vec<u8>,
+    ) -> Box<dyn Future<Item = Vec<u8>, Error = Error> + Send> {
+        Box::new(futures::future::result(self.post(&json_data)))
+    }
+
+    fn post(&self, body: &[u8]) -> Result<Vec<u8>> {
+        let socket = UnixStream::connect(&self.path).chain_err(|| ErrorKind::TransportError)?;
+        let stream = DeadlineStream::new(socket, deadline::after(self.timeouts.request));
+        http::post(stream, "localhost", "/", body)
+    }
+}
@@ -0,0 +1,227 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A blocking WebSocket transport for the RPC client. All requests are sent over one connection,
+//! which is opened by the first request and opened again if it fails.
+
+use base64;
+use futures::{self, Future};
+use httparse;
+use oqs::rand::{OqsRand, OqsRandAlg};
+
+use super::{Timeouts, MAX_RESPONSE_LEN};
+use super::deadline::{self, DeadlineStream};
+use super::rpc::{Error, ErrorKind, Result, ResultExt};
+use super::tcp;
+use websocket::frame::{self, FrameHeader};
+
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::str;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use jsonrpc_http_server::hyper::Uri;
+
+const DEFAULT_PORT: u16 = 80;
+
+/// Max size of the server's opening handshake response.
+const MAX_HANDSHAKE_LEN: usize = 8 * 1024;
+
+const MAX_HEADERS: usize = 32;
+
+type Stream = DeadlineStream<TcpStream>;
+
+/// Sends JSON-RPC requests to one server over a WebSocket connection.
+pub struct WebSocketHandle {
+    host: String,
+    port: u16,
+    path: String,
+    timeouts: Timeouts,
+    stream: Mutex<Option<Stream>>,
+    next_id: u64,
+}
+
+impl WebSocketHandle {
+    pub fn new(server_uri: &str, timeouts: Timeouts) -> Result<Self> {
+        let uri: Uri = server_uri.parse().chain_err(|| ErrorKind::RpcInitError)?;
+        ensure!(uri.scheme() == Some("ws"), ErrorKind::RpcInitError);
+        let host = uri.host().ok_or(ErrorKind::RpcInitError)?;
+        Ok(WebSocketHandle {
+            host: host.to_owned(),
+            port: uri.port().unwrap_or(DEFAULT_PORT),
+            path: uri.path().to_owned(),
+            timeouts,
+            stream: Mutex::new(None),
+            next_id: 0,
+        })
+    }
+
+    pub fn get_next_id(&mut self) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    pub fn send(
+        &self,
+        json_data: Vec<u8>,
+    ) -> Box<dyn Future<Item = Vec<u8>, Error = Error> + Send> {
+        Box::new(futures::future::result(self.request(&json_data)))
+    }
+
+    fn request(&self, body: &[u8]) -> Result<Vec<u8>> {
+        let deadline = deadline::after(self.timeouts.request);
+        let mut stream = self.stream.lock().unwrap();
+        if stream.is_none() {
+            *stream = Some(self.connect(deadline)?);
+        }
+        let response = {
+            let stream = stream.as_mut().unwrap();
+            stream.set_deadline(deadline);
+            exchange(stream, body)
+        };
+        if response.is_err() {
+            *stream = None;
+        }
+        response
+    }
+
+    /// Connects to the server and performs the opening handshake, within `deadline`.
+    fn connect(&self, deadline: Option<Instant>) -> Result<Stream> {
+        let socket = tcp::connect(&self.host, self.port, &self.timeouts)?;
+        let mut stream = DeadlineStream::new(socket, deadline);
+        let mut nonce = [0; 16];
+        random_bytes(&mut nonce)?;
+        let key = base64::encode(&nonce);
+        write!(
+            stream,
+            "GET {} HTTP/1.1\r\nHost: {}:{}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+             Sec-WebSocket-Key: {}\r\nSec-WebSocket-Version: {}\r\n\r\n",
+            self.path,
+            self.host,
+            self.port,
+            key,
+            frame::VERSION
+        ).chain_err(|| ErrorKind::TransportError)?;
+
+        let response = read_handshake_response(&mut stream)?;
+        check_handshake_response(&response, &frame::accept_key(&key))?;
+        Ok(stream)
+    }
+}
+
+/// Reads the server's response to the opening handshake, up to and including the empty line
+/// ending its headers. Reads one byte at a time to not consume any frame following it.
+fn read_handshake_response(stream: &mut Stream) -> Result<Vec<u8>> {
+    let mut response = Vec::new();
+    let mut byte = [0];
+    while !response.ends_with(b"\r\n\r\n") {
+        ensure!(response.len() < MAX_HANDSHAKE_LEN, ErrorKind::TransportError);
+        stream
+            .read_exact(&mut byte)
+            .chain_err(|| ErrorKind::TransportError)?;
+        response.push(byte[0]);
+    }
+    Ok(response)
+}
+
+/// Checks that the server accepted the upgrade to a WebSocket connection.
+fn check_handshake_response(response: &[u8], expected_accept: &str) -> Result<()> {
+    let mut headers = [httparse::EMPTY_HEADER; MAX_HEADERS];
+    let mut parsed = httparse::Response::new(&mut headers);
+    match parsed.parse(response) {
+        Ok(httparse::Status::Complete(_)) => (),
+        _ => bail!(ErrorKind::TransportError),
+    }
+    match parsed.code {
+        Some(101) => (),
+        Some(status) => bail!(ErrorKind::HttpStatusError(status)),
+        None => bail!(ErrorKind::TransportError),
+    }
+    let accept = parsed
+        .headers
+        .iter()
+        .find(|header| header.name.eq_ignore_ascii_case("Sec-WebSocket-Accept"))
+        .and_then(|header| str::from_utf8(header.value).ok());
+    ensure!(
+        accept.map(str::trim) == Some(expected_accept),
+        ErrorKind::TransportError
+    );
+    Ok(())
+}
+
+/// Sends `body` as a text message and returns the next message the server sends. Fails if the
+/// message is larger than `MAX_RESPONSE_LEN`.
+fn exchange(stream: &mut Stream, body: &[u8]) -> Result<Vec<u8>> {
+    write_frame(stream, frame::OPCODE_TEXT, body)?;
+    let mut message: Option<Vec<u8>> = None;
+    loop {
+        let (header, payload) = read_frame(stream)?;
+        match (header.opcode, message.as_mut()) {
+            (frame::OPCODE_PING, _) => write_frame(stream, frame::OPCODE_PONG, &payload)?,
+            (frame::OPCODE_PONG, _) => (),
+            (frame::OPCODE_TEXT, None) | (frame::OPCODE_BINARY, None) => message = Some(payload),
+            (frame::OPCODE_CONTINUATION, Some(message)) => {
+                ensure!(
+                    message.len() + payload.len() <= MAX_RESPONSE_LEN,
+                    ErrorKind::TransportError
+                );
+                message.extend_from_slice(&payload)
+            }
+            // Close frames, and frames violating the protocol.
+            _ => bail!(ErrorKind::TransportError),
+        }
+        // Control frames may come between the fragments of a message.
+        let is_control = header.opcode & 0x8 != 0;
+        if header.fin && !is_control {
+            if let Some(message) = message.take() {
+                return Ok(message);
+            }
+        }
+    }
+}
+
+fn write_frame(stream: &mut Stream, opcode: u8, payload: &[u8]) -> Result<()> {
+    let mut mask = [0; 4];
+    random_bytes(&mut mask)?;
+    stream
+        .write_all(&frame::encode(opcode, payload, Some(mask)))
+        .chain_err(|| ErrorKind::TransportError)
+}
+
+fn read_frame(stream: &mut Stream) -> Result<(FrameHeader, Vec<u8>)> {
+    let mut start = [0; 2];
+    stream
+        .read_exact(&mut start)
+        .chain_err(|| ErrorKind::TransportError)?;
+    let mut rest = vec![0; FrameHeader::remaining_len(start)];
+    stream
+        .read_exact(&mut rest)
+        .chain_err(|| ErrorKind::TransportError)?;
+    let header = FrameHeader::decode(start, &rest).ok_or(ErrorKind::TransportError)?;
+    // Servers must not mask their frames.
+    ensure!(header.mask.is_none(), ErrorKind::TransportError);
+    ensure!(
+        header.len <= MAX_RESPONSE_LEN as u64,
+        ErrorKind::TransportError
+    );
+
+    let mut payload = Vec::new();
+    Read::by_ref(stream)
+        .take(header.len)
+        .read_to_end(&mut payload)
+        .chain_err(|| ErrorKind::TransportError)?;
+    ensure!(payload.len() as u64 == header.len, ErrorKind::TransportError);
+    Ok((header, payload))
+}
+
+fn random_bytes(buffer: &mut [u8]) -> Result<()> {
+    let rand = OqsRand::new(OqsRandAlg::default()).chain_err(|| ErrorKind::TransportError)?;
+    rand.rand_n(buffer).chain_err(|| ErrorKind::TransportError)
+}
@@ -0,0 +1,124 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Key confirmation shared by the client and the server. Each side computes an HMAC-SHA256 over
+//! the transcript hash of the exchange, keyed by the concatenation of all shared keys. A side that
+//! can verify the other side's MAC knows that the peer derived the same keys from the same
+//! messages.
+
+use hmac::{Hmac, Mac};
+use oqs::kex::SharedKey;
+use sha2::Sha256;
+use transcript::TranscriptHash;
+
+use std::ptr;
+use std::sync::atomic;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Which side of the exchange a MAC is computed by. Part of the MAC input, so a MAC sent by one
+/// side can't be reflected back as the MAC of the other side.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Role {
+    Client,
+    Server,
+}
+
+impl Role {
+    fn label(&self) -> &'static [u8] {
+        match *self {
+            Role::Client => b"oqs-kex-rpc client confirmation",
+            Role::Server => b"oqs-kex-rpc server confirmation",
+        }
+    }
+}
+
+/// Computes the MAC `role` sends to the other side.
+pub fn mac(role: Role, keys: &[SharedKey], transcript_hash: &TranscriptHash) -> Vec<u8> {
+    hmac(role, keys, transcript_hash).result().code().to_vec()
+}
+
+/// Checks, in constant time, that `code` is the MAC `role` should have sent.
+pub fn verify(
+    role: Role,
+    keys: &[SharedKey],
+    transcript_hash: &TranscriptHash,
+    code: &[u8],
+) -> bool {
+    hmac(role, keys, transcript_hash).verify(code).is_ok()
+}
+
+fn hmac(role: Role, keys: &[SharedKey], transcript_hash: &TranscriptHash) -> HmacSha256 {
+    // Allocated with the final length up front, so the key material is never copied into a buffer
+    // that is freed without being cleansed.
+    let mut key = Vec::with_capacity(keys.iter().map(|shared_key| shared_key.data().len()).sum());
+    for shared_key in keys {
+        key.extend_from_slice(shared_key.data());
+    }
+    let mut hmac = HmacSha256::new_varkey(&key).expect("HMAC accepts keys of any length");
+    cleanse(&mut key);
+    hmac.input(role.label());
+    hmac.input(transcript_hash.data());
+    hmac
+}
+
+/// Zeroes out `data` with volatile writes, which the compiler can't remove as dead stores.
+fn cleanse(data: &mut [u8]) {
+    for byte in data.iter_mut() {
+        unsafe { ptr::write_volatile(byte, 0) };
+    }
+    atomic::compiler_fence(atomic::Ordering::SeqCst);
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oqs::kex::{OqsKex, OqsKexAlg};
+    use oqs::rand::{OqsRand, OqsRandAlg};
+    use transcript::Transcript;
+
+    fn exchange() -> (TranscriptHash, Vec<SharedKey>) {
+        let rand = OqsRand::new(OqsRandAlg::default()).unwrap();
+        let kex_alice = OqsKex::new(&rand, OqsKexAlg::MlKem512).unwrap();
+        let kex_alice_0 = kex_alice.alice_0().unwrap();
+        let kex_bob = OqsKex::new(&rand, OqsKexAlg::MlKem512).unwrap();
+        let (bob_msg, key) = kex_bob.bob(kex_alice_0.get_alice_msg()).unwrap();
+        let transcript = Transcript::from_msgs(&[kex_alice_0.get_alice_msg()], &[bob_msg]);
+        (transcript.hash(), vec![key])
+    }
+
+    #[test]
+    fn cleanse_zeroes_data() {
+        let mut data = vec![0xff; 32];
+        cleanse(&mut data);
+        assert!(data.iter().all(|&byte| byte == 0));
+    }
+
+    #[test]
+    fn verify_own_mac() {
+        let (transcript_hash, keys) = exchange();
+        let code = mac(Role::Server, &keys, &transcript_hash);
+        assert!(verify(Role::Server, &keys, &transcript_hash, &code));
+    }
+
+    #[test]
+    fn roles_differ() {
+        let (transcript_hash, keys) = exchange();
+        let code = mac(Role::Server, &keys, &transcript_hash);
+        assert!(!verify(Role::Client, &keys, &transcript_hash, &code));
+    }
+
+    #[test]
+    fn other_transcript_fails() {
+        let (transcript_hash, keys) = exchange();
+        let (other_transcript_hash, _) = exchange();
+        let code = mac(Role::Client, &keys, &transcript_hash);
+        assert!(!verify(Role::Client, &keys, &other_transcript_hash, &code));
+    }
+}
@@ -0,0 +1,164 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The cost model the server uses to limit how much work a single request, or a single client,
+//! can cause.
+
+use oqs::kex::OqsKexAlg;
+use serde::{Serialize, Serializer};
+
+use std::collections::HashMap;
+
+/// Every algorithm, in the order of `OqsKexAlg`.
+const ALGORITHMS: [OqsKexAlg; 17] = {
+    use self::OqsKexAlg::*;
+    [
+        MlKem512,
+        MlKem768,
+        MlKem1024,
+        FrodoKem640Aes,
+        FrodoKem640Shake,
+        FrodoKem976Aes,
+        FrodoKem976Shake,
+        FrodoKem1344Aes,
+        FrodoKem1344Shake,
+        ClassicMcEliece348864,
+        ClassicMcEliece460896,
+        ClassicMcEliece6688128,
+        ClassicMcEliece6960119,
+        ClassicMcEliece8192128,
+        Hqc128,
+        Hqc192,
+        Hqc256,
+    ]
+};
+
+/// The cost of computing Bob's side of a key exchange, for every algorithm. The cost of a request
+/// is the sum of the costs of all algorithms in it.
+///
+/// The default costs are rough estimates of the CPU time of one exchange on the server, in units
+/// of about ten microseconds on a modern x86-64 CPU. Operators should benchmark the algorithms
+/// they enable on their own hardware and tune the costs with
+/// [`set_cost`](#method.set_cost).
+///
+/// Serialized with the cost of every algorithm, not only those set with `set_cost`, so a client
+/// uses the server's costs even if its own defaults differ.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AlgorithmCosts {
+    /// Costs that differ from the defaults.
+    #[serde(serialize_with = "serialize_all_costs")]
+    costs: HashMap<OqsKexAlg, u32>,
+}
+
+impl AlgorithmCosts {
+    /// Creates a cost model with the default costs.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the cost of one exchange with `algorithm`.
+    pub fn cost(&self, algorithm: OqsKexAlg) -> u32 {
+        match self.costs.get(&algorithm) {
+            Some(&cost) => cost,
+            None => default_cost(algorithm),
+        }
+    }
+
+    /// Sets the cost of one exchange with `algorithm`.
+    pub fn set_cost(&mut self, algorithm: OqsKexAlg, cost: u32) {
+        self.costs.insert(algorithm, cost);
+    }
+
+    /// Returns the cost of a request with the given algorithms.
+    pub fn request_cost(&self, algorithms: &[OqsKexAlg]) -> u64 {
+        algorithms
+            .iter()
+            .map(|algorithm| u64::from(self.cost(*algorithm)))
+            .sum()
+    }
+}
+
+fn default_cost(algorithm: OqsKexAlg) -> u32 {
+    use self::OqsKexAlg::*;
+    match algorithm {
+        MlKem512 => 2,
+        MlKem768 => 3,
+        MlKem1024 => 4,
+        FrodoKem640Aes => 40,
+        FrodoKem640Shake => 120,
+        FrodoKem976Aes => 80,
+        FrodoKem976Shake => 250,
+        FrodoKem1344Aes => 150,
+        FrodoKem1344Shake => 450,
+        // Encapsulation is fast, but the public keys are hundreds of kilobytes to parse and hash.
+        ClassicMcEliece348864 => 10,
+        ClassicMcEliece460896 => 20,
+        ClassicMcEliece6688128 => 30,
+        ClassicMcEliece6960119 => 30,
+        ClassicMcEliece8192128 => 35,
+        Hqc128 => 15,
+        Hqc192 => 30,
+        Hqc256 => 55,
+    }
+}
+
+/// Serializes the costs of all algorithms, with the defaults filled in for those not in `costs`.
+fn serialize_all_costs<S: Serializer>(
+    costs: &HashMap<OqsKexAlg, u32>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let all_costs: HashMap<OqsKexAlg, u32> = ALGORITHMS
+        .iter()
+        .map(|&algorithm| {
+            let cost = costs
+                .get(&algorithm)
+                .cloned()
+                .unwrap_or_else(|| default_cost(algorithm));
+            (algorithm, cost)
+        })
+        .collect();
+    all_costs.serialize(serializer)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request_cost_sums_algorithms() {
+        let costs = AlgorithmCosts::new();
+        let algorithms = [OqsKexAlg::MlKem768, OqsKexAlg::MlKem768, OqsKexAlg::Hqc128];
+        assert_eq!(costs.request_cost(&algorithms), 3 + 3 + 15);
+        assert_eq!(costs.request_cost(&[]), 0);
+    }
+
+    #[test]
+    fn set_cost_overrides_default() {
+        let mut costs = AlgorithmCosts::new();
+        costs.set_cost(OqsKexAlg::FrodoKem640Aes, 7);
+        assert_eq!(costs.cost(OqsKexAlg::FrodoKem640Aes), 7);
+        assert_eq!(costs.cost(OqsKexAlg::FrodoKem640Shake), 120);
+    }
+
+    #[test]
+    fn serialize_full_table() {
+        let mut costs = AlgorithmCosts::new();
+        costs.set_cost(OqsKexAlg::Hqc128, 7);
+        let json = ::serde_json::to_value(&costs).unwrap();
+        let serialized = json["costs"].as_object().unwrap();
+        assert_eq!(serialized.len(), ALGORITHMS.len());
+        assert_eq!(serialized["Hqc128"], 7);
+        assert_eq!(serialized["MlKem768"], 3);
+
+        let deserialized: AlgorithmCosts = ::serde_json::from_value(json).unwrap();
+        for &algorithm in &ALGORITHMS {
+            assert_eq!(deserialized.cost(algorithm), costs.cost(algorithm));
+        }
+    }
+}
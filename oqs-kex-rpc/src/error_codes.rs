@@ -0,0 +1,34 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The application defined JSON-RPC 2.0 error codes the server responds with. They are in the
+//! range the specification reserves for implementation defined server errors, and are stable
+//! between versions of this crate. Any other failure is reported as the standard
+//! `internal_error`.
+
+/// The request did not meet the server's constraints. The error's `data` holds the
+/// [`ConstraintViolation`](../enum.ConstraintViolation.html).
+pub const CONSTRAINT_ERROR: i64 = -32000;
+
+/// The cryptographic operations in `oqs` failed on the server. Usually a malformed message from
+/// Alice.
+pub const OQS_ERROR: i64 = -32001;
+
+/// The server's `on_kex` callback failed to handle the exchanged keys.
+pub const CALLBACK_ERROR: i64 = -32002;
+
+/// The key confirmation was invalid, or referred to an unknown or expired exchange.
+pub const CONFIRMATION_ERROR: i64 = -32003;
+
+/// The server is too busy to compute the key exchange. All worker threads are busy and too many
+/// key exchanges are already waiting for one. The request can be retried later.
+pub const BUSY_ERROR: i64 = -32004;
+
+/// The client has used up its rate limit. The request can be retried once its token bucket has
+/// refilled.
+pub const RATE_LIMIT_ERROR: i64 = -32005;
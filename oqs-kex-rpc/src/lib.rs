@@ -19,20 +19,20 @@
 //!
 //! use oqs_kex_rpc::server::ServerConstraints;
 //! use oqs_kex_rpc::client::OqsKexClient;
-//! use oqs_kex_rpc::{OqsKexAlg, SharedKey};
+//! use oqs_kex_rpc::{ExchangedKeys, OqsKexAlg};
 //! # use std::net::SocketAddr;
 //! # use std::str::FromStr;
 //!
 //! static ALGORITHMS: &[oqs_kex_rpc::OqsKexAlg] = &[
-//!     OqsKexAlg::RlweNewhope,
+//!     OqsKexAlg::MlKem768,
 //! ];
 //!
 //! # fn main() {
 //! // This is the callback that will be called on the server after the shared key
 //! // has been computed on the server, but before Bob's messages are returned
 //! // to the client.
-//! let on_kex = move |_metadata: (), keys: Vec<SharedKey>| {
-//!     println!("Done exchanging {} keys", keys.len());
+//! let on_kex = move |_metadata: (), exchanged: ExchangedKeys| {
+//!     println!("Done exchanging {} keys", exchanged.keys.len());
 //!     // If this callback return an `Err`, Bob's messages will not be returned
 //!     // to the client, instead a JSON-RPC error will be returned.
 //!     Ok(()) as Result<(), ::std::io::Error>
@@ -60,13 +60,33 @@
 //! let client_keys = client.kex(ALGORITHMS).expect("Error in client during exchange");
 //!
 //! // Check that the result is sane (same algorithms as requested)
-//! assert_eq!(client_keys.len(), ALGORITHMS.len());
-//! for (key, algorithm) in client_keys.iter().zip(ALGORITHMS) {
+//! assert_eq!(client_keys.keys.len(), ALGORITHMS.len());
+//! for (key, algorithm) in client_keys.keys.iter().zip(ALGORITHMS) {
 //!     assert_eq!(key.algorithm(), *algorithm);
 //! }
 //! # }
 //! ```
 //!
+//! # TLS
+//!
+//! With the `tls` feature enabled, the server and client can use HTTPS instead of plain HTTP, with
+//! optional client certificates. See the `tls` module.
+//!
+//! # Unix domain sockets
+//!
+//! On Unix, the server can listen on a Unix domain socket instead of a TCP port, giving the
+//! `MetaExtractor` the uid, gid and pid of the connecting process. See the `unix` module.
+//!
+//! # Binary protocol
+//!
+//! The server and client can also speak a compact length prefixed binary protocol over TCP instead
+//! of JSON-RPC over HTTP. See the `binary` module.
+//!
+//! # WebSocket
+//!
+//! The JSON-RPC calls can also be sent over a WebSocket connection, doing several exchanges over
+//! one connection. See the `websocket` module.
+//!
 //! [oqs]: https://crates.io/crates/oqs
 
 #![deny(missing_docs)]
@@ -76,23 +96,79 @@ extern crate error_chain;
 extern crate futures;
 #[macro_use]
 extern crate log;
+extern crate num_cpus;
 extern crate oqs;
 
+extern crate hmac;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+extern crate sha1;
+extern crate sha2;
+
 #[macro_use]
 extern crate jsonrpc_client_core;
-extern crate jsonrpc_client_http;
 
 extern crate jsonrpc_core;
 extern crate jsonrpc_http_server;
 #[macro_use]
 extern crate jsonrpc_macros;
 
+extern crate base64;
+extern crate httparse;
+extern crate tokio_core;
+extern crate tokio_io;
+
+#[cfg(feature = "tls")]
+extern crate rustls;
+#[cfg(feature = "tls")]
+extern crate tokio_rustls;
+
+#[cfg(unix)]
+extern crate libc;
+#[cfg(unix)]
+extern crate tokio_uds;
+
 
 pub use oqs::kex::{OqsKexAlg, SharedKey};
 pub use oqs::rand::OqsRandAlg;
+pub use oqs::sig::{OqsSigAlg, PublicKey, SecretKey};
+pub use transcript::{ExchangedKeys, TranscriptHash};
+pub use cost::AlgorithmCosts;
+pub use messages::{Capabilities, ConstraintViolation};
 
 /// Module containing a JSON-RPC 2.0 client for key exchange.
 pub mod client;
 
 /// Module containing a JSON-RPC 2.0 server for key exchange.
 pub mod server;
+
+/// Module containing a compact binary protocol server and client for key exchange.
+pub mod binary;
+
+/// Module containing the JSON-RPC 2.0 error codes the server uses to report why a request failed.
+pub mod error_codes;
+
+/// Module containing the transcript hash binding exchanged keys to the messages exchanged.
+pub mod transcript;
+
+/// Module containing the TLS configuration of the server and client.
+#[cfg(feature = "tls")]
+pub mod tls;
+
+/// Module containing the peer credentials of clients connecting over a Unix domain socket.
+#[cfg(unix)]
+pub mod unix;
+
+/// Module containing a server accepting key exchanges over WebSocket connections.
+pub mod websocket;
+
+mod confirm;
+mod cost;
+mod listener;
+mod messages;
+mod pending;
+mod rate_limit;
+mod tags;
+mod tcp;
@@ -0,0 +1,250 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Building blocks of the listeners the server runs instead of the jsonrpc-http-server one.
+
+use futures::{future, Future, Stream};
+use futures::future::Either;
+use futures::sync::oneshot;
+use tokio_core::reactor::{Core, Handle, Timeout};
+use tokio_io::{AsyncRead, AsyncWrite};
+
+use server::TRUSTED_HEADERS;
+
+use std::io;
+use std::net::SocketAddr;
+use std::rc::Rc;
+use std::result::Result as StdResult;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use jsonrpc_http_server::hyper;
+use jsonrpc_http_server::hyper::StatusCode;
+use jsonrpc_http_server::hyper::header::Connection;
+use jsonrpc_http_server::hyper::server::{Http, Request, Response, Service};
+
+/// The thread a listener runs its event loop on. The event loop stops when this is dropped.
+pub struct ListenerThread {
+    close: Option<oneshot::Sender<()>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl ListenerThread {
+    /// Spawns a thread named `name` running an event loop. `bind` is called on the new thread to
+    /// bind the listener, and returns its address together with the future serving connections
+    /// on it. Returns once the listener is bound, with its address or the error binding it.
+    pub fn spawn<A, F, S>(name: &str, bind: F) -> io::Result<(Self, A)>
+    where
+        A: Send + 'static,
+        F: FnOnce(&Handle) -> io::Result<(A, S)> + Send + 'static,
+        S: Future<Item = (), Error = ()> + 'static,
+    {
+        let (address_tx, address_rx) = mpsc::channel();
+        let (close_tx, close_rx) = oneshot::channel::<()>();
+        let thread = thread::Builder::new().name(name.to_owned()).spawn(move || {
+            let bound = Core::new().and_then(|core| {
+                let (address, serve) = bind(&core.handle())?;
+                Ok((core, address, serve))
+            });
+            match bound {
+                Ok((mut core, address, serve)) => {
+                    let _ = address_tx.send(Ok(address));
+                    let close = close_rx.then(|_| Ok(()));
+                    let _ = core.run(serve.select(close));
+                }
+                Err(error) => {
+                    let _ = address_tx.send(Err(error));
+                }
+            }
+        })?;
+        let address = address_rx
+            .recv()
+            .map_err(|_| io::Error::new(io::ErrorKind::Interrupted, "Listener thread died"))??;
+
+        Ok((
+            ListenerThread {
+                close: Some(close_tx),
+                thread: Some(thread),
+            },
+            address,
+        ))
+    }
+
+    /// Blocks until the event loop has stopped.
+    pub fn wait(mut self) {
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for ListenerThread {
+    fn drop(&mut self) {
+        if let Some(close) = self.close.take() {
+            let _ = close.send(());
+        }
+    }
+}
+
+
+/// Returns a future resolving like `future`, or failing with `TimedOut` if `future` has not
+/// resolved within `timeout`.
+pub fn with_timeout<F>(
+    future: F,
+    timeout: Duration,
+    handle: &Handle,
+) -> Box<dyn Future<Item = F::Item, Error = io::Error>>
+where
+    F: Future<Error = io::Error> + 'static,
+{
+    let timer = match Timeout::new(timeout, handle) {
+        Ok(timer) => timer,
+        Err(error) => return Box::new(future::err(error)),
+    };
+    Box::new(future.select2(timer).then(|result| match result {
+        Ok(Either::A((item, _))) => Ok(item),
+        Ok(Either::B(((), _))) => Err(io::Error::new(io::ErrorKind::TimedOut, "Timed out")),
+        Err(Either::A((error, _))) | Err(Either::B((error, _))) => Err(error),
+    }))
+}
+
+
+/// Passes what the listener knows about the peer of a connection on to the `MetaExtractor`, in
+/// headers set on every request. All headers in `TRUSTED_HEADERS` sent by the client are removed
+/// first, so the client can't forge them.
+pub struct TrustedHeadersService<S> {
+    pub service: S,
+    pub headers: Vec<(&'static str, String)>,
+}
+
+impl<S> Service for TrustedHeadersService<S>
+where
+    S: Service<Request = Request, Response = Response, Error = hyper::Error>,
+{
+    type Request = Request;
+    type Response = Response;
+    type Error = hyper::Error;
+    type Future = S::Future;
+
+    fn call(&self, mut request: Request) -> Self::Future {
+        {
+            let headers = request.headers_mut();
+            for name in TRUSTED_HEADERS {
+                headers.remove_raw(name);
+            }
+            for &(name, ref value) in &self.headers {
+                headers.set_raw(name, value.clone());
+            }
+        }
+        self.service.call(request)
+    }
+}
+
+/// Answers requests with a body larger than `max_request_size` with `413 Payload Too Large`. The
+/// body is counted as it is read, so chunked bodies of unknown size are limited too. The body is
+/// read in full before `service` is called, as the JSON-RPC handler would anyway.
+pub struct BodyLimitService<S> {
+    service: Rc<S>,
+    max_request_size: Option<usize>,
+}
+
+impl<S> BodyLimitService<S> {
+    pub fn new(service: S, max_request_size: Option<usize>) -> Self {
+        BodyLimitService {
+            service: Rc::new(service),
+            max_request_size,
+        }
+    }
+}
+
+impl<S> Service for BodyLimitService<S>
+where
+    S: Service<Request = Request, Response = Response, Error = hyper::Error> + 'static,
+    S::Future: 'static,
+{
+    type Request = Request;
+    type Response = Response;
+    type Error = hyper::Error;
+    type Future = Box<dyn Future<Item = Response, Error = hyper::Error>>;
+
+    fn call(&self, mut request: Request) -> Self::Future {
+        let max_request_size = match self.max_request_size {
+            Some(max_request_size) => max_request_size,
+            None => return Box::new(self.service.call(request)),
+        };
+        let service = self.service.clone();
+        let body = request.body_mut().take().unwrap_or_default();
+        // Reading stops with `Err(None)` as soon as the body is too large.
+        let read_body = body.map_err(Some).fold(
+            Vec::new(),
+            move |mut body, chunk| -> StdResult<Vec<u8>, Option<hyper::Error>> {
+                if body.len() + chunk.len() > max_request_size {
+                    return Err(None);
+                }
+                body.extend_from_slice(&chunk);
+                Ok(body)
+            },
+        );
+        Box::new(read_body.then(move |result| -> Self::Future {
+            match result {
+                Ok(body) => {
+                    request.set_body(body);
+                    Box::new(service.call(request))
+                }
+                // The rest of the body is not read, so the connection can't be used any further.
+                Err(None) => Box::new(future::ok(
+                    Response::new()
+                        .with_status(StatusCode::PayloadTooLarge)
+                        .with_header(Connection::close()),
+                )),
+                Err(Some(error)) => Box::new(future::err(error)),
+            }
+        }))
+    }
+}
+
+/// Serves the HTTP connection over `io` with `service`, on the event loop of `handle`.
+//
+// `bind_connection` is the only way to serve a connection with the peer address set on the
+// requests in this hyper version. The rate limiting needs the address.
+#[allow(deprecated)]
+pub fn bind_connection<I, S>(
+    http: &Http,
+    handle: &Handle,
+    io: I,
+    peer_addr: SocketAddr,
+    service: S,
+) where
+    I: AsyncRead + AsyncWrite + 'static,
+    S: Service<Request = Request, Response = Response, Error = hyper::Error> + 'static,
+{
+    http.bind_connection(handle, io, peer_addr, service);
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timeout_fails_pending_future() {
+        let mut core = Core::new().unwrap();
+        let pending = future::empty::<(), io::Error>();
+        let result = core.run(with_timeout(pending, Duration::from_millis(10), &core.handle()));
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::TimedOut);
+    }
+
+    #[test]
+    fn timeout_passes_result_through() {
+        let mut core = Core::new().unwrap();
+        let done = future::ok::<u32, io::Error>(5);
+        let result = core.run(with_timeout(done, Duration::from_secs(10), &core.handle()));
+        assert_eq!(result.unwrap(), 5);
+    }
+}
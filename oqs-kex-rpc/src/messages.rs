@@ -0,0 +1,241 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Responses of the server's RPC methods, shared by the client and the server.
+
+use cost::AlgorithmCosts;
+use oqs::kex::{BobMsg, OqsKexAlg};
+use oqs::sig::Signature;
+
+use std::fmt;
+
+/// The constraints a server enforces on key exchange requests. Returned by the server's
+/// `capabilities` RPC method, see [`OqsKexClient::capabilities`]. `None` means unlimited.
+///
+/// [`OqsKexClient::capabilities`]: client/struct.OqsKexClient.html#method.capabilities
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Capabilities {
+    /// The algorithms the server accepts. `None` means all algorithms liboqs was built with.
+    pub algorithms: Option<Vec<OqsKexAlg>>,
+    /// Max number of algorithms in a single request.
+    pub max_algorithms: Option<usize>,
+    /// Max number of times a specific algorithm may occur in a single request.
+    pub max_occurrences: Option<usize>,
+    /// Max size in bytes of an HTTP request.
+    pub max_request_size: Option<usize>,
+    /// The cost of every algorithm, used for `max_request_cost` and rate limiting.
+    pub algorithm_costs: AlgorithmCosts,
+    /// Max total cost of the algorithms in a single request.
+    pub max_request_cost: Option<u64>,
+    /// Whether the server only accepts key exchanges confirmed by the client.
+    pub require_confirmation: bool,
+}
+
+impl Capabilities {
+    /// Picks the algorithms to use in a key exchange with a server having these capabilities.
+    ///
+    /// `preferences` is ordered from most to least preferred, and an algorithm may be listed
+    /// several times to request several keys of it. The selection is filled from the front of
+    /// the list: every preference the server accepts is picked, in order, until `max` algorithms,
+    /// or the server's `max_algorithms` if lower, are picked. Preferences that would exceed
+    /// `max_occurrences` or `max_request_cost` are skipped. So with `max` set to one, the most
+    /// preferred algorithm the server accepts is picked.
+    pub fn select(&self, preferences: &[OqsKexAlg], max: usize) -> Vec<OqsKexAlg> {
+        let max_algorithms = match self.max_algorithms {
+            Some(max_algorithms) => max_algorithms.min(max),
+            None => max,
+        };
+        let mut selected = Vec::new();
+        let mut cost = 0;
+        for &algorithm in preferences {
+            if selected.len() >= max_algorithms {
+                break;
+            }
+            let occurrences = selected.iter().filter(|&&alg| alg == algorithm).count();
+            let new_cost = cost + u64::from(self.algorithm_costs.cost(algorithm));
+            if self.is_allowed_algorithm(algorithm) && self.is_below_max_occurrences(occurrences)
+                && self.is_within_max_cost(new_cost)
+            {
+                selected.push(algorithm);
+                cost = new_cost;
+            }
+        }
+        selected
+    }
+
+    fn is_allowed_algorithm(&self, algorithm: OqsKexAlg) -> bool {
+        match self.algorithms {
+            Some(ref algorithms) => algorithms.contains(&algorithm),
+            None => true,
+        }
+    }
+
+    fn is_below_max_occurrences(&self, occurrences: usize) -> bool {
+        match self.max_occurrences {
+            Some(max_occurrences) => occurrences < max_occurrences,
+            None => true,
+        }
+    }
+
+    fn is_within_max_cost(&self, cost: u64) -> bool {
+        match self.max_request_cost {
+            Some(max_request_cost) => cost <= max_request_cost,
+            None => true,
+        }
+    }
+}
+
+/// Which of the server's constraints a key exchange request violated. Sent as the `data` of
+/// [`CONSTRAINT_ERROR`](error_codes/constant.CONSTRAINT_ERROR.html) responses.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConstraintViolation {
+    /// The request contained an algorithm the server does not accept.
+    DisallowedAlgorithm(OqsKexAlg),
+    /// The request contained more algorithms than `max_algorithms`.
+    TooManyAlgorithms {
+        /// Number of algorithms in the request.
+        count: usize,
+        /// The server's `max_algorithms`.
+        max: usize,
+    },
+    /// The request contained an algorithm more times than `max_occurrences`.
+    TooManyOccurrences {
+        /// The algorithm occurring too many times.
+        algorithm: OqsKexAlg,
+        /// Number of times it occurs in the request.
+        count: usize,
+        /// The server's `max_occurrences`.
+        max: usize,
+    },
+    /// The total cost of the algorithms in the request is above `max_request_cost`.
+    TooExpensive {
+        /// The cost of the request.
+        cost: u64,
+        /// The server's `max_request_cost`.
+        max: u64,
+    },
+    /// The server only accepts key exchanges with confirmation.
+    ConfirmationRequired,
+}
+
+impl fmt::Display for ConstraintViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::ConstraintViolation::*;
+        match *self {
+            DisallowedAlgorithm(algorithm) => write!(f, "algorithm {:?} is not allowed", algorithm),
+            TooManyAlgorithms { count, max } => {
+                write!(f, "{} algorithms requested, max is {}", count, max)
+            }
+            TooManyOccurrences {
+                algorithm,
+                count,
+                max,
+            } => write!(
+                f,
+                "algorithm {:?} requested {} times, max is {}",
+                algorithm,
+                count,
+                max
+            ),
+            TooExpensive { cost, max } => write!(f, "request costs {}, max is {}", cost, max),
+            ConfirmationRequired => write!(f, "key confirmation is required"),
+        }
+    }
+}
+
+/// Server reply to a key exchange.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KexResponse {
+    /// Bob's messages, in the same order as Alice's messages in the request.
+    pub bob_msgs: Vec<BobMsg>,
+    /// The server's signature over the transcript hash, if the server has a signing key.
+    pub signature: Option<Signature>,
+}
+
+/// Server reply to a key exchange that has to be confirmed by the client before the server
+/// hands the shared keys to its `on_kex` callback.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnconfirmedKex {
+    /// Identifies the exchange when the client sends its confirmation.
+    pub session_id: u64,
+    /// Bob's messages, in the same order as Alice's messages in the request.
+    pub bob_msgs: Vec<BobMsg>,
+    /// The server's signature over the transcript hash, if the server has a signing key.
+    pub signature: Option<Signature>,
+    /// The server's key confirmation MAC over the transcript hash.
+    pub server_mac: Vec<u8>,
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oqs::kex::OqsKexAlg::*;
+
+    fn capabilities(
+        algorithms: Option<Vec<OqsKexAlg>>,
+        max_algorithms: Option<usize>,
+        max_occurrences: Option<usize>,
+    ) -> Capabilities {
+        Capabilities {
+            algorithms,
+            max_algorithms,
+            max_occurrences,
+            max_request_size: None,
+            algorithm_costs: AlgorithmCosts::default(),
+            max_request_cost: None,
+            require_confirmation: false,
+        }
+    }
+
+    #[test]
+    fn select_unconstrained() {
+        let capabilities = capabilities(None, None, None);
+        let preferences = [MlKem1024, MlKem1024, Hqc256];
+        assert_eq!(capabilities.select(&preferences, 3), preferences.to_vec());
+    }
+
+    #[test]
+    fn select_up_to_max() {
+        let capabilities = capabilities(Some(vec![MlKem768, Hqc128]), Some(2), None);
+        let preferences = [MlKem1024, MlKem768, Hqc128];
+        assert_eq!(capabilities.select(&preferences, 1), vec![MlKem768]);
+        assert_eq!(capabilities.select(&preferences, 5), vec![MlKem768, Hqc128]);
+    }
+
+    #[test]
+    fn select_skips_disallowed() {
+        let capabilities = capabilities(Some(vec![MlKem768, Hqc128]), None, None);
+        let preferences = [MlKem1024, MlKem768, FrodoKem976Aes, Hqc128];
+        assert_eq!(capabilities.select(&preferences, 4), vec![MlKem768, Hqc128]);
+    }
+
+    #[test]
+    fn select_keeps_most_preferred() {
+        let capabilities = capabilities(None, Some(2), Some(1));
+        let preferences = [MlKem1024, MlKem1024, FrodoKem976Aes, Hqc128];
+        assert_eq!(capabilities.select(&preferences, 4), vec![MlKem1024, FrodoKem976Aes]);
+    }
+
+    #[test]
+    fn select_within_max_cost() {
+        let mut capabilities = capabilities(None, None, None);
+        capabilities.algorithm_costs.set_cost(MlKem768, 10);
+        capabilities.algorithm_costs.set_cost(FrodoKem976Aes, 100);
+        capabilities.algorithm_costs.set_cost(Hqc128, 20);
+        capabilities.max_request_cost = Some(40);
+        let preferences = [MlKem768, FrodoKem976Aes, Hqc128, MlKem768, MlKem768];
+        assert_eq!(capabilities.select(&preferences, 5), vec![MlKem768, Hqc128, MlKem768]);
+    }
+
+    #[test]
+    fn select_nothing_in_common() {
+        let capabilities = capabilities(Some(vec![MlKem512]), None, None);
+        assert!(capabilities.select(&[MlKem768, Hqc128], 2).is_empty());
+    }
+}
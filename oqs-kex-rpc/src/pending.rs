@@ -0,0 +1,186 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The key exchanges the server keeps while waiting for the client to confirm them.
+
+use oqs::kex::SharedKey;
+use oqs::rand::OqsRand;
+
+use rate_limit::ClientId;
+use server::{ErrorKind, Result, ResultExt};
+use transcript::TranscriptHash;
+
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// How long the server keeps the keys of an exchange while waiting for the client to confirm it.
+pub const CONFIRMATION_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Max number of exchanges waiting for confirmation. Further exchanges are rejected as busy.
+const MAX_PENDING: usize = 4096;
+
+/// Max number of exchanges waiting for confirmation from a single client. Further exchanges from
+/// the client are rejected as rate limited.
+const MAX_PENDING_PER_CLIENT: usize = 16;
+
+/// A key exchange waiting for the client's confirmation.
+pub struct PendingKex<M> {
+    pub meta: M,
+    pub client: ClientId,
+    pub keys: Vec<SharedKey>,
+    pub transcript_hash: TranscriptHash,
+    pub started: Instant,
+}
+
+/// The key exchanges waiting for confirmation, by session ID. Expired exchanges are removed
+/// whenever an exchange is added or taken.
+pub struct PendingExchanges<M> {
+    exchanges: HashMap<u64, PendingKex<M>>,
+    /// Session IDs in the order the exchanges were added, which is also the order they expire in.
+    /// May hold IDs of exchanges that are already taken, they are skipped.
+    expiry: VecDeque<u64>,
+    per_client: HashMap<ClientId, usize>,
+}
+
+impl<M> PendingExchanges<M> {
+    pub fn new() -> Self {
+        PendingExchanges {
+            exchanges: HashMap::new(),
+            expiry: VecDeque::new(),
+            per_client: HashMap::new(),
+        }
+    }
+
+    /// Checks that an exchange from `client` can be added, so the work of computing it can be
+    /// skipped if it can't.
+    pub fn check_room(&mut self, client: ClientId) -> Result<()> {
+        self.remove_expired();
+        ensure!(self.exchanges.len() < MAX_PENDING, ErrorKind::BusyError);
+        let client_pending = self.per_client.get(&client).cloned().unwrap_or(0);
+        ensure!(
+            client_pending < MAX_PENDING_PER_CLIENT,
+            ErrorKind::RateLimitError
+        );
+        Ok(())
+    }
+
+    /// Adds `kex` under a new random session ID, and returns the ID.
+    pub fn insert(&mut self, rand: &OqsRand, kex: PendingKex<M>) -> Result<u64> {
+        self.check_room(kex.client)?;
+        let mut session_id = rand.rand_64().chain_err(|| ErrorKind::OqsError)?;
+        while self.exchanges.contains_key(&session_id) {
+            session_id = rand.rand_64().chain_err(|| ErrorKind::OqsError)?;
+        }
+        *self.per_client.entry(kex.client).or_insert(0) += 1;
+        self.exchanges.insert(session_id, kex);
+        self.expiry.push_back(session_id);
+        Ok(session_id)
+    }
+
+    /// Removes and returns the exchange with `session_id`, unless it has expired.
+    pub fn take(&mut self, session_id: u64) -> Option<PendingKex<M>> {
+        self.remove_expired();
+        let kex = self.exchanges.remove(&session_id)?;
+        self.release(kex.client);
+        Some(kex)
+    }
+
+    fn remove_expired(&mut self) {
+        while let Some(&session_id) = self.expiry.front() {
+            let expired = match self.exchanges.get(&session_id) {
+                Some(kex) => kex.started.elapsed() >= CONFIRMATION_TIMEOUT,
+                None => true,
+            };
+            if !expired {
+                break;
+            }
+            self.expiry.pop_front();
+            if let Some(kex) = self.exchanges.remove(&session_id) {
+                self.release(kex.client);
+            }
+        }
+    }
+
+    fn release(&mut self, client: ClientId) {
+        let remaining = match self.per_client.get_mut(&client) {
+            Some(count) => {
+                *count -= 1;
+                *count
+            }
+            None => return,
+        };
+        if remaining == 0 {
+            self.per_client.remove(&client);
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oqs::kex::AliceMsg;
+    use oqs::rand::OqsRandAlg;
+    use server::Error;
+    use transcript::Transcript;
+
+    fn kex(client: ClientId, started: Instant) -> PendingKex<()> {
+        PendingKex {
+            meta: (),
+            client,
+            keys: Vec::new(),
+            transcript_hash: Transcript::from_msgs::<AliceMsg>(&[], &[]).hash(),
+            started,
+        }
+    }
+
+    fn is_rate_limited(error: &Error) -> bool {
+        matches!(*error.kind(), ErrorKind::RateLimitError)
+    }
+
+    #[test]
+    fn insert_and_take() {
+        let rand = OqsRand::new(OqsRandAlg::default()).unwrap();
+        let mut pending = PendingExchanges::new();
+        let session_id = pending.insert(&rand, kex(ClientId::Uid(1), Instant::now())).unwrap();
+        assert!(pending.take(session_id).is_some());
+        assert!(pending.take(session_id).is_none());
+    }
+
+    #[test]
+    fn limit_per_client() {
+        let rand = OqsRand::new(OqsRandAlg::default()).unwrap();
+        let mut pending = PendingExchanges::new();
+        let now = Instant::now();
+        let session_ids: Vec<u64> = (0..MAX_PENDING_PER_CLIENT)
+            .map(|_| pending.insert(&rand, kex(ClientId::Uid(1), now)).unwrap())
+            .collect();
+        let error = pending.insert(&rand, kex(ClientId::Uid(1), now)).unwrap_err();
+        assert!(is_rate_limited(&error));
+        assert!(pending.insert(&rand, kex(ClientId::Uid(2), now)).is_ok());
+
+        pending.take(session_ids[0]).unwrap();
+        assert!(pending.insert(&rand, kex(ClientId::Uid(1), now)).is_ok());
+    }
+
+    #[test]
+    fn expired_exchanges_are_removed() {
+        let rand = OqsRand::new(OqsRandAlg::default()).unwrap();
+        let mut pending = PendingExchanges::new();
+        // An `Instant` can't go further back than the clock it's read from, e.g. system boot.
+        let expired = match Instant::now().checked_sub(CONFIRMATION_TIMEOUT) {
+            Some(expired) => expired,
+            None => return,
+        };
+        let session_id = pending.insert(&rand, kex(ClientId::Uid(1), expired)).unwrap();
+        pending.insert(&rand, kex(ClientId::Uid(1), Instant::now())).unwrap();
+        assert!(pending.take(session_id).is_none());
+        assert_eq!(pending.exchanges.len(), 1);
+        assert_eq!(pending.per_client[&ClientId::Uid(1)], 1);
+    }
+}
@@ -0,0 +1,258 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Per client rate limiting of key exchanges, using one token bucket per client.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// Max number of clients the server tracks the buckets of. When a new client arrives while this
+/// many are tracked, a quarter of the buckets are removed.
+const MAX_CLIENTS: usize = 65_536;
+
+/// Token bucket rate limiting of key exchanges, applied per client.
+///
+/// Every client has a bucket holding at most `capacity` tokens, refilled with `refill_per_second`
+/// tokens per second. A key exchange costs as many tokens as its cost in the server's
+/// [`AlgorithmCosts`], and is rejected if the client's bucket does not hold that many tokens. A
+/// new client starts with a full bucket.
+///
+/// Clients are told apart by their IPv4 address, by the /64 network of their IPv6 address, or,
+/// on a Unix domain socket, by the user ID of the client process.
+///
+/// [`AlgorithmCosts`]: ../struct.AlgorithmCosts.html
+#[derive(Debug, Clone)]
+pub struct RateLimit {
+    /// Max number of tokens in a bucket. The largest burst of work a client can cause.
+    pub capacity: u32,
+    /// Number of tokens added to every bucket per second.
+    pub refill_per_second: u32,
+}
+
+impl RateLimit {
+    /// Creates a rate limit with the given bucket capacity and refill rate.
+    pub fn new(capacity: u32, refill_per_second: u32) -> Self {
+        RateLimit {
+            capacity,
+            refill_per_second,
+        }
+    }
+}
+
+
+/// What the rate limit tells clients apart by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ClientId {
+    /// An IPv4 address, or an IPv4 address mapped into IPv6.
+    Ipv4([u8; 4]),
+    /// The /64 network of the address. Hosts commonly get a whole /64, so keying on the full
+    /// address would let a client pick a fresh bucket for every request.
+    Ipv6([u8; 8]),
+    /// The user ID of a process connected over a Unix domain socket.
+    Uid(u32),
+    /// A client the server knows nothing about. All of them share one bucket.
+    #[default]
+    Unknown,
+}
+
+impl ClientId {
+    /// Returns the key of a client connecting from `ip`.
+    pub fn from_ip(ip: IpAddr) -> Self {
+        match ip {
+            IpAddr::V4(ip) => ClientId::Ipv4(ip.octets()),
+            IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+                Some(ip) => ClientId::Ipv4(ip.octets()),
+                None => {
+                    let mut network = [0; 8];
+                    network.copy_from_slice(&ip.octets()[..8]);
+                    ClientId::Ipv6(network)
+                }
+            },
+        }
+    }
+}
+
+
+/// Keeps track of the token buckets of all clients.
+pub struct RateLimiter {
+    limit: RateLimit,
+    max_clients: usize,
+    buckets: Mutex<HashMap<ClientId, Bucket>>,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(limit: RateLimit) -> Self {
+        Self::with_max_clients(limit, MAX_CLIENTS)
+    }
+
+    fn with_max_clients(limit: RateLimit, max_clients: usize) -> Self {
+        RateLimiter {
+            limit,
+            max_clients,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Takes `cost` tokens from the bucket of `client`. Returns `false`, without taking any
+    /// tokens, if the bucket does not hold enough of them.
+    pub fn try_acquire(&self, client: ClientId, cost: u64) -> bool {
+        self.try_acquire_at(client, cost, Instant::now())
+    }
+
+    fn try_acquire_at(&self, client: ClientId, cost: u64, now: Instant) -> bool {
+        let cost = cost as f64;
+        let mut buckets = self.buckets.lock().unwrap();
+        let tokens = match buckets.get(&client) {
+            Some(bucket) => self.refilled(bucket, now),
+            None => {
+                if buckets.len() >= self.max_clients {
+                    self.evict(&mut buckets, now);
+                }
+                f64::from(self.limit.capacity)
+            }
+        };
+        let allowed = cost <= tokens;
+        let tokens = if allowed { tokens - cost } else { tokens };
+        buckets.insert(
+            client,
+            Bucket {
+                tokens,
+                updated: now,
+            },
+        );
+        allowed
+    }
+
+    /// Removes a quarter of the buckets, so this only runs once every `max_clients / 4` new
+    /// clients. Buckets that have refilled completely go first, since they would be recreated full
+    /// anyway, then the least recently used ones. A client whose bucket is removed starts over
+    /// with a full bucket, so a large number of clients can refill each other's buckets early, but
+    /// never grow the memory use past `max_clients` buckets.
+    fn evict(&self, buckets: &mut HashMap<ClientId, Bucket>, now: Instant) {
+        let capacity = f64::from(self.limit.capacity);
+        buckets.retain(|_, bucket| self.refilled(bucket, now) < capacity);
+
+        let keep = self.max_clients * 3 / 4;
+        if buckets.len() > keep {
+            let mut updated: Vec<Instant> = buckets.values().map(|bucket| bucket.updated).collect();
+            let index = buckets.len() - keep - 1;
+            let cutoff = *updated.select_nth_unstable(index).1;
+            buckets.retain(|_, bucket| bucket.updated > cutoff);
+        }
+    }
+
+    /// Returns the number of tokens in `bucket` at `now`.
+    fn refilled(&self, bucket: &Bucket, now: Instant) -> f64 {
+        let elapsed = now.duration_since(bucket.updated);
+        let seconds = elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) * 1e-9;
+        let tokens = bucket.tokens + seconds * f64::from(self.limit.refill_per_second);
+        tokens.min(f64::from(self.limit.capacity))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn client(last_octet: u8) -> ClientId {
+        ClientId::from_ip(IpAddr::from([10, 0, 0, last_octet]))
+    }
+
+    #[test]
+    fn burst_then_reject() {
+        let limiter = RateLimiter::new(RateLimit::new(3, 1));
+        let now = Instant::now();
+        for _ in 0..3 {
+            assert!(limiter.try_acquire_at(client(1), 1, now));
+        }
+        assert!(!limiter.try_acquire_at(client(1), 1, now));
+    }
+
+    #[test]
+    fn refills_over_time() {
+        let limiter = RateLimiter::new(RateLimit::new(2, 1));
+        let now = Instant::now();
+        assert!(limiter.try_acquire_at(client(1), 2, now));
+        assert!(!limiter.try_acquire_at(client(1), 2, now + Duration::from_secs(1)));
+        assert!(limiter.try_acquire_at(client(1), 2, now + Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn clients_are_independent() {
+        let limiter = RateLimiter::new(RateLimit::new(1, 0));
+        let now = Instant::now();
+        assert!(limiter.try_acquire_at(client(1), 1, now));
+        assert!(!limiter.try_acquire_at(client(1), 1, now));
+        assert!(limiter.try_acquire_at(client(2), 1, now));
+        assert!(limiter.try_acquire_at(ClientId::Uid(1000), 1, now));
+        assert!(!limiter.try_acquire_at(ClientId::Uid(1000), 1, now));
+        assert!(limiter.try_acquire_at(ClientId::Uid(1001), 1, now));
+        assert!(limiter.try_acquire_at(ClientId::Unknown, 1, now));
+        assert!(!limiter.try_acquire_at(ClientId::Unknown, 1, now));
+    }
+
+    #[test]
+    fn rejected_request_takes_no_tokens() {
+        let limiter = RateLimiter::new(RateLimit::new(10, 0));
+        let now = Instant::now();
+        assert!(limiter.try_acquire_at(client(1), 8, now));
+        assert!(!limiter.try_acquire_at(client(1), 8, now));
+        assert!(limiter.try_acquire_at(client(1), 2, now));
+    }
+
+    #[test]
+    fn ipv6_keyed_by_network() {
+        let ip = |host: u16| IpAddr::from([0x2001, 0xdb8, 0, 1, 0, 0, 0, host]);
+        assert_eq!(ClientId::from_ip(ip(1)), ClientId::from_ip(ip(2)));
+        let other_network = IpAddr::from([0x2001, 0xdb8, 0, 2, 0, 0, 0, 1]);
+        assert_ne!(ClientId::from_ip(ip(1)), ClientId::from_ip(other_network));
+
+        let mapped = IpAddr::from([0, 0, 0, 0, 0, 0xffff, 0x0a00, 0x0001]);
+        assert_eq!(ClientId::from_ip(mapped), client(1));
+    }
+
+    #[test]
+    fn bounded_number_of_clients() {
+        let limiter = RateLimiter::with_max_clients(RateLimit::new(1, 0), 8);
+        let now = Instant::now();
+        for i in 0..100u32 {
+            let at = now + Duration::from_millis(u64::from(i));
+            assert!(limiter.try_acquire_at(ClientId::Uid(i), 1, at));
+            assert!(limiter.buckets.lock().unwrap().len() <= 8);
+        }
+        // The most recently used clients are still tracked, with empty buckets.
+        let later = now + Duration::from_secs(1);
+        assert!(!limiter.try_acquire_at(ClientId::Uid(99), 1, later));
+        assert!(!limiter.try_acquire_at(ClientId::Uid(98), 1, later));
+    }
+
+    #[test]
+    fn evicts_refilled_buckets_first() {
+        let limiter = RateLimiter::with_max_clients(RateLimit::new(1, 1), 4);
+        let now = Instant::now();
+        assert!(limiter.try_acquire_at(ClientId::Uid(0), 1, now));
+        for i in 1..4 {
+            let at = now + Duration::from_secs(2);
+            assert!(limiter.try_acquire_at(ClientId::Uid(i), 1, at));
+        }
+        // Uid(0) has refilled and is removed, the others are kept.
+        let at = now + Duration::from_secs(2);
+        assert!(limiter.try_acquire_at(ClientId::Uid(4), 1, at));
+        let buckets = limiter.buckets.lock().unwrap();
+        assert!(!buckets.contains_key(&ClientId::Uid(0)));
+        assert_eq!(buckets.len(), 4);
+    }
+}
@@ -10,24 +10,49 @@
 //! Use the [`start`](fn.start.html) function to spawn a key exchange server.
 
 use futures;
+use futures::{Future, IntoFuture};
+use num_cpus;
 use oqs;
+use oqs::async_kex::OqsKexPool;
 use oqs::kex::{AliceMsg, BobMsg, OqsKex, OqsKexAlg, SharedKey};
-use oqs::rand::{OqsRand, OqsRandAlg};
+use oqs::rand::OqsRand;
+use oqs::sig::{OqsSig, SecretKey, Signature};
+
+use binary::{BinaryServer, MetaExtractor as BinaryMetaExtractor};
+use confirm::{self, Role};
+use cost::AlgorithmCosts;
+use error_codes;
+use messages::{Capabilities, ConstraintViolation, KexResponse, UnconfirmedKex};
+use pending::{PendingExchanges, PendingKex, CONFIRMATION_TIMEOUT};
+use rate_limit::{ClientId, RateLimiter};
+use tcp::TcpServer;
+#[cfg(feature = "tls")]
+use tls::{ServerTlsConfig, TlsServer};
+use transcript::{self, ExchangedKeys, Transcript, TranscriptHash};
+use websocket::WebSocketServer;
+#[cfg(unix)]
+use unix::UnixServer;
 
 use error_chain::ChainedError;
+use serde_json;
 
 use std::collections::HashMap;
+use std::fmt;
 use std::net::SocketAddr;
-use std::marker::PhantomData;
+#[cfg(unix)]
+use std::path::{Path, PathBuf};
 use std::result::Result as StdResult;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
-use jsonrpc_core::{BoxFuture, Error as JsonError, MetaIoHandler};
-use jsonrpc_http_server::ServerBuilder;
+use jsonrpc_core::{BoxFuture, Error as JsonError, ErrorCode, MetaIoHandler};
+use jsonrpc_http_server::hyper::StatusCode;
 use jsonrpc_http_server::hyper::header::ContentLength;
-use jsonrpc_http_server::hyper::error::Error as HyperError;
+use jsonrpc_http_server::hyper::server::Response;
 
 pub use jsonrpc_core::Metadata;
-pub use jsonrpc_http_server::{MetaExtractor, RequestMiddleware, RequestMiddlewareAction, Server};
+pub use rate_limit::RateLimit;
+pub use jsonrpc_http_server::{MetaExtractor, RequestMiddleware, RequestMiddlewareAction};
 pub use jsonrpc_http_server::hyper::server::Request;
 
 
@@ -40,60 +65,458 @@ error_chain! {
         /// There was an error in the user supplied callback.
         CallbackError { description("Error in on_kex callback") }
         /// The client RPC message did not meet configured server constraints.
-        ConstraintError { description("Client RPC message does not meet constraints") }
+        ConstraintError(violation: ConstraintViolation) {
+            description("Client RPC message does not meet constraints")
+            display("Client RPC message does not meet constraints: {}", violation)
+        }
+        /// The client did not confirm a key exchange in time, or sent an invalid confirmation.
+        ConfirmationError { description("Key confirmation failed") }
+        /// All worker threads are busy and too many key exchanges are already waiting for one.
+        BusyError { description("Too many key exchanges waiting for a worker thread") }
+        /// The client has used up its rate limit.
+        RateLimitError { description("Client exceeded its rate limit") }
     }
 }
 
+/// Future resolving when the server is done with a request, or with the `on_kex` callback.
+pub(crate) type ServerFuture<T> = Box<dyn Future<Item = T, Error = Error> + Send>;
+
+/// The number of tokens a key confirmation takes from the client's bucket of the `rate_limit`.
+const CONFIRMATION_COST: u64 = 1;
+
+/// Header the server passes the client certificate of a TLS connection to the `MetaExtractor` in.
+pub(crate) const PEER_CERTIFICATE_HEADER: &str = "X-Oqs-Kex-Peer-Certificate";
+
+/// Header the server passes the peer credentials of a Unix domain socket connection to the
+/// `MetaExtractor` in.
+pub(crate) const PEER_CREDENTIALS_HEADER: &str = "X-Oqs-Kex-Peer-Credentials";
 
-/// Tries to start a HTTP JSON-RPC 2.0 server bound to `addr`.
+/// Headers the server sets itself from what it knows about the connection. Servers setting them
+/// remove any the client sent, and plain HTTP servers reject requests having them, so clients
+/// can't forge them.
+pub(crate) const TRUSTED_HEADERS: &[&str] = &[PEER_CERTIFICATE_HEADER, PEER_CREDENTIALS_HEADER];
+
+
+/// Tries to start a HTTP JSON-RPC 2.0 server bound to `addr`. `addr` is a TCP socket address, or
+/// on Unix a [`ListenAddress::Unix`] path to create a Unix domain socket at.
 ///
 /// Will call `on_kex` as soon as the shared keys has been computed on the server (Bob actor),
 /// but before Bob's messages are returned to the client. If this callback returns an error,
 /// then an error will be returned to the client instead of Bob's messages.
 ///
+/// Failed requests are answered with one of the JSON-RPC error codes in
+/// [`error_codes`](../error_codes/index.html), so clients can tell why the request failed.
+///
 /// `meta_extractor` should be a type that, given a HTTP request, should compute some metadata that
 /// one wants to associate with the final shared key. The `meta_extractor` will be called before
 /// any key exchange starts, and the resulting metadata will be fed to `on_kex` together with the
 /// resulting shared keys.
 ///
+/// `on_kex` gets the shared keys as an [`ExchangedKeys`], which also holds the transcript hash
+/// over all messages in the exchange. It is the same hash the client returns, so both sides can
+/// bind the keys they derive to the full handshake.
+///
+/// [`ExchangedKeys`]: ../transcript/struct.ExchangedKeys.html
+///
 /// The `constraints` can be used to protect from abuse. It can limit which algorithms the server
 /// accepts and how many keys can be exchanged per request.
-pub fn start<ME, M, E, F>(
-    addr: SocketAddr,
+///
+/// If `constraints` holds a [`signing_key`](struct.ServerConstraints.html#structfield.signing_key),
+/// the server signs the transcript hash of every exchange with it. Clients created with
+/// [`OqsKexClient::with_server_key`] verify that signature before accepting the keys.
+///
+/// Clients doing a key exchange with confirmation, see
+/// [`OqsKexClient::kex_with_confirmation`], get a MAC over the exchange together with Bob's
+/// messages. For those exchanges `on_kex` is instead called after the client has sent back its own
+/// MAC and the server has verified it. Set
+/// [`ServerConstraints::require_confirmation`](struct.ServerConstraints.html) to only accept
+/// confirmed exchanges.
+///
+/// The key exchanges are computed on a pool of worker threads, configured with
+/// [`ServerConstraints::worker_threads`] and [`ServerConstraints::max_queued_exchanges`].
+///
+/// [`ServerConstraints::worker_threads`]: struct.ServerConstraints.html#structfield.worker_threads
+/// [`ServerConstraints::max_queued_exchanges`]:
+/// struct.ServerConstraints.html#structfield.max_queued_exchanges
+///
+/// All connections are served on one thread, but `on_kex` is called on the worker pool, so a slow
+/// `on_kex` only takes up a worker thread until it returns. Use
+/// [`start_async`](fn.start_async.html) for callbacks that have to wait for I/O.
+///
+/// With the `tls` feature, set `ServerConstraints::tls` to serve HTTPS instead of plain HTTP. See
+/// the [`tls`](../tls/index.html) module.
+///
+/// When listening on a Unix domain socket, the `MetaExtractor` can get the credentials of the
+/// client process with [`unix::peer_credentials`](../unix/fn.peer_credentials.html). Such clients
+/// have no IP address, so the `rate_limit` keeps one bucket per user ID instead. TLS is not used on
+/// Unix domain sockets.
+///
+/// [`ListenAddress::Unix`]: enum.ListenAddress.html#variant.Unix
+/// [`OqsKexClient::with_server_key`]: ../client/struct.OqsKexClient.html#method.with_server_key
+/// [`OqsKexClient::kex_with_confirmation`]:
+/// ../client/struct.OqsKexClient.html#method.kex_with_confirmation
+pub fn start<A, ME, M, E, F>(
+    addr: A,
     meta_extractor: ME,
     on_kex: F,
     constraints: ServerConstraints,
 ) -> Result<Server>
 where
+    A: Into<ListenAddress>,
     M: Metadata + Sync,
     ME: MetaExtractor<M>,
     E: ::std::error::Error + Send + 'static,
-    F: Fn(M, Vec<SharedKey>) -> StdResult<(), E> + Send + Sync + 'static,
+    F: Fn(M, ExchangedKeys) -> StdResult<(), E> + Send + Sync + 'static,
+{
+    start_server(addr.into(), meta_extractor, SyncCallback(on_kex), constraints)
+}
+
+/// Tries to start a HTTP JSON-RPC 2.0 server bound to `addr`, with an asynchronous `on_kex`
+/// callback.
+///
+/// Works like [`start`](fn.start.html), except that `on_kex` returns a future instead of a
+/// result. The server replies to the client once the future resolves, with an error if the future
+/// fails. Neither the thread serving all connections nor the worker pool is blocked while waiting
+/// for the future, so `on_kex` can install the keys in a remote store or database without holding
+/// up other requests. `on_kex` itself is called on the thread serving the connections, and must
+/// return its future without blocking.
+pub fn start_async<A, ME, M, R, F>(
+    addr: A,
+    meta_extractor: ME,
+    on_kex: F,
+    constraints: ServerConstraints,
+) -> Result<Server>
+where
+    A: Into<ListenAddress>,
+    M: Metadata + Sync,
+    ME: MetaExtractor<M>,
+    R: IntoFuture<Item = ()>,
+    R::Future: Send + 'static,
+    R::Error: ::std::error::Error + Send + 'static,
+    F: Fn(M, ExchangedKeys) -> R + Send + Sync + 'static,
+{
+    start_server(addr.into(), meta_extractor, AsyncCallback(on_kex), constraints)
+}
+
+fn start_server<ME, M, C>(
+    addr: ListenAddress,
+    meta_extractor: ME,
+    on_kex: C,
+    constraints: ServerConstraints,
+) -> Result<Server>
+where
+    M: Metadata + Sync,
+    ME: MetaExtractor<M>,
+    C: KexCallback<M>,
+{
+    let max_request_size = constraints.max_request_size;
+    #[cfg(feature = "tls")]
+    let tls_config = constraints.tls.clone();
+
+    let io = io_handler(on_kex, constraints)?;
+    let meta_extractor = RequestMetaExtractor(meta_extractor);
+
+    let addr = match addr {
+        ListenAddress::Tcp(addr) => addr,
+        #[cfg(unix)]
+        ListenAddress::Unix(path) => {
+            let middleware = ServerConstraintsMiddleware::new(max_request_size, true);
+            return UnixServer::start(&path, io, meta_extractor, middleware, max_request_size)
+                .map(|server| Server {
+                    address: ListenAddress::Unix(path),
+                    listener: Listener::Unix(server),
+                })
+                .chain_err(|| ErrorKind::RpcError);
+        }
+    };
+
+    #[cfg(feature = "tls")]
+    {
+        if let Some(tls_config) = tls_config {
+            let middleware = ServerConstraintsMiddleware::new(max_request_size, true);
+            let server = TlsServer::start(
+                &addr,
+                &tls_config,
+                io,
+                meta_extractor,
+                middleware,
+                max_request_size,
+            );
+            return server
+                .map(|server| Server {
+                    address: ListenAddress::Tcp(*server.address()),
+                    listener: Listener::Tls(server),
+                })
+                .chain_err(|| ErrorKind::RpcError);
+        }
+    }
+
+    let middleware = ServerConstraintsMiddleware::new(max_request_size, false);
+    TcpServer::start(&addr, io, meta_extractor, middleware, max_request_size)
+        .map(|server| Server {
+            address: ListenAddress::Tcp(*server.address()),
+            listener: Listener::Http(server),
+        })
+        .chain_err(|| ErrorKind::RpcError)
+}
+
+/// Starts a server speaking the binary protocol, see the [`binary`](../binary/index.html) module.
+pub(crate) fn start_binary_server<ME, M, C>(
+    addr: SocketAddr,
+    meta_extractor: ME,
+    on_kex: C,
+    constraints: ServerConstraints,
+) -> Result<Server>
+where
+    M: Metadata + Sync,
+    ME: BinaryMetaExtractor<M>,
+    C: KexCallback<M>,
+{
+    #[cfg(feature = "tls")]
+    {
+        if constraints.tls.is_some() {
+            bail!(ErrorKind::RpcError);
+        }
+    }
+    let max_request_size = constraints.max_request_size;
+    let server = OqsKexRpcServer::new(on_kex, constraints)?;
+    BinaryServer::start(&addr, server, meta_extractor, max_request_size)
+        .map(|server| Server {
+            address: ListenAddress::Tcp(*server.address()),
+            listener: Listener::Binary(server),
+        })
+        .chain_err(|| ErrorKind::RpcError)
+}
+
+/// Starts a server accepting WebSocket connections, see the [`websocket`](../websocket/index.html)
+/// module.
+pub(crate) fn start_websocket_server<ME, M, C>(
+    addr: SocketAddr,
+    meta_extractor: ME,
+    on_kex: C,
+    constraints: ServerConstraints,
+) -> Result<Server>
+where
+    M: Metadata + Sync,
+    ME: MetaExtractor<M>,
+    C: KexCallback<M>,
 {
+    #[cfg(feature = "tls")]
+    {
+        if constraints.tls.is_some() {
+            bail!(ErrorKind::RpcError);
+        }
+    }
     let max_request_size = constraints.max_request_size;
+    let io = io_handler(on_kex, constraints)?;
+    WebSocketServer::start(&addr, io, meta_extractor, max_request_size)
+        .map(|server| Server {
+            address: ListenAddress::Tcp(*server.address()),
+            listener: Listener::WebSocket(server),
+        })
+        .chain_err(|| ErrorKind::RpcError)
+}
 
-    let server = OqsKexRpcServer::new(on_kex, constraints);
+/// Returns the JSON-RPC handler of a server calling `on_kex`.
+fn io_handler<M, C>(
+    on_kex: C,
+    constraints: ServerConstraints,
+) -> Result<MetaIoHandler<RequestMeta<M>>>
+where
+    M: Metadata + Sync,
+    C: KexCallback<M>,
+{
+    let server = OqsKexRpcServer::new(on_kex, constraints)?;
     let mut io = MetaIoHandler::default();
     io.extend_with(server.to_delegate());
+    Ok(io)
+}
 
-    ServerBuilder::new(io)
-        .request_middleware(ServerConstraintsMiddleware::new(max_request_size))
-        .meta_extractor(meta_extractor)
-        .start_http(&addr)
-        .chain_err(|| ErrorKind::RpcError)
+
+/// The address a server listens on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ListenAddress {
+    /// A TCP socket address.
+    Tcp(SocketAddr),
+    /// The path of a Unix domain socket. The server creates the socket when started, and removes
+    /// it when stopped. Starting fails if the path already exists. Only available on Unix.
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+impl ListenAddress {
+    /// Returns the TCP socket address, if this is one.
+    pub fn tcp(&self) -> Option<&SocketAddr> {
+        match *self {
+            ListenAddress::Tcp(ref addr) => Some(addr),
+            #[cfg(unix)]
+            ListenAddress::Unix(_) => None,
+        }
+    }
+
+    /// Returns the path of the Unix domain socket, if this is one.
+    #[cfg(unix)]
+    pub fn unix(&self) -> Option<&Path> {
+        match *self {
+            ListenAddress::Tcp(_) => None,
+            ListenAddress::Unix(ref path) => Some(path),
+        }
+    }
+}
+
+impl From<SocketAddr> for ListenAddress {
+    fn from(addr: SocketAddr) -> Self {
+        ListenAddress::Tcp(addr)
+    }
+}
+
+#[cfg(unix)]
+impl From<PathBuf> for ListenAddress {
+    fn from(path: PathBuf) -> Self {
+        ListenAddress::Unix(path)
+    }
+}
+
+#[cfg(unix)]
+impl<'a> From<&'a Path> for ListenAddress {
+    fn from(path: &'a Path) -> Self {
+        ListenAddress::Unix(path.to_owned())
+    }
+}
+
+impl fmt::Display for ListenAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ListenAddress::Tcp(ref addr) => addr.fmt(f),
+            #[cfg(unix)]
+            ListenAddress::Unix(ref path) => path.display().fmt(f),
+        }
+    }
+}
+
+
+/// A running key exchange server, returned by [`start`](fn.start.html) and
+/// [`start_async`](fn.start_async.html), or the functions starting a server speaking the
+/// [`binary`](../binary/index.html) protocol or accepting [`websocket`](../websocket/index.html)
+/// connections. The server stops when this is dropped.
+pub struct Server {
+    address: ListenAddress,
+    listener: Listener,
+}
+
+enum Listener {
+    Http(TcpServer),
+    #[cfg(feature = "tls")]
+    Tls(TlsServer),
+    #[cfg(unix)]
+    Unix(UnixServer),
+    Binary(BinaryServer),
+    WebSocket(WebSocketServer),
+}
+
+impl Server {
+    /// Returns the address the server is listening on.
+    pub fn address(&self) -> &ListenAddress {
+        &self.address
+    }
+
+    /// Stops the server.
+    pub fn close(self) {
+        drop(self.listener)
+    }
+
+    /// Blocks until the server has stopped.
+    pub fn wait(self) {
+        match self.listener {
+            Listener::Http(server) => server.wait(),
+            #[cfg(feature = "tls")]
+            Listener::Tls(server) => server.wait(),
+            #[cfg(unix)]
+            Listener::Unix(server) => server.wait(),
+            Listener::Binary(server) => server.wait(),
+            Listener::WebSocket(server) => server.wait(),
+        }
+    }
+}
+
+/// A key exchange server that does not listen on any socket. Clients send requests to it with a
+/// [`LoopbackTransport`], which passes them on directly in memory. Meant for testing code using
+/// [`OqsKexClient`] without starting a real server.
+///
+/// Handles requests like a server started with [`start`](fn.start.html), except for what depends
+/// on HTTP. There is no `MetaExtractor`, every transport is instead created with the metadata to
+/// pass to `on_kex`. `max_request_size` is not checked, and all transports share one bucket of the
+/// `rate_limit`.
+///
+/// Cloning it gives a new handle to the same server.
+///
+/// [`LoopbackTransport`]: ../client/struct.LoopbackTransport.html
+/// [`OqsKexClient`]: ../client/struct.OqsKexClient.html
+#[derive(Clone)]
+pub struct LoopbackServer<M: Metadata> {
+    io: Arc<MetaIoHandler<RequestMeta<M>>>,
+}
+
+impl<M: Metadata + Sync> LoopbackServer<M> {
+    /// Creates a server calling `on_kex` for every exchange, like [`start`](fn.start.html) does.
+    /// Fails if the server's entropy source can't be selected.
+    pub fn new<E, F>(on_kex: F, constraints: ServerConstraints) -> Result<Self>
+    where
+        E: ::std::error::Error + Send + 'static,
+        F: Fn(M, ExchangedKeys) -> StdResult<(), E> + Send + Sync + 'static,
+    {
+        Ok(LoopbackServer {
+            io: Arc::new(io_handler(SyncCallback(on_kex), constraints)?),
+        })
+    }
+
+    /// Creates a server with an asynchronous `on_kex` callback, like
+    /// [`start_async`](fn.start_async.html) does.
+    pub fn new_async<R, F>(on_kex: F, constraints: ServerConstraints) -> Result<Self>
+    where
+        R: IntoFuture<Item = ()>,
+        R::Future: Send + 'static,
+        R::Error: ::std::error::Error + Send + 'static,
+        F: Fn(M, ExchangedKeys) -> R + Send + Sync + 'static,
+    {
+        Ok(LoopbackServer {
+            io: Arc::new(io_handler(AsyncCallback(on_kex), constraints)?),
+        })
+    }
+
+    /// Handles a JSON-RPC request with the given metadata, and returns the response. Blocks until
+    /// the response is ready.
+    pub(crate) fn handle_request(&self, request: &str, meta: M) -> Option<String> {
+        let meta = RequestMeta {
+            meta,
+            client: ClientId::Unknown,
+        };
+        self.io.handle_request_sync(request, meta)
+    }
 }
 
 
 mod api {
     use jsonrpc_core::{BoxFuture, Error};
-    use oqs::kex::{AliceMsg, BobMsg};
+    use messages::{Capabilities, KexResponse, UnconfirmedKex};
+    use oqs::kex::AliceMsg;
 
     build_rpc_trait! {
         pub trait OqsKexRpcServerApi {
             type Metadata;
 
             #[rpc(meta, name = "kex")]
-            fn kex(&self, Self::Metadata, Vec<AliceMsg>) -> BoxFuture<Vec<BobMsg>, Error>;
+            fn kex(&self, Self::Metadata, Vec<AliceMsg>) -> BoxFuture<KexResponse, Error>;
+
+            #[rpc(meta, name = "kex_with_confirmation")]
+            fn kex_with_confirmation(&self, Self::Metadata, Vec<AliceMsg>)
+                -> BoxFuture<UnconfirmedKex, Error>;
+
+            #[rpc(meta, name = "confirm")]
+            fn confirm(&self, Self::Metadata, u64, Vec<u8>) -> BoxFuture<(), Error>;
+
+            #[rpc(name = "capabilities")]
+            fn capabilities(&self) -> BoxFuture<Capabilities, Error>;
         }
     }
 }
@@ -103,7 +526,14 @@ use self::api::OqsKexRpcServerApi;
 /// Defines a runtime configuration with constraints the server must adhere to.
 #[derive(Default, Clone)]
 pub struct ServerConstraints {
-    /// Maximum size of incoming HTTP request.
+    /// Maximum size in bytes of the body of incoming HTTP requests. Larger requests are answered
+    /// with `413 Payload Too Large`, as soon as the `Content-Length` header or, for chunked
+    /// bodies, the bytes read so far exceed the limit. Servers speaking the [`binary`] protocol
+    /// instead limit the payload of every frame, and servers accepting [`websocket`] connections
+    /// every message.
+    ///
+    /// [`binary`]: ../binary/index.html
+    /// [`websocket`]: ../websocket/index.html
     pub max_request_size: Option<usize>,
     /// Identifiers of all algorithms to enable in the server.
     pub algorithms: Option<Vec<OqsKexAlg>>,
@@ -111,6 +541,28 @@ pub struct ServerConstraints {
     pub max_algorithms: Option<usize>,
     /// Max number of times a specific algorithm is allowed to occur in a single RPC message.
     pub max_occurrences: Option<usize>,
+    /// Reject key exchanges that are not confirmed by the client. When set, `on_kex` is only
+    /// called for keys the client has proven it derived as well.
+    pub require_confirmation: bool,
+    /// Long-term key the server signs the transcript hash of every exchange with. Lets clients
+    /// pinning the matching public key detect a man-in-the-middle.
+    pub signing_key: Option<SecretKey>,
+    /// Number of worker threads computing key exchanges. Defaults to one per CPU.
+    pub worker_threads: Option<usize>,
+    /// Max number of key exchanges waiting for a free worker thread. Further key exchanges are
+    /// rejected as busy until the queue drains.
+    pub max_queued_exchanges: Option<usize>,
+    /// The cost of every algorithm, used for `max_request_cost` and `rate_limit`.
+    pub algorithm_costs: AlgorithmCosts,
+    /// Max total cost of the algorithms in a single RPC message.
+    pub max_request_cost: Option<u64>,
+    /// Rate limit applied to every client, see [`RateLimit`](struct.RateLimit.html). Checked
+    /// before any key exchange work is started for a request, and before every key confirmation,
+    /// which costs 1.
+    pub rate_limit: Option<RateLimit>,
+    /// Accept TLS connections only, with this configuration. Requires the `tls` feature.
+    #[cfg(feature = "tls")]
+    pub tls: Option<ServerTlsConfig>,
 }
 
 impl ServerConstraints {
@@ -126,12 +578,41 @@ impl ServerConstraints {
             algorithms,
             max_algorithms,
             max_occurrences,
+            require_confirmation: false,
+            signing_key: None,
+            worker_threads: None,
+            max_queued_exchanges: None,
+            algorithm_costs: AlgorithmCosts::default(),
+            max_request_cost: None,
+            rate_limit: None,
+            #[cfg(feature = "tls")]
+            tls: None,
+        }
+    }
+
+    /// Returns the constraints that clients can query with the `capabilities` RPC method.
+    pub fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            algorithms: self.algorithms.clone(),
+            max_algorithms: self.max_algorithms,
+            max_occurrences: self.max_occurrences,
+            max_request_size: self.max_request_size,
+            algorithm_costs: self.algorithm_costs.clone(),
+            max_request_cost: self.max_request_cost,
+            require_confirmation: self.require_confirmation,
         }
     }
 
-    fn check_constraints(&self, algorithms: &[OqsKexAlg]) -> bool {
-        if !self.meets_max_algorithms(algorithms.len()) {
-            return false;
+    /// Returns the first constraint `algorithms` violates, if any. Algorithms are checked in the
+    /// order they appear in the request.
+    fn check_constraints(&self, algorithms: &[OqsKexAlg]) -> StdResult<(), ConstraintViolation> {
+        if let Some(max) = self.max_algorithms {
+            if algorithms.len() > max {
+                return Err(ConstraintViolation::TooManyAlgorithms {
+                    count: algorithms.len(),
+                    max,
+                });
+            }
         }
 
         let mut stats = HashMap::new();
@@ -140,27 +621,29 @@ impl ServerConstraints {
             *stats.entry(*algo).or_insert(0) += 1;
         }
 
-        for (algo, algo_count) in stats.iter() {
-            if !self.is_allowed_algorithm(*algo) || !self.meets_max_occurrences(*algo_count) {
-                return false;
+        for algo in algorithms.iter() {
+            if !self.is_allowed_algorithm(*algo) {
+                return Err(ConstraintViolation::DisallowedAlgorithm(*algo));
+            }
+            if let Some(max) = self.max_occurrences {
+                if stats[algo] > max {
+                    return Err(ConstraintViolation::TooManyOccurrences {
+                        algorithm: *algo,
+                        count: stats[algo],
+                        max,
+                    });
+                }
             }
         }
 
-        true
-    }
-
-    fn meets_max_algorithms(&self, algorithms: usize) -> bool {
-        match self.max_algorithms {
-            Some(max_algorithms) => algorithms <= max_algorithms,
-            None => true,
+        if let Some(max) = self.max_request_cost {
+            let cost = self.algorithm_costs.request_cost(algorithms);
+            if cost > max {
+                return Err(ConstraintViolation::TooExpensive { cost, max });
+            }
         }
-    }
 
-    fn meets_max_occurrences(&self, occurrences: usize) -> bool {
-        match self.max_occurrences {
-            Some(max_occurrences) => occurrences <= max_occurrences,
-            None => true,
-        }
+        Ok(())
     }
 
     fn is_allowed_algorithm(&self, algorithm: OqsKexAlg) -> bool {
@@ -173,126 +656,496 @@ impl ServerConstraints {
 
 struct ServerConstraintsMiddleware {
     max_request_size: Option<usize>,
+    /// Whether the `TRUSTED_HEADERS` of the requests are set by the server, and not by the client.
+    trusted_headers: bool,
 }
 
 impl ServerConstraintsMiddleware {
-    pub fn new(max_request_size: Option<usize>) -> Self {
-        ServerConstraintsMiddleware { max_request_size }
+    pub fn new(max_request_size: Option<usize>, trusted_headers: bool) -> Self {
+        ServerConstraintsMiddleware {
+            max_request_size,
+            trusted_headers,
+        }
+    }
+
+    /// Returns the error response to send instead of handling `request`, if its `Content-Length`
+    /// is larger than `max_request_size` or it tries to forge one of the `TRUSTED_HEADERS`.
+    /// Chunked bodies are counted while read, by the listener's `BodyLimitService`.
+    fn reject(&self, request: &Request) -> Option<Response> {
+        if !self.trusted_headers && TRUSTED_HEADERS
+            .iter()
+            .any(|name| request.headers().get_raw(name).is_some())
+        {
+            return Some(Response::new().with_status(StatusCode::BadRequest));
+        }
+        let max_request_size = self.max_request_size? as u64;
+        match request.headers().get::<ContentLength>() {
+            // hyper reads exactly `Content-Length` bytes as the body. Anything a client sends
+            // past that is parsed as the next request on the connection, and checked again.
+            Some(&ContentLength(length)) if length <= max_request_size => None,
+            Some(_) => Some(Response::new().with_status(StatusCode::PayloadTooLarge)),
+            None => None,
+        }
     }
 }
 
 impl RequestMiddleware for ServerConstraintsMiddleware {
     fn on_request(&self, request: &Request) -> RequestMiddlewareAction {
-        let proceed = RequestMiddlewareAction::Proceed {
-            should_continue_on_invalid_cors: false,
-        };
-        match self.max_request_size {
-            Some(max_request_size) => {
-                if let Some(&length) = request.headers().get::<ContentLength>() {
-                    if *length <= max_request_size as u64 {
-                        proceed
-                    } else {
-                        RequestMiddlewareAction::Respond {
-                            should_validate_hosts: false,
-                            handler: Box::new(futures::future::err(HyperError::TooLarge)),
-                        }
-                    }
-                } else {
-                    // Invalid header
-                    RequestMiddlewareAction::Respond {
-                        should_validate_hosts: false,
-                        handler: Box::new(futures::future::err(HyperError::Header)),
-                    }
-                }
-            }
-            None => proceed,
+        match self.reject(request) {
+            Some(response) => RequestMiddlewareAction::Respond {
+                should_validate_hosts: false,
+                handler: Box::new(futures::future::ok(response)),
+            },
+            None => RequestMiddlewareAction::Proceed {
+                should_continue_on_invalid_cors: false,
+            },
         }
     }
 }
 
 
-struct OqsKexRpcServer<M, E, F>
+/// The metadata of a request. Holds the metadata from the user supplied `MetaExtractor`, together
+/// with what the rate limit tells the client apart by.
+#[derive(Clone, Default)]
+pub(crate) struct RequestMeta<M> {
+    pub meta: M,
+    pub client: ClientId,
+}
+
+impl<M: Metadata> Metadata for RequestMeta<M> {}
+
+struct RequestMetaExtractor<ME>(ME);
+
+impl<M, ME> MetaExtractor<RequestMeta<M>> for RequestMetaExtractor<ME>
 where
     M: Metadata,
+    ME: MetaExtractor<M>,
+{
+    fn read_metadata(&self, request: &Request) -> RequestMeta<M> {
+        RequestMeta {
+            meta: self.0.read_metadata(request),
+            client: client_id(request),
+        }
+    }
+}
+
+/// Returns the user ID of the client process on a Unix domain socket, otherwise the client's IP
+/// address.
+// `remote_addr` is the only way to get the peer address of a request in this hyper version.
+#[allow(deprecated)]
+fn client_id(request: &Request) -> ClientId {
+    #[cfg(unix)]
+    {
+        if let Some(credentials) = ::unix::peer_credentials(request) {
+            return ClientId::Uid(credentials.uid);
+        }
+    }
+    request
+        .remote_addr()
+        .map(|addr| ClientId::from_ip(addr.ip()))
+        .unwrap_or_default()
+}
+
+
+/// The user supplied `on_kex` callback, either returning a result or a future.
+pub(crate) trait KexCallback<M>: Send + Sync + 'static {
+    fn call(&self, meta: M, exchanged_keys: ExchangedKeys) -> ServerFuture<()>;
+
+    /// Whether `call` does all the work before returning, so it has to be called on the worker
+    /// pool instead of on the thread serving the connections.
+    fn is_blocking(&self) -> bool;
+}
+
+pub(crate) struct SyncCallback<F>(pub F);
+
+impl<M, E, F> KexCallback<M> for SyncCallback<F>
+where
     E: ::std::error::Error + Send + 'static,
-    F: Fn(M, Vec<SharedKey>) -> StdResult<(), E> + Send + Sync + 'static,
+    F: Fn(M, ExchangedKeys) -> StdResult<(), E> + Send + Sync + 'static,
 {
-    pub on_kex: F,
-    _meta: PhantomData<M>,
-    constraints: ServerConstraints,
+    fn call(&self, meta: M, exchanged_keys: ExchangedKeys) -> ServerFuture<()> {
+        let result = (self.0)(meta, exchanged_keys).chain_err(|| ErrorKind::CallbackError);
+        Box::new(futures::future::result(result))
+    }
+
+    fn is_blocking(&self) -> bool {
+        true
+    }
+}
+
+pub(crate) struct AsyncCallback<F>(pub F);
+
+impl<M, R, F> KexCallback<M> for AsyncCallback<F>
+where
+    R: IntoFuture<Item = ()>,
+    R::Future: Send + 'static,
+    R::Error: ::std::error::Error + Send + 'static,
+    F: Fn(M, ExchangedKeys) -> R + Send + Sync + 'static,
+{
+    fn call(&self, meta: M, exchanged_keys: ExchangedKeys) -> ServerFuture<()> {
+        let future = (self.0)(meta, exchanged_keys).into_future();
+        Box::new(future.map_err(|e| Error::with_chain(e, ErrorKind::CallbackError)))
+    }
+
+    fn is_blocking(&self) -> bool {
+        false
+    }
+}
+
+
+pub(crate) struct OqsKexRpcServer<M: Metadata, C: KexCallback<M>> {
+    on_kex: Arc<C>,
+    constraints: Arc<ServerConstraints>,
+    pending: Arc<Mutex<PendingExchanges<M>>>,
+    pool: OqsKexPool,
+    rate_limiter: Option<RateLimiter>,
+}
+
+/// Bob's side of a key exchange, computed on the worker pool.
+struct BobExchange {
+    bob_msgs: Vec<BobMsg>,
+    keys: Vec<SharedKey>,
+    transcript_hash: TranscriptHash,
+    signature: Option<Signature>,
+}
+
+impl BobExchange {
+    fn compute(
+        constraints: &ServerConstraints,
+        rand: &OqsRand,
+        alice_msgs: &[AliceMsg],
+    ) -> Result<Self> {
+        let (bob_msgs, keys) = Self::bob(rand, alice_msgs)?;
+        let transcript_hash = Transcript::from_msgs(alice_msgs, &bob_msgs).hash();
+        let signature = Self::sign(constraints, rand, &transcript_hash)?;
+        Ok(BobExchange {
+            bob_msgs,
+            keys,
+            transcript_hash,
+            signature,
+        })
+    }
+
+    /// Splits the exchange into the keys for `on_kex` and the response to the client.
+    fn into_parts(self) -> (ExchangedKeys, KexResponse) {
+        let exchanged_keys = ExchangedKeys {
+            keys: self.keys,
+            transcript_hash: self.transcript_hash,
+        };
+        let response = KexResponse {
+            bob_msgs: self.bob_msgs,
+            signature: self.signature,
+        };
+        (exchanged_keys, response)
+    }
+
+    /// Signs the transcript hash with the server's signing key, if it has one.
+    fn sign(
+        constraints: &ServerConstraints,
+        rand: &OqsRand,
+        transcript_hash: &TranscriptHash,
+    ) -> Result<Option<Signature>> {
+        match constraints.signing_key {
+            Some(ref signing_key) => {
+                let sig = OqsSig::new(rand, signing_key.algorithm())
+                    .chain_err(|| ErrorKind::OqsError)?;
+                let message = transcript::signed_message(transcript_hash);
+                let signature = sig.sign(signing_key, &message)
+                    .chain_err(|| ErrorKind::OqsError)?;
+                Ok(Some(signature))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Performs Bob's step for every message, one after another. The exchange already runs on a
+    /// pool worker, so starting more threads here would let the server use more threads than
+    /// `worker_threads`.
+    fn bob(rand: &OqsRand, alice_msgs: &[AliceMsg]) -> Result<(Vec<BobMsg>, Vec<SharedKey>)> {
+        let results = alice_msgs
+            .iter()
+            .map(|alice_msg| OqsKex::new(rand, alice_msg.algorithm())?.bob(alice_msg))
+            .collect::<oqs::kex::Result<Vec<_>>>()
+            .chain_err(|| ErrorKind::OqsError)?;
+        Ok(results.into_iter().unzip())
+    }
 }
 
-impl<M, E, F> OqsKexRpcServer<M, E, F>
+impl<M, C> OqsKexRpcServer<M, C>
 where
     M: Metadata + Sync,
-    E: ::std::error::Error + Send + 'static,
-    F: Fn(M, Vec<SharedKey>) -> StdResult<(), E>,
-    F: Send + Sync + 'static,
+    C: KexCallback<M>,
 {
-    pub fn new(on_kex: F, constraints: ServerConstraints) -> Self {
-        OqsKexRpcServer {
-            on_kex,
-            _meta: PhantomData,
-            constraints,
+    pub fn new(on_kex: C, constraints: ServerConstraints) -> Result<Self> {
+        let size = constraints.worker_threads.unwrap_or_else(num_cpus::get);
+        let mut pool = OqsKexPool::new(size).chain_err(|| ErrorKind::OqsError)?;
+        pool.set_max_queued(constraints.max_queued_exchanges);
+        let rate_limiter = constraints.rate_limit.clone().map(RateLimiter::new);
+        Ok(OqsKexRpcServer {
+            on_kex: Arc::new(on_kex),
+            constraints: Arc::new(constraints),
+            pending: Arc::new(Mutex::new(PendingExchanges::new())),
+            pool,
+            rate_limiter,
+        })
+    }
+
+    /// Checks that a request from `client` meets the constraints and the rate limit, and takes
+    /// its cost from the client's rate limit if it does.
+    fn admit(&self, client: ClientId, alice_msgs: &[AliceMsg]) -> Result<()> {
+        let algorithms: Vec<OqsKexAlg> = alice_msgs.iter().map(|msg| msg.algorithm()).collect();
+        self.constraints
+            .check_constraints(&algorithms)
+            .map_err(ErrorKind::ConstraintError)?;
+        if let Some(ref rate_limiter) = self.rate_limiter {
+            let cost = self.constraints.algorithm_costs.request_cost(&algorithms);
+            ensure!(
+                rate_limiter.try_acquire(client, cost),
+                ErrorKind::RateLimitError
+            );
         }
+        Ok(())
     }
 
-    fn perform_exchange(&self, meta: M, alice_msgs: &[AliceMsg]) -> Result<Vec<BobMsg>> {
-        ensure!(
-            self.constraints.check_constraints(&alice_msgs
-                .iter()
-                .map(|msg| msg.algorithm())
-                .collect::<Vec<OqsKexAlg>>()),
-            ErrorKind::ConstraintError
-        );
-        let rand = OqsRand::new(OqsRandAlg::default()).chain_err(|| ErrorKind::OqsError)?;
-        let kexs = Self::init_kex(&rand, &alice_msgs)?;
-        let (bob_msgs, keys) = Self::bob(&kexs, alice_msgs)?;
-        (self.on_kex)(meta, keys).chain_err(|| ErrorKind::CallbackError)?;
-        Ok(bob_msgs)
+    /// Computes Bob's side of the exchange on the worker pool, if the request is admitted, and
+    /// then `finish` in the same job. Fails with a `BusyError` if too many exchanges are already
+    /// waiting for a worker thread.
+    fn spawn_exchange<T, F>(
+        &self,
+        client: ClientId,
+        alice_msgs: Vec<AliceMsg>,
+        finish: F,
+    ) -> ServerFuture<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(BobExchange) -> Result<T> + Send + 'static,
+    {
+        if let Err(e) = self.admit(client, &alice_msgs) {
+            return Box::new(futures::future::err(e));
+        }
+        let constraints = self.constraints.clone();
+        self.spawn(move |rand| {
+            BobExchange::compute(&constraints, rand, &alice_msgs).and_then(finish)
+        })
     }
 
-    fn init_kex<'r>(rand: &'r OqsRand, msgs: &[AliceMsg]) -> Result<Vec<OqsKex<'r>>> {
-        msgs.iter()
-            .map(|msg| OqsKex::new(&rand, msg.algorithm()))
-            .collect::<oqs::kex::Result<_>>()
-            .chain_err(|| ErrorKind::OqsError)
+    /// Runs `f` on the worker pool. Fails with a `BusyError` if the queue is full.
+    fn spawn<T, F>(&self, f: F) -> ServerFuture<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&OqsRand) -> Result<T> + Send + 'static,
+    {
+        match self.pool.spawn_fn(f) {
+            Some(future) => Box::new(future),
+            None => Box::new(futures::future::err(ErrorKind::BusyError.into())),
+        }
     }
 
-    fn bob<'r>(
-        kexs: &[OqsKex<'r>],
-        alice_msgs: &[AliceMsg],
-    ) -> Result<(Vec<BobMsg>, Vec<SharedKey>)> {
-        let mut bob_msgs = Vec::with_capacity(alice_msgs.len());
-        let mut keys = Vec::with_capacity(alice_msgs.len());
+    pub fn perform_exchange(
+        &self,
+        meta: RequestMeta<M>,
+        alice_msgs: Vec<AliceMsg>,
+    ) -> ServerFuture<KexResponse> {
+        if self.constraints.require_confirmation {
+            let violation = ConstraintViolation::ConfirmationRequired;
+            return Box::new(futures::future::err(ErrorKind::ConstraintError(violation).into()));
+        }
+        let on_kex = self.on_kex.clone();
+        let RequestMeta { meta, client } = meta;
+        if on_kex.is_blocking() {
+            return self.spawn_exchange(client, alice_msgs, move |exchange| {
+                let (exchanged_keys, response) = exchange.into_parts();
+                on_kex.call(meta, exchanged_keys).wait()?;
+                Ok(response)
+            });
+        }
+        Box::new(self.spawn_exchange(client, alice_msgs, Ok).and_then(move |exchange| {
+            let (exchanged_keys, response) = exchange.into_parts();
+            on_kex.call(meta, exchanged_keys).map(move |()| response)
+        }))
+    }
+
+    pub fn begin_confirmed_exchange(
+        &self,
+        meta: RequestMeta<M>,
+        alice_msgs: Vec<AliceMsg>,
+    ) -> ServerFuture<UnconfirmedKex> {
+        let RequestMeta { meta, client } = meta;
+        if let Err(e) = self.pending.lock().unwrap().check_room(client) {
+            return Box::new(futures::future::err(e));
+        }
+        let pending = self.pending.clone();
+        let pool = self.pool.clone();
+        Box::new(self.spawn_exchange(client, alice_msgs, Ok).and_then(move |exchange| {
+            Self::add_pending(&pending, pool.rand(), meta, client, exchange)
+        }))
+    }
+
+    /// Stores the keys of `exchange` until the client confirms it. Fails if the server, or
+    /// `client`, has too many exchanges waiting for confirmation already.
+    fn add_pending(
+        pending: &Mutex<PendingExchanges<M>>,
+        rand: &OqsRand,
+        meta: M,
+        client: ClientId,
+        exchange: BobExchange,
+    ) -> Result<UnconfirmedKex> {
+        let server_mac = confirm::mac(Role::Server, &exchange.keys, &exchange.transcript_hash);
+        let kex = PendingKex {
+            meta,
+            client,
+            keys: exchange.keys,
+            transcript_hash: exchange.transcript_hash,
+            started: Instant::now(),
+        };
+        let session_id = pending.lock().unwrap().insert(rand, kex)?;
+
+        Ok(UnconfirmedKex {
+            session_id,
+            bob_msgs: exchange.bob_msgs,
+            signature: exchange.signature,
+            server_mac,
+        })
+    }
 
-        for (kex, alice_msg) in kexs.iter().zip(alice_msgs) {
-            let (bob_msg, key) = kex.bob(alice_msg).chain_err(|| ErrorKind::OqsError)?;
-            bob_msgs.push(bob_msg);
-            keys.push(key);
+    pub fn finish_confirmed_exchange(
+        &self,
+        client: ClientId,
+        session_id: u64,
+        client_mac: &[u8],
+    ) -> ServerFuture<()> {
+        if let Some(ref rate_limiter) = self.rate_limiter {
+            if !rate_limiter.try_acquire(client, CONFIRMATION_COST) {
+                return Box::new(futures::future::err(ErrorKind::RateLimitError.into()));
+            }
+        }
+        let on_kex = self.on_kex.clone();
+        if on_kex.is_blocking() {
+            // The confirmation is checked in the job too, so the exchange stays pending if the
+            // queue is full and the client can try again.
+            let pending = self.pending.clone();
+            let client_mac = client_mac.to_vec();
+            return self.spawn(move |_| {
+                let (meta, exchanged_keys) =
+                    Self::check_confirmation(&pending, session_id, &client_mac)?;
+                on_kex.call(meta, exchanged_keys).wait()
+            });
+        }
+        match Self::check_confirmation(&self.pending, session_id, client_mac) {
+            Ok((meta, exchanged_keys)) => Box::new(on_kex.call(meta, exchanged_keys)),
+            Err(e) => Box::new(futures::future::err(e)),
         }
-        Ok((bob_msgs, keys))
+    }
+
+    fn check_confirmation(
+        pending: &Mutex<PendingExchanges<M>>,
+        session_id: u64,
+        client_mac: &[u8],
+    ) -> Result<(M, ExchangedKeys)> {
+        // The exchange is removed whether or not the confirmation is valid, so every exchange can
+        // only be confirmed once.
+        let kex = pending
+            .lock()
+            .unwrap()
+            .take(session_id)
+            .ok_or(ErrorKind::ConfirmationError)?;
+        ensure!(
+            kex.started.elapsed() < CONFIRMATION_TIMEOUT,
+            ErrorKind::ConfirmationError
+        );
+        ensure!(
+            confirm::verify(Role::Client, &kex.keys, &kex.transcript_hash, client_mac),
+            ErrorKind::ConfirmationError
+        );
+        let exchanged_keys = ExchangedKeys {
+            keys: kex.keys,
+            transcript_hash: kex.transcript_hash,
+        };
+        Ok((kex.meta, exchanged_keys))
     }
 }
 
-impl<M, E, F> OqsKexRpcServerApi for OqsKexRpcServer<M, E, F>
+impl<M, C> OqsKexRpcServerApi for OqsKexRpcServer<M, C>
 where
     M: Metadata + Sync,
-    E: ::std::error::Error + Send + 'static,
-    F: Fn(M, Vec<SharedKey>) -> StdResult<(), E>,
-    F: Send + Sync + 'static,
+    C: KexCallback<M>,
 {
-    type Metadata = M;
+    type Metadata = RequestMeta<M>;
 
     fn kex(
         &self,
         meta: Self::Metadata,
         alice_msgs: Vec<AliceMsg>,
-    ) -> BoxFuture<Vec<BobMsg>, JsonError> {
-        let result = self.perform_exchange(meta, &alice_msgs).map_err(|e| {
-            error!("Error during key exchange: {}", e.display_chain());
-            JsonError::internal_error()
-        });
-        Box::new(futures::future::result(result))
+    ) -> BoxFuture<KexResponse, JsonError> {
+        rpc_future(self.perform_exchange(meta, alice_msgs), "key exchange")
+    }
+
+    fn kex_with_confirmation(
+        &self,
+        meta: Self::Metadata,
+        alice_msgs: Vec<AliceMsg>,
+    ) -> BoxFuture<UnconfirmedKex, JsonError> {
+        rpc_future(
+            self.begin_confirmed_exchange(meta, alice_msgs),
+            "key exchange",
+        )
+    }
+
+    fn confirm(
+        &self,
+        meta: Self::Metadata,
+        session_id: u64,
+        client_mac: Vec<u8>,
+    ) -> BoxFuture<(), JsonError> {
+        rpc_future(
+            self.finish_confirmed_exchange(meta.client, session_id, &client_mac),
+            "key confirmation",
+        )
+    }
+
+    fn capabilities(&self) -> BoxFuture<Capabilities, JsonError> {
+        Box::new(futures::future::ok(self.constraints.capabilities()))
+    }
+}
+
+fn rpc_future<T: Send + 'static>(
+    future: ServerFuture<T>,
+    operation: &'static str,
+) -> BoxFuture<T, JsonError> {
+    Box::new(future.map_err(move |e| {
+        error!("Error during {}: {}", operation, e.display_chain());
+        json_error(&e)
+    }))
+}
+
+/// Maps an error to the JSON-RPC error sent to the client. Only the error code and the constraint
+/// violation are sent, the rest of the error chain stays in the server log.
+fn json_error(error: &Error) -> JsonError {
+    let code = match error_code(error) {
+        Some(code) => code,
+        None => return JsonError::internal_error(),
+    };
+    let data = match *error.kind() {
+        ErrorKind::ConstraintError(ref violation) => serde_json::to_value(violation).ok(),
+        _ => None,
+    };
+    JsonError {
+        code: ErrorCode::ServerError(code),
+        message: error.to_string(),
+        data,
+    }
+}
+
+/// Returns the error code to send to the client for an error, or `None` if it should be reported
+/// as an internal error.
+pub(crate) fn error_code(error: &Error) -> Option<i64> {
+    match *error.kind() {
+        ErrorKind::ConstraintError(_) => Some(error_codes::CONSTRAINT_ERROR),
+        ErrorKind::OqsError => Some(error_codes::OQS_ERROR),
+        ErrorKind::CallbackError => Some(error_codes::CALLBACK_ERROR),
+        ErrorKind::ConfirmationError => Some(error_codes::CONFIRMATION_ERROR),
+        ErrorKind::BusyError => Some(error_codes::BUSY_ERROR),
+        ErrorKind::RateLimitError => Some(error_codes::RATE_LIMIT_ERROR),
+        _ => None,
     }
 }
@@ -0,0 +1,109 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The one byte tags identifying algorithms in the binary protocol and in transcript hashes.
+//! Unlike the names of the enum variants, these never change, so encoded messages and hashes stay
+//! the same between versions of the crate.
+
+use oqs::kex::OqsKexAlg;
+use oqs::sig::OqsSigAlg;
+
+/// The tags identifying the key exchange algorithms. Never reuse or change a tag, add new
+/// algorithms with new tags instead.
+pub fn kex_alg_tag(algorithm: OqsKexAlg) -> u8 {
+    use oqs::kex::OqsKexAlg::*;
+    match algorithm {
+        MlKem512 => 0x01,
+        MlKem768 => 0x02,
+        MlKem1024 => 0x03,
+        FrodoKem640Aes => 0x10,
+        FrodoKem640Shake => 0x11,
+        FrodoKem976Aes => 0x12,
+        FrodoKem976Shake => 0x13,
+        FrodoKem1344Aes => 0x14,
+        FrodoKem1344Shake => 0x15,
+        ClassicMcEliece348864 => 0x20,
+        ClassicMcEliece460896 => 0x21,
+        ClassicMcEliece6688128 => 0x22,
+        ClassicMcEliece6960119 => 0x23,
+        ClassicMcEliece8192128 => 0x24,
+        Hqc128 => 0x30,
+        Hqc192 => 0x31,
+        Hqc256 => 0x32,
+    }
+}
+
+pub fn kex_alg_from_tag(tag: u8) -> Option<OqsKexAlg> {
+    use oqs::kex::OqsKexAlg::*;
+    let algorithm = match tag {
+        0x01 => MlKem512,
+        0x02 => MlKem768,
+        0x03 => MlKem1024,
+        0x10 => FrodoKem640Aes,
+        0x11 => FrodoKem640Shake,
+        0x12 => FrodoKem976Aes,
+        0x13 => FrodoKem976Shake,
+        0x14 => FrodoKem1344Aes,
+        0x15 => FrodoKem1344Shake,
+        0x20 => ClassicMcEliece348864,
+        0x21 => ClassicMcEliece460896,
+        0x22 => ClassicMcEliece6688128,
+        0x23 => ClassicMcEliece6960119,
+        0x24 => ClassicMcEliece8192128,
+        0x30 => Hqc128,
+        0x31 => Hqc192,
+        0x32 => Hqc256,
+        _ => return None,
+    };
+    Some(algorithm)
+}
+
+/// The tags identifying the signature algorithms. Zero is reserved for no signature.
+pub fn sig_alg_tag(algorithm: OqsSigAlg) -> u8 {
+    use oqs::sig::OqsSigAlg::*;
+    match algorithm {
+        MlDsa44 => 0x01,
+        MlDsa65 => 0x02,
+        MlDsa87 => 0x03,
+        Falcon512 => 0x10,
+        Falcon1024 => 0x11,
+    }
+}
+
+pub fn sig_alg_from_tag(tag: u8) -> Option<OqsSigAlg> {
+    use oqs::sig::OqsSigAlg::*;
+    let algorithm = match tag {
+        0x01 => MlDsa44,
+        0x02 => MlDsa65,
+        0x03 => MlDsa87,
+        0x10 => Falcon512,
+        0x11 => Falcon1024,
+        _ => return None,
+    };
+    Some(algorithm)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oqs::kex::OqsKexAlg::*;
+
+    #[test]
+    fn kex_alg_tags_roundtrip() {
+        let algorithms = [
+            MlKem512, MlKem768, MlKem1024, FrodoKem640Aes, FrodoKem640Shake, FrodoKem976Aes,
+            FrodoKem976Shake, FrodoKem1344Aes, FrodoKem1344Shake, ClassicMcEliece348864,
+            ClassicMcEliece460896, ClassicMcEliece6688128, ClassicMcEliece6960119,
+            ClassicMcEliece8192128, Hqc128, Hqc192, Hqc256,
+        ];
+        for &algorithm in &algorithms {
+            assert_eq!(kex_alg_from_tag(kex_alg_tag(algorithm)), Some(algorithm));
+        }
+    }
+}
@@ -0,0 +1,97 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The plain HTTP listener of the server.
+
+use futures::{Future, Stream};
+use tokio_core::net::TcpListener;
+use tokio_core::reactor::Handle;
+
+use listener::{bind_connection, BodyLimitService, ListenerThread};
+
+use std::io;
+use std::net::SocketAddr;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use jsonrpc_core::{MetaIoHandler, Metadata, Middleware};
+use jsonrpc_http_server::{MetaExtractor, RequestMiddleware, Rpc, ServerHandler};
+use jsonrpc_http_server::hyper::server::Http;
+
+
+/// A JSON-RPC server accepting plain HTTP connections. Serves the requests on its own thread, and
+/// stops when dropped.
+pub(crate) struct TcpServer {
+    address: SocketAddr,
+    thread: ListenerThread,
+}
+
+impl TcpServer {
+    pub fn start<M, S, ME, RM>(
+        addr: &SocketAddr,
+        handler: MetaIoHandler<M, S>,
+        meta_extractor: ME,
+        middleware: RM,
+        max_request_size: Option<usize>,
+    ) -> io::Result<Self>
+    where
+        M: Metadata,
+        S: Middleware<M>,
+        ME: MetaExtractor<M>,
+        RM: RequestMiddleware,
+    {
+        let addr = *addr;
+        let rpc = Rpc {
+            handler: Arc::new(handler),
+            extractor: Arc::new(meta_extractor),
+        };
+        let middleware: Arc<dyn RequestMiddleware> = Arc::new(middleware);
+
+        let (thread, address) = ListenerThread::spawn("oqs-kex-rpc-http", move |handle| {
+            let listener = TcpListener::bind(&addr, handle)?;
+            let address = listener.local_addr()?;
+            Ok((address, serve(listener, handle, rpc, middleware, max_request_size)))
+        })?;
+        Ok(TcpServer { address, thread })
+    }
+
+    pub fn address(&self) -> &SocketAddr {
+        &self.address
+    }
+
+    /// Blocks until the server has stopped.
+    pub fn wait(self) {
+        self.thread.wait()
+    }
+}
+
+/// Returns a future serving the connections accepted on `listener`.
+fn serve<M, S>(
+    listener: TcpListener,
+    handle: &Handle,
+    rpc: Rpc<M, S>,
+    middleware: Arc<dyn RequestMiddleware>,
+    max_request_size: Option<usize>,
+) -> Box<dyn Future<Item = (), Error = ()>>
+where
+    M: Metadata,
+    S: Middleware<M>,
+{
+    let handle = handle.clone();
+    let http = Rc::new(Http::new());
+    let connections = listener
+        .incoming()
+        .for_each(move |(socket, peer_addr)| {
+            let service = ServerHandler::new(rpc.clone(), None, None, middleware.clone());
+            let service = BodyLimitService::new(service, max_request_size);
+            bind_connection(&http, &handle, socket, peer_addr, service);
+            Ok(())
+        })
+        .map_err(|error| error!("Unable to accept HTTP connections: {}", error));
+    Box::new(connections)
+}
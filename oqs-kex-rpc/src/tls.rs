@@ -0,0 +1,293 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! TLS for the server and the client, using [rustls]. Requires the `tls` feature.
+//!
+//! Set [`ServerConstraints::tls`] to make the server accept TLS connections only, and connect to
+//! it with [`OqsKexClient::with_tls`]. The client only trusts server certificates signed by one
+//! of the CA certificates in its [`ClientTlsConfig`], so it can be pinned to a private CA.
+//!
+//! A server configured with [`ServerTlsConfig::with_client_auth`] also requires clients to present
+//! a certificate. The `MetaExtractor` can get it from the request with [`peer_certificate`].
+//!
+//! [rustls]: https://crates.io/crates/rustls
+//! [`ServerConstraints::tls`]: ../server/struct.ServerConstraints.html#structfield.tls
+//! [`OqsKexClient::with_tls`]: ../client/struct.OqsKexClient.html#method.with_tls
+//! [`ClientTlsConfig`]: struct.ClientTlsConfig.html
+//! [`ServerTlsConfig::with_client_auth`]: struct.ServerTlsConfig.html#method.with_client_auth
+//! [`peer_certificate`]: fn.peer_certificate.html
+
+use base64;
+use futures::{Future, Stream};
+use rustls::{ClientConfig, ServerConfig, ServerSession, Session};
+use rustls::internal::pemfile;
+use tokio_core::net::TcpListener;
+use tokio_core::reactor::Handle;
+use tokio_rustls::ServerConfigExt;
+
+use listener::{bind_connection, with_timeout, BodyLimitService, ListenerThread,
+               TrustedHeadersService};
+use server::PEER_CERTIFICATE_HEADER;
+
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
+
+use jsonrpc_core::{MetaIoHandler, Metadata, Middleware};
+use jsonrpc_http_server::{MetaExtractor, RequestMiddleware, Rpc, ServerHandler};
+use jsonrpc_http_server::hyper::server::{Http, Request};
+
+pub use rustls::{Certificate, PrivateKey};
+
+
+error_chain! {
+    errors {
+        /// A certificate or key file could not be read.
+        ReadError(path: PathBuf) {
+            description("Unable to read file")
+            display("Unable to read {}", path.display())
+        }
+        /// A file did not contain the expected PEM encoded certificates or key.
+        ParseError(path: PathBuf) {
+            description("Invalid certificate or key file")
+            display("No valid certificate or key in {}", path.display())
+        }
+        /// A CA certificate could not be used as a trust anchor.
+        InvalidCaCertificate { description("Invalid CA certificate") }
+    }
+}
+
+
+/// Reads all certificates in a PEM file.
+pub fn load_certificates<P: AsRef<Path>>(path: P) -> Result<Vec<Certificate>> {
+    let path = path.as_ref();
+    match pemfile::certs(&mut open(path)?) {
+        Ok(ref certificates) if certificates.is_empty() => {
+            Err(ErrorKind::ParseError(path.to_owned()).into())
+        }
+        Ok(certificates) => Ok(certificates),
+        Err(()) => Err(ErrorKind::ParseError(path.to_owned()).into()),
+    }
+}
+
+/// Reads the first private key in a PEM file. Both PKCS#1 RSA keys and PKCS#8 keys are accepted.
+pub fn load_private_key<P: AsRef<Path>>(path: P) -> Result<PrivateKey> {
+    let path = path.as_ref();
+    let rsa_keys = pemfile::rsa_private_keys(&mut open(path)?);
+    let pkcs8_keys = pemfile::pkcs8_private_keys(&mut open(path)?);
+    rsa_keys
+        .into_iter()
+        .chain(pkcs8_keys)
+        .flatten()
+        .next()
+        .ok_or_else(|| ErrorKind::ParseError(path.to_owned()).into())
+}
+
+fn open(path: &Path) -> Result<BufReader<File>> {
+    File::open(path)
+        .map(BufReader::new)
+        .chain_err(|| ErrorKind::ReadError(path.to_owned()))
+}
+
+/// Returns the certificate the client presented when connecting to a server configured with
+/// [`ServerTlsConfig::with_client_auth`]. Meant to be called from the server's `MetaExtractor`.
+///
+/// The server replaces whatever the client sent in the header holding the certificate, and plain
+/// HTTP servers reject requests having it. So the certificate can be trusted to have been verified
+/// by the server.
+///
+/// [`ServerTlsConfig::with_client_auth`]: struct.ServerTlsConfig.html#method.with_client_auth
+pub fn peer_certificate(request: &Request) -> Option<Certificate> {
+    let value = request.headers().get_raw(PEER_CERTIFICATE_HEADER)?.one()?;
+    base64::decode(value).ok().map(Certificate)
+}
+
+
+/// TLS configuration of the server.
+#[derive(Clone)]
+pub struct ServerTlsConfig {
+    config: Arc<ServerConfig>,
+}
+
+impl ServerTlsConfig {
+    /// Creates a configuration presenting `certificates` to clients, the server's own certificate
+    /// first followed by any intermediate certificates. `private_key` is the server certificate's
+    /// key.
+    pub fn new(certificates: Vec<Certificate>, private_key: PrivateKey) -> Self {
+        let mut config = ServerConfig::new();
+        config.set_single_cert(certificates, private_key);
+        ServerTlsConfig {
+            config: Arc::new(config),
+        }
+    }
+
+    /// Creates a configuration like [`new`](#method.new), that also requires clients to present a
+    /// certificate signed by one of `client_ca_certificates`.
+    pub fn with_client_auth(
+        certificates: Vec<Certificate>,
+        private_key: PrivateKey,
+        client_ca_certificates: Vec<Certificate>,
+    ) -> Self {
+        let mut config = ServerConfig::new();
+        config.set_single_cert(certificates, private_key);
+        config.set_client_auth_roots(client_ca_certificates, true);
+        ServerTlsConfig {
+            config: Arc::new(config),
+        }
+    }
+}
+
+/// TLS configuration of the client.
+#[derive(Clone)]
+pub struct ClientTlsConfig {
+    pub(crate) config: Arc<ClientConfig>,
+}
+
+impl ClientTlsConfig {
+    /// Creates a configuration trusting only server certificates signed by one of
+    /// `ca_certificates`. The trust store of the operating system is not used.
+    pub fn new(ca_certificates: &[Certificate]) -> Result<Self> {
+        let config = client_config(ca_certificates)?;
+        Ok(ClientTlsConfig {
+            config: Arc::new(config),
+        })
+    }
+
+    /// Creates a configuration like [`new`](#method.new), that also presents `certificates` to
+    /// servers requiring client authentication. `private_key` is the client certificate's key.
+    pub fn with_client_certificate(
+        ca_certificates: &[Certificate],
+        certificates: Vec<Certificate>,
+        private_key: PrivateKey,
+    ) -> Result<Self> {
+        let mut config = client_config(ca_certificates)?;
+        config.set_single_client_cert(certificates, private_key);
+        Ok(ClientTlsConfig {
+            config: Arc::new(config),
+        })
+    }
+}
+
+fn client_config(ca_certificates: &[Certificate]) -> Result<ClientConfig> {
+    let mut config = ClientConfig::new();
+    for certificate in ca_certificates {
+        config
+            .root_store
+            .add(certificate)
+            .map_err(|_| ErrorKind::InvalidCaCertificate)?;
+    }
+    Ok(config)
+}
+
+
+/// How long a client gets to complete the TLS handshake before its connection is closed.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A JSON-RPC server accepting TLS connections only. Serves the requests on its own thread, and
+/// stops when dropped.
+pub(crate) struct TlsServer {
+    address: SocketAddr,
+    thread: ListenerThread,
+}
+
+impl TlsServer {
+    pub fn start<M, S, ME, RM>(
+        addr: &SocketAddr,
+        config: &ServerTlsConfig,
+        handler: MetaIoHandler<M, S>,
+        meta_extractor: ME,
+        middleware: RM,
+        max_request_size: Option<usize>,
+    ) -> io::Result<Self>
+    where
+        M: Metadata,
+        S: Middleware<M>,
+        ME: MetaExtractor<M>,
+        RM: RequestMiddleware,
+    {
+        let addr = *addr;
+        let config = config.config.clone();
+        let rpc = Rpc {
+            handler: Arc::new(handler),
+            extractor: Arc::new(meta_extractor),
+        };
+        let middleware: Arc<dyn RequestMiddleware> = Arc::new(middleware);
+
+        let (thread, address) = ListenerThread::spawn("oqs-kex-rpc-tls", move |handle| {
+            let listener = TcpListener::bind(&addr, handle)?;
+            let address = listener.local_addr()?;
+            let serve = serve(listener, handle, config, rpc, middleware, max_request_size);
+            Ok((address, serve))
+        })?;
+        Ok(TlsServer { address, thread })
+    }
+
+    pub fn address(&self) -> &SocketAddr {
+        &self.address
+    }
+
+    /// Blocks until the server has stopped.
+    pub fn wait(self) {
+        self.thread.wait()
+    }
+}
+
+/// Returns a future serving TLS connections accepted on `listener`.
+fn serve<M, S>(
+    listener: TcpListener,
+    handle: &Handle,
+    config: Arc<ServerConfig>,
+    rpc: Rpc<M, S>,
+    middleware: Arc<dyn RequestMiddleware>,
+    max_request_size: Option<usize>,
+) -> Box<dyn Future<Item = (), Error = ()>>
+where
+    M: Metadata,
+    S: Middleware<M>,
+{
+    let handle = handle.clone();
+    let http = Rc::new(Http::new());
+    let connections = listener
+        .incoming()
+        .for_each(move |(socket, peer_addr)| {
+            let connection_handle = handle.clone();
+            let http = http.clone();
+            let rpc = rpc.clone();
+            let middleware = middleware.clone();
+            let handshake = config.accept_async(socket);
+            let connection = with_timeout(handshake, HANDSHAKE_TIMEOUT, &handle)
+                .map(move |stream| {
+                    let headers = encoded_peer_certificate(stream.get_ref().1)
+                        .map(|certificate| (PEER_CERTIFICATE_HEADER, certificate))
+                        .into_iter()
+                        .collect();
+                    let service = TrustedHeadersService {
+                        service: ServerHandler::new(rpc, None, None, middleware),
+                        headers,
+                    };
+                    let service = BodyLimitService::new(service, max_request_size);
+                    bind_connection(&http, &connection_handle, stream, peer_addr, service);
+                })
+                .map_err(move |error| debug!("TLS handshake with {} failed: {}", peer_addr, error));
+            handle.spawn(connection);
+            Ok(())
+        })
+        .map_err(|error| error!("Unable to accept TLS connections: {}", error));
+    Box::new(connections)
+}
+
+fn encoded_peer_certificate(session: &ServerSession) -> Option<String> {
+    let certificates = session.get_peer_certificates()?;
+    certificates
+        .first()
+        .map(|certificate| base64::encode(&certificate.0))
+}
@@ -0,0 +1,188 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The transcript of a key exchange is a SHA-256 hash over all messages sent in it, together with
+//! their algorithms. The client and the server both compute it, and return it together with the
+//! shared keys. Deriving the final secret from the keys and the transcript hash binds it to the
+//! exact messages exchanged, so a substituted message makes the two sides derive different
+//! secrets.
+//!
+//! The key confirmation MACs and the server signature are also computed over the transcript hash.
+
+use oqs::kex::{AliceMsg, BobMsg, OqsKexAlg, SharedKey};
+use sha2::{Digest, Sha256};
+
+use tags::kex_alg_tag;
+
+use std::borrow::Borrow;
+
+/// Prefix of the message the server signs, so a signature over a transcript hash can't be
+/// mistaken for a signature made by the same key in another protocol.
+const SIGNATURE_LABEL: &[u8] = b"oqs-kex-rpc server signature";
+
+/// Length of a [`TranscriptHash`](struct.TranscriptHash.html) in bytes.
+pub const TRANSCRIPT_HASH_LEN: usize = 32;
+
+
+/// Accumulates the messages of a key exchange into a transcript hash.
+///
+/// Every message is hashed together with which side sent it and the fixed tag of its algorithm,
+/// the same tag that identifies it in the binary protocol, in the order the messages are
+/// appended.
+#[derive(Clone, Default)]
+pub struct Transcript {
+    hasher: Sha256,
+}
+
+impl Transcript {
+    /// Creates an empty transcript.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a transcript with all of Alice's messages followed by all of Bob's messages, the
+    /// order in which they are sent by the client and server in this crate.
+    pub fn from_msgs<A: Borrow<AliceMsg>>(alice_msgs: &[A], bob_msgs: &[BobMsg]) -> Self {
+        let mut transcript = Self::new();
+        for alice_msg in alice_msgs {
+            transcript.append_alice_msg(alice_msg.borrow());
+        }
+        for bob_msg in bob_msgs {
+            transcript.append_bob_msg(bob_msg);
+        }
+        transcript
+    }
+
+    /// Appends one of Alice's messages to the transcript.
+    pub fn append_alice_msg(&mut self, alice_msg: &AliceMsg) {
+        self.append(b"alice", alice_msg.algorithm(), alice_msg.data());
+    }
+
+    /// Appends one of Bob's messages to the transcript.
+    pub fn append_bob_msg(&mut self, bob_msg: &BobMsg) {
+        self.append(b"bob", bob_msg.algorithm(), bob_msg.data());
+    }
+
+    /// Returns the hash over all messages appended so far.
+    pub fn hash(&self) -> TranscriptHash {
+        let mut hash = [0; TRANSCRIPT_HASH_LEN];
+        hash.copy_from_slice(&self.hasher.clone().result());
+        TranscriptHash(hash)
+    }
+
+    /// All fields are length prefixed to keep the encoding unambiguous.
+    fn append(&mut self, sender: &[u8], algorithm: OqsKexAlg, data: &[u8]) {
+        let algorithm = [kex_alg_tag(algorithm)];
+        for field in &[sender, &algorithm[..], data] {
+            self.hasher.input((field.len() as u64).to_be_bytes());
+            self.hasher.input(field);
+        }
+    }
+}
+
+
+/// Hash over all messages of a key exchange, computed by a [`Transcript`].
+///
+/// [`Transcript`]: struct.Transcript.html
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct TranscriptHash([u8; TRANSCRIPT_HASH_LEN]);
+
+impl TranscriptHash {
+    /// Returns the hash as a slice.
+    pub fn data(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl AsRef<[u8]> for TranscriptHash {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+
+/// The result of a completed key exchange. Returned by the client and handed to the server's
+/// `on_kex` callback.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ExchangedKeys {
+    /// The shared keys, one for each algorithm in the request and in the same order.
+    pub keys: Vec<SharedKey>,
+    /// Hash over all messages the keys were computed from.
+    pub transcript_hash: TranscriptHash,
+}
+
+
+/// Returns the message the server signs to prove its identity for the exchange with the given
+/// transcript hash.
+pub(crate) fn signed_message(transcript_hash: &TranscriptHash) -> Vec<u8> {
+    let mut message = SIGNATURE_LABEL.to_vec();
+    message.extend_from_slice(transcript_hash.data());
+    message
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oqs::kex::OqsKex;
+    use oqs::rand::{OqsRand, OqsRandAlg};
+
+    fn exchange(algorithm: OqsKexAlg) -> (AliceMsg, BobMsg) {
+        let rand = OqsRand::new(OqsRandAlg::default()).unwrap();
+        let kex_alice = OqsKex::new(&rand, algorithm).unwrap();
+        let kex_alice_0 = kex_alice.alice_0().unwrap();
+        let kex_bob = OqsKex::new(&rand, algorithm).unwrap();
+        let (bob_msg, _) = kex_bob.bob(kex_alice_0.get_alice_msg()).unwrap();
+        (kex_alice_0.get_alice_msg().clone(), bob_msg)
+    }
+
+    #[test]
+    fn from_msgs_appends_in_order() {
+        let (alice_msg, bob_msg) = exchange(OqsKexAlg::MlKem512);
+        let mut transcript = Transcript::new();
+        transcript.append_alice_msg(&alice_msg);
+        transcript.append_bob_msg(&bob_msg);
+
+        let from_msgs = Transcript::from_msgs(&[&alice_msg], &[bob_msg]);
+        assert_eq!(transcript.hash(), from_msgs.hash());
+    }
+
+    #[test]
+    fn substituted_msg_changes_hash() {
+        let (alice_msg, bob_msg) = exchange(OqsKexAlg::MlKem512);
+        let (other_alice_msg, _) = exchange(OqsKexAlg::MlKem512);
+        let bob_msgs = vec![bob_msg];
+
+        let transcript = Transcript::from_msgs(&[alice_msg], &bob_msgs);
+        let other_transcript = Transcript::from_msgs(&[other_alice_msg], &bob_msgs);
+        assert_ne!(transcript.hash(), other_transcript.hash());
+    }
+
+    #[test]
+    fn algorithm_hashed_as_tag() {
+        let alice_msg = AliceMsg::from_bytes(OqsKexAlg::MlKem768, &[1, 2, 3]);
+        let transcript = Transcript::from_msgs(&[alice_msg], &[]);
+
+        let mut hasher = Sha256::default();
+        for field in &[&b"alice"[..], &[0x02], &[1, 2, 3]] {
+            hasher.input((field.len() as u64).to_be_bytes());
+            hasher.input(field);
+        }
+        assert_eq!(transcript.hash().data(), &hasher.result()[..]);
+    }
+
+    #[test]
+    fn hash_does_not_consume() {
+        let (alice_msg, bob_msg) = exchange(OqsKexAlg::MlKem512);
+        let mut transcript = Transcript::new();
+        transcript.append_alice_msg(&alice_msg);
+        let partial_hash = transcript.hash();
+        transcript.append_bob_msg(&bob_msg);
+        assert_ne!(partial_hash, transcript.hash());
+    }
+}
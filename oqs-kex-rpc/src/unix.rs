@@ -0,0 +1,247 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Serving the key exchange over a Unix domain socket instead of TCP. Only available on Unix.
+//!
+//! Start the server with a [`ListenAddress::Unix`] address, and connect to it with
+//! [`OqsKexClient::with_unix_socket`]. The `MetaExtractor` can get the credentials of the process
+//! that connected from the request with [`peer_credentials`].
+//!
+//! [`ListenAddress::Unix`]: ../server/enum.ListenAddress.html#variant.Unix
+//! [`OqsKexClient::with_unix_socket`]: ../client/struct.OqsKexClient.html#method.with_unix_socket
+//! [`peer_credentials`]: fn.peer_credentials.html
+
+use futures::{Future, Stream};
+use tokio_core::reactor::Handle;
+use tokio_uds::{UnixListener, UnixStream};
+
+use listener::{BodyLimitService, ListenerThread, TrustedHeadersService};
+use server::PEER_CREDENTIALS_HEADER;
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::str;
+use std::sync::Arc;
+
+use jsonrpc_core::{MetaIoHandler, Metadata, Middleware};
+use jsonrpc_http_server::{MetaExtractor, RequestMiddleware, Rpc, ServerHandler};
+use jsonrpc_http_server::hyper::Chunk;
+use jsonrpc_http_server::hyper::server::{Http, Request};
+
+
+/// The credentials of the process on the other end of a Unix domain socket, as they were when it
+/// connected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PeerCredentials {
+    /// User ID of the process.
+    pub uid: u32,
+    /// Group ID of the process.
+    pub gid: u32,
+    /// Process ID. Only available on Linux and Android.
+    pub pid: Option<u32>,
+}
+
+impl PeerCredentials {
+    fn encode(&self) -> String {
+        match self.pid {
+            Some(pid) => format!("{} {} {}", self.uid, self.gid, pid),
+            None => format!("{} {}", self.uid, self.gid),
+        }
+    }
+
+    fn decode(value: &str) -> Option<Self> {
+        let mut fields = value.split(' ').map(str::parse::<u32>);
+        let uid = fields.next()?.ok()?;
+        let gid = fields.next()?.ok()?;
+        let pid = match fields.next() {
+            Some(pid) => Some(pid.ok()?),
+            None => None,
+        };
+        if fields.next().is_some() {
+            return None;
+        }
+        Some(PeerCredentials { uid, gid, pid })
+    }
+}
+
+/// Returns the credentials of the process that sent the request to a server listening on a Unix
+/// domain socket. Meant to be called from the server's `MetaExtractor`. Returns `None` for
+/// requests that did not come over a Unix domain socket.
+///
+/// The server replaces whatever the client sent in the header holding the credentials, and plain
+/// HTTP servers reject requests having it. So the credentials can be trusted to be the ones the
+/// kernel reported for the connection.
+pub fn peer_credentials(request: &Request) -> Option<PeerCredentials> {
+    let value = request.headers().get_raw(PEER_CREDENTIALS_HEADER)?.one()?;
+    PeerCredentials::decode(str::from_utf8(value).ok()?)
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn get_peer_credentials(stream: &UnixStream) -> io::Result<PeerCredentials> {
+    use libc;
+    use std::mem;
+    use std::os::unix::io::AsRawFd;
+
+    let mut ucred = libc::ucred {
+        pid: 0,
+        uid: 0,
+        gid: 0,
+    };
+    let mut len = mem::size_of::<libc::ucred>() as libc::socklen_t;
+    let result = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut ucred as *mut libc::ucred as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(PeerCredentials {
+        uid: ucred.uid,
+        gid: ucred.gid,
+        pid: Some(ucred.pid as u32),
+    })
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn get_peer_credentials(stream: &UnixStream) -> io::Result<PeerCredentials> {
+    let ucred = stream.peer_cred()?;
+    Ok(PeerCredentials {
+        uid: ucred.uid,
+        gid: ucred.gid,
+        pid: None,
+    })
+}
+
+
+/// A JSON-RPC server listening on a Unix domain socket. Serves the requests on its own thread,
+/// and stops and removes the socket file when dropped.
+pub(crate) struct UnixServer {
+    path: PathBuf,
+    thread: Option<ListenerThread>,
+}
+
+impl UnixServer {
+    pub fn start<M, S, ME, RM>(
+        path: &Path,
+        handler: MetaIoHandler<M, S>,
+        meta_extractor: ME,
+        middleware: RM,
+        max_request_size: Option<usize>,
+    ) -> io::Result<Self>
+    where
+        M: Metadata,
+        S: Middleware<M>,
+        ME: MetaExtractor<M>,
+        RM: RequestMiddleware,
+    {
+        let rpc = Rpc {
+            handler: Arc::new(handler),
+            extractor: Arc::new(meta_extractor),
+        };
+        let middleware: Arc<dyn RequestMiddleware> = Arc::new(middleware);
+
+        let bind_path = path.to_owned();
+        let (thread, ()) = ListenerThread::spawn("oqs-kex-rpc-unix", move |handle| {
+            let listener = UnixListener::bind(&bind_path)?;
+            Ok(((), serve(listener, handle, rpc, middleware, max_request_size)))
+        })?;
+        Ok(UnixServer {
+            path: path.to_owned(),
+            thread: Some(thread),
+        })
+    }
+
+    /// Blocks until the server has stopped.
+    pub fn wait(mut self) {
+        if let Some(thread) = self.thread.take() {
+            thread.wait();
+        }
+    }
+}
+
+impl Drop for UnixServer {
+    fn drop(&mut self) {
+        // Stop accepting connections before removing the socket file.
+        self.thread.take();
+        if let Err(error) = fs::remove_file(&self.path) {
+            warn!("Unable to remove {}: {}", self.path.display(), error);
+        }
+    }
+}
+
+/// Returns a future serving the connections accepted on `listener`.
+fn serve<M, S>(
+    listener: UnixListener,
+    handle: &Handle,
+    rpc: Rpc<M, S>,
+    middleware: Arc<dyn RequestMiddleware>,
+    max_request_size: Option<usize>,
+) -> Box<dyn Future<Item = (), Error = ()>>
+where
+    M: Metadata,
+    S: Middleware<M>,
+{
+    let handle = handle.clone();
+    let http = Rc::new(Http::<Chunk>::new());
+    let connections = listener
+        .incoming()
+        .for_each(move |stream| {
+            let credentials = match get_peer_credentials(&stream) {
+                Ok(credentials) => credentials,
+                Err(error) => {
+                    debug!("Unable to get the peer credentials of a connection: {}", error);
+                    return Ok(());
+                }
+            };
+            let service = TrustedHeadersService {
+                service: ServerHandler::new(rpc.clone(), None, None, middleware.clone()),
+                headers: vec![(PEER_CREDENTIALS_HEADER, credentials.encode())],
+            };
+            let service = BodyLimitService::new(service, max_request_size);
+            let connection = http
+                .serve_connection(stream, service)
+                .map_err(|error| debug!("Error on Unix socket connection: {}", error));
+            handle.spawn(connection);
+            Ok(())
+        })
+        .map_err(|error| error!("Unable to accept Unix socket connections: {}", error));
+    Box::new(connections)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn credentials_roundtrip() {
+        let credentials = PeerCredentials {
+            uid: 1000,
+            gid: 100,
+            pid: Some(4242),
+        };
+        assert_eq!(PeerCredentials::decode(&credentials.encode()), Some(credentials));
+        let credentials = PeerCredentials { pid: None, ..credentials };
+        assert_eq!(PeerCredentials::decode(&credentials.encode()), Some(credentials));
+    }
+
+    #[test]
+    fn decode_invalid_credentials() {
+        assert_eq!(PeerCredentials::decode(""), None);
+        assert_eq!(PeerCredentials::decode("1000"), None);
+        assert_eq!(PeerCredentials::decode("1000 100 4242 1"), None);
+        assert_eq!(PeerCredentials::decode("1000 -1"), None);
+    }
+}
@@ -0,0 +1,208 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The parts of RFC 6455 shared by the server and the client: the opening handshake key and the
+//! framing.
+
+use base64;
+use sha1::{Digest, Sha1};
+
+pub const OPCODE_CONTINUATION: u8 = 0x0;
+pub const OPCODE_TEXT: u8 = 0x1;
+pub const OPCODE_BINARY: u8 = 0x2;
+pub const OPCODE_CLOSE: u8 = 0x8;
+pub const OPCODE_PING: u8 = 0x9;
+pub const OPCODE_PONG: u8 = 0xa;
+
+/// Close status code for a normal closure.
+pub const CLOSE_NORMAL: u16 = 1000;
+/// Close status code for a violation of the protocol.
+pub const CLOSE_PROTOCOL_ERROR: u16 = 1002;
+/// Close status code for a text message that is not valid UTF-8.
+pub const CLOSE_INVALID_DATA: u16 = 1007;
+/// Close status code for a message larger than the endpoint accepts.
+pub const CLOSE_TOO_BIG: u16 = 1009;
+
+/// The only protocol version in RFC 6455.
+pub const VERSION: &str = "13";
+
+const ACCEPT_GUID: &[u8] = b"258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+const FIN: u8 = 0x80;
+const RESERVED: u8 = 0x70;
+const MASKED: u8 = 0x80;
+
+
+/// Returns the `Sec-WebSocket-Accept` value the server answers the `Sec-WebSocket-Key` `key` with.
+pub fn accept_key(key: &str) -> String {
+    let mut hasher = Sha1::default();
+    hasher.input(key.trim().as_bytes());
+    hasher.input(ACCEPT_GUID);
+    base64::encode(&hasher.result())
+}
+
+/// The header of a frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameHeader {
+    pub fin: bool,
+    pub opcode: u8,
+    pub mask: Option<[u8; 4]>,
+    pub len: u64,
+}
+
+impl FrameHeader {
+    /// Returns how many bytes of the header follow its first two bytes.
+    pub fn remaining_len(start: [u8; 2]) -> usize {
+        let mask_len = if start[1] & MASKED != 0 { 4 } else { 0 };
+        let extended_len = match start[1] & !MASKED {
+            126 => 2,
+            127 => 8,
+            _ => 0,
+        };
+        mask_len + extended_len
+    }
+
+    /// Parses a header from its first two bytes and the `remaining_len` bytes following them.
+    /// Returns `None` if the header is invalid.
+    pub fn decode(start: [u8; 2], rest: &[u8]) -> Option<Self> {
+        if start[0] & RESERVED != 0 || rest.len() != Self::remaining_len(start) {
+            return None;
+        }
+        let fin = start[0] & FIN != 0;
+        let opcode = start[0] & 0x0f;
+        let (len, rest) = match start[1] & !MASKED {
+            126 => (u64::from(read_u16(&rest[..2])), &rest[2..]),
+            127 => (read_u64(&rest[..8]), &rest[8..]),
+            len => (u64::from(len), rest),
+        };
+        let mask = if rest.is_empty() {
+            None
+        } else {
+            let mut mask = [0; 4];
+            mask.copy_from_slice(rest);
+            Some(mask)
+        };
+        // Control frames can't be fragmented and have at most 125 bytes of payload.
+        if opcode & 0x8 != 0 && (!fin || len > 125) {
+            return None;
+        }
+        Some(FrameHeader {
+            fin,
+            opcode,
+            mask,
+            len,
+        })
+    }
+}
+
+/// Returns an unfragmented frame with the given opcode and payload. Clients have to mask their
+/// frames, servers must not.
+pub fn encode(opcode: u8, payload: &[u8], mask: Option<[u8; 4]>) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(payload.len() + 14);
+    frame.push(FIN | opcode);
+    let mask_bit = if mask.is_some() { MASKED } else { 0 };
+    if payload.len() < 126 {
+        frame.push(mask_bit | payload.len() as u8);
+    } else if payload.len() <= usize::from(u16::MAX) {
+        frame.push(mask_bit | 126);
+        frame.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+    } else {
+        frame.push(mask_bit | 127);
+        frame.extend_from_slice(&(payload.len() as u64).to_be_bytes());
+    }
+    let start = frame.len();
+    frame.extend_from_slice(payload);
+    if let Some(mask) = mask {
+        frame.extend_from_slice(&mask);
+        frame[start..].rotate_right(4);
+        apply_mask(&mut frame[start + 4..], mask);
+    }
+    frame
+}
+
+/// Returns a close frame with the given status code.
+pub fn encode_close(code: u16, mask: Option<[u8; 4]>) -> Vec<u8> {
+    encode(OPCODE_CLOSE, &code.to_be_bytes(), mask)
+}
+
+/// Masks or unmasks a payload.
+pub fn apply_mask(data: &mut [u8], mask: [u8; 4]) {
+    for (i, byte) in data.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+}
+
+fn read_u16(data: &[u8]) -> u16 {
+    let mut bytes = [0; 2];
+    bytes.copy_from_slice(data);
+    u16::from_be_bytes(bytes)
+}
+
+fn read_u64(data: &[u8]) -> u64 {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(data);
+    u64::from_be_bytes(bytes)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(frame: &[u8]) -> (FrameHeader, Vec<u8>) {
+        let start = [frame[0], frame[1]];
+        let header_len = 2 + FrameHeader::remaining_len(start);
+        let header = FrameHeader::decode(start, &frame[2..header_len]).unwrap();
+        let mut payload = frame[header_len..].to_vec();
+        assert_eq!(payload.len() as u64, header.len);
+        if let Some(mask) = header.mask {
+            apply_mask(&mut payload, mask);
+        }
+        (header, payload)
+    }
+
+    #[test]
+    fn accept_key_from_rfc() {
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    #[test]
+    fn masked_frame_roundtrip() {
+        let payload = b"{\"jsonrpc\":\"2.0\"}";
+        let frame = encode(OPCODE_TEXT, payload, Some([1, 2, 3, 4]));
+        assert_ne!(&frame[6..], &payload[..]);
+        let (header, decoded) = decode(&frame);
+        assert!(header.fin);
+        assert_eq!(header.opcode, OPCODE_TEXT);
+        assert_eq!(header.mask, Some([1, 2, 3, 4]));
+        assert_eq!(decoded, &payload[..]);
+    }
+
+    #[test]
+    fn extended_length_roundtrip() {
+        for &len in &[125, 126, 65535, 65536] {
+            let payload = vec![0x42; len];
+            let (header, decoded) = decode(&encode(OPCODE_BINARY, &payload, None));
+            assert_eq!(header.mask, None);
+            assert_eq!(decoded, payload);
+        }
+    }
+
+    #[test]
+    fn decode_invalid_header() {
+        // Reserved bit set.
+        assert_eq!(FrameHeader::decode([0xc1, 0], &[]), None);
+        // Fragmented ping.
+        assert_eq!(FrameHeader::decode([OPCODE_PING, 0], &[]), None);
+        // Ping with more than 125 bytes.
+        assert_eq!(FrameHeader::decode([0x80 | OPCODE_PING, 126], &[0, 126]), None);
+    }
+}
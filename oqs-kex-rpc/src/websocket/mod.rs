@@ -0,0 +1,81 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Serving the JSON-RPC key exchange over WebSocket connections.
+//!
+//! The server accepts the same JSON-RPC calls as the HTTP server, but a client can send any number
+//! of them over one upgraded connection. That suits clients behind HTTP proxies that hold on to
+//! connections, and clients doing several exchanges in a row. Start the server with [`start`] or
+//! [`start_async`], and connect to it with [`OqsKexClient::with_websocket`].
+//!
+//! The `MetaExtractor` is called once for every connection, with the HTTP request opening it.
+//! Requests on a connection are answered one at a time, in order. The server closes connections
+//! that don't complete the opening handshake within 10 seconds, or send no frame for 60 seconds.
+//!
+//! [`start`]: fn.start.html
+//! [`start_async`]: fn.start_async.html
+//! [`OqsKexClient::with_websocket`]: ../client/struct.OqsKexClient.html#method.with_websocket
+
+use futures::IntoFuture;
+
+use server::{AsyncCallback, MetaExtractor, Metadata, Result, Server, ServerConstraints,
+             SyncCallback};
+use transcript::ExchangedKeys;
+
+use std::net::SocketAddr;
+use std::result::Result as StdResult;
+
+pub(crate) mod frame;
+mod server;
+
+pub(crate) use self::server::WebSocketServer;
+
+
+/// Tries to start a JSON-RPC 2.0 server accepting WebSocket connections, bound to `addr`.
+///
+/// Works like [`server::start`](../server/fn.start.html). `max_request_size` limits the size of
+/// every message, and larger messages close the connection with status code 1009. The server
+/// can't use TLS, so starting it fails if `constraints` holds a TLS configuration.
+///
+/// All connections are served on one thread, but `on_kex` is called on the worker pool, so a slow
+/// `on_kex` only takes up a worker thread until it returns. Use
+/// [`start_async`](fn.start_async.html) for callbacks that have to wait for I/O.
+pub fn start<ME, M, E, F>(
+    addr: SocketAddr,
+    meta_extractor: ME,
+    on_kex: F,
+    constraints: ServerConstraints,
+) -> Result<Server>
+where
+    M: Metadata + Sync,
+    ME: MetaExtractor<M>,
+    E: ::std::error::Error + Send + 'static,
+    F: Fn(M, ExchangedKeys) -> StdResult<(), E> + Send + Sync + 'static,
+{
+    ::server::start_websocket_server(addr, meta_extractor, SyncCallback(on_kex), constraints)
+}
+
+/// Tries to start a JSON-RPC 2.0 server accepting WebSocket connections, bound to `addr`, with an
+/// asynchronous `on_kex` callback. Works like
+/// [`server::start_async`](../server/fn.start_async.html), see [`start`](fn.start.html).
+pub fn start_async<ME, M, R, F>(
+    addr: SocketAddr,
+    meta_extractor: ME,
+    on_kex: F,
+    constraints: ServerConstraints,
+) -> Result<Server>
+where
+    M: Metadata + Sync,
+    ME: MetaExtractor<M>,
+    R: IntoFuture<Item = ()>,
+    R::Future: Send + 'static,
+    R::Error: ::std::error::Error + Send + 'static,
+    F: Fn(M, ExchangedKeys) -> R + Send + Sync + 'static,
+{
+    ::server::start_websocket_server(addr, meta_extractor, AsyncCallback(on_kex), constraints)
+}
@@ -0,0 +1,394 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use futures::{future, Future, Stream};
+use futures::future::Loop;
+use httparse;
+use tokio_core::net::{TcpListener, TcpStream};
+use tokio_core::reactor::Handle;
+use tokio_io::io::{read, read_exact, read_to_end, write_all};
+
+use super::frame::{self, FrameHeader};
+use listener::{with_timeout, ListenerThread};
+use rate_limit::ClientId;
+use server::{Metadata, RequestMeta, TRUSTED_HEADERS};
+
+use std::io::{self, Read};
+use std::net::SocketAddr;
+use std::str;
+use std::sync::Arc;
+use std::time::Duration;
+
+use jsonrpc_core::MetaIoHandler;
+use jsonrpc_http_server::MetaExtractor;
+use jsonrpc_http_server::hyper::{Method, Uri};
+use jsonrpc_http_server::hyper::server::Request;
+
+/// Max size of the opening handshake request.
+const MAX_HANDSHAKE_LEN: usize = 8 * 1024;
+
+const MAX_HEADERS: usize = 32;
+
+/// How long a client gets to send the opening handshake before its connection is closed.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long the server waits for the next frame from a client before closing the connection.
+const FRAME_TIMEOUT: Duration = Duration::from_secs(60);
+
+const BAD_REQUEST: &[u8] = b"HTTP/1.1 400 Bad Request\r\nConnection: close\r\n\
+                              Content-Length: 0\r\n\r\n";
+
+type IoFuture<T> = Box<dyn Future<Item = T, Error = io::Error>>;
+
+
+/// A JSON-RPC server accepting WebSocket connections. Serves the connections on its own thread,
+/// and stops when dropped.
+pub(crate) struct WebSocketServer {
+    address: SocketAddr,
+    thread: ListenerThread,
+}
+
+impl WebSocketServer {
+    pub fn start<M, ME>(
+        addr: &SocketAddr,
+        handler: MetaIoHandler<RequestMeta<M>>,
+        meta_extractor: ME,
+        max_message_len: Option<usize>,
+    ) -> io::Result<Self>
+    where
+        M: Metadata,
+        ME: MetaExtractor<M>,
+    {
+        let addr = *addr;
+        let connection = Arc::new(Connection {
+            handler,
+            meta_extractor,
+            max_message_len,
+        });
+
+        let (thread, address) = ListenerThread::spawn("oqs-kex-rpc-websocket", move |handle| {
+            let listener = TcpListener::bind(&addr, handle)?;
+            let address = listener.local_addr()?;
+            Ok((address, serve(listener, handle, connection)))
+        })?;
+        Ok(WebSocketServer { address, thread })
+    }
+
+    pub fn address(&self) -> &SocketAddr {
+        &self.address
+    }
+
+    /// Blocks until the server has stopped.
+    pub fn wait(self) {
+        self.thread.wait()
+    }
+}
+
+/// Returns a future serving the connections accepted on `listener`.
+fn serve<M, ME>(
+    listener: TcpListener,
+    handle: &Handle,
+    connection: Arc<Connection<M, ME>>,
+) -> Box<dyn Future<Item = (), Error = ()>>
+where
+    M: Metadata,
+    ME: MetaExtractor<M>,
+{
+    let handle = handle.clone();
+    let connections = listener
+        .incoming()
+        .for_each(move |(stream, peer_addr)| {
+            let future = connection
+                .clone()
+                .serve(stream, peer_addr, &handle)
+                .map_err(move |error| debug!("Error on connection from {}: {}", peer_addr, error));
+            handle.spawn(future);
+            Ok(())
+        })
+        .map_err(|error| error!("Unable to accept WebSocket connections: {}", error));
+    Box::new(connections)
+}
+
+
+/// What the server needs to serve a connection.
+struct Connection<M: Metadata, ME> {
+    handler: MetaIoHandler<RequestMeta<M>>,
+    meta_extractor: ME,
+    max_message_len: Option<usize>,
+}
+
+/// A message being received, possibly in several fragments.
+struct Message {
+    opcode: u8,
+    data: Vec<u8>,
+}
+
+/// The result of reading a frame.
+enum Received {
+    Frame(TcpStream, FrameHeader, Vec<u8>),
+    /// The frame violates the protocol, the connection should be closed with the given code.
+    Invalid(TcpStream, u16),
+    /// The client closed the connection without a close frame.
+    Closed,
+}
+
+impl<M, ME> Connection<M, ME>
+where
+    M: Metadata,
+    ME: MetaExtractor<M>,
+{
+    /// Returns a future performing the opening handshake on `stream` and then answering the
+    /// JSON-RPC requests sent over it until the connection is closed, or the client takes too
+    /// long to send the handshake or the next frame.
+    fn serve(
+        self: Arc<Self>,
+        stream: TcpStream,
+        peer_addr: SocketAddr,
+        handle: &Handle,
+    ) -> IoFuture<()> {
+        let handshake = with_timeout(read_handshake(stream), HANDSHAKE_TIMEOUT, handle);
+        let handle = handle.clone();
+        Box::new(handshake.then(move |result| -> IoFuture<()> {
+            let (stream, handshake) = match result {
+                Ok(read) => read,
+                Err(ref error) if error.kind() == io::ErrorKind::TimedOut => {
+                    return Box::new(future::ok(()));
+                }
+                Err(error) => return Box::new(future::err(error)),
+            };
+            let (request, accept) = match handshake {
+                Some(handshake) => handshake,
+                None => return Box::new(write_all(stream, BAD_REQUEST).map(|_| ())),
+            };
+            let meta = RequestMeta {
+                meta: self.meta_extractor.read_metadata(&request),
+                client: ClientId::from_ip(peer_addr.ip()),
+            };
+            let response = format!(
+                "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+                 Sec-WebSocket-Accept: {}\r\n\r\n",
+                accept
+            );
+            Box::new(
+                write_all(stream, response.into_bytes())
+                    .and_then(move |(stream, _)| self.serve_messages(stream, &handle, meta)),
+            )
+        }))
+    }
+
+    /// Returns a future answering the messages sent over `stream`, one at a time.
+    fn serve_messages(
+        self: Arc<Self>,
+        stream: TcpStream,
+        handle: &Handle,
+        meta: RequestMeta<M>,
+    ) -> IoFuture<()> {
+        let handle = handle.clone();
+        let messages = future::loop_fn((stream, None), move |(stream, message)| {
+            let connection = self.clone();
+            let meta = meta.clone();
+            let received_len = message.as_ref().map_or(0, |message: &Message| message.data.len());
+            let max_len = self.max_message_len
+                .map(|max_len| max_len.saturating_sub(received_len) as u64);
+            let frame = with_timeout(read_frame(stream, max_len), FRAME_TIMEOUT, &handle);
+            frame.then(move |result| match result {
+                Ok(received) => connection.handle_frame(received, message, meta),
+                Err(ref error) if error.kind() == io::ErrorKind::TimedOut => {
+                    Box::new(future::ok(Loop::Break(())))
+                }
+                Err(error) => Box::new(future::err(error)),
+            })
+        });
+        Box::new(messages)
+    }
+
+    fn handle_frame(
+        &self,
+        received: Received,
+        message: Option<Message>,
+        meta: RequestMeta<M>,
+    ) -> IoFuture<Loop<(), (TcpStream, Option<Message>)>> {
+        let (stream, header, payload) = match received {
+            Received::Frame(stream, header, payload) => (stream, header, payload),
+            Received::Invalid(stream, code) => return close(stream, code),
+            Received::Closed => return Box::new(future::ok(Loop::Break(()))),
+        };
+        let message = match (header.opcode, message) {
+            (frame::OPCODE_PING, message) => {
+                let pong = frame::encode(frame::OPCODE_PONG, &payload, None);
+                return Box::new(
+                    write_all(stream, pong).map(|(stream, _)| Loop::Continue((stream, message))),
+                );
+            }
+            (frame::OPCODE_PONG, message) => {
+                return Box::new(future::ok(Loop::Continue((stream, message))));
+            }
+            (frame::OPCODE_CLOSE, _) => return close(stream, frame::CLOSE_NORMAL),
+            (frame::OPCODE_TEXT, None) | (frame::OPCODE_BINARY, None) => Message {
+                opcode: header.opcode,
+                data: payload,
+            },
+            (frame::OPCODE_CONTINUATION, Some(mut message)) => {
+                message.data.extend_from_slice(&payload);
+                message
+            }
+            _ => return close(stream, frame::CLOSE_PROTOCOL_ERROR),
+        };
+        if !header.fin {
+            return Box::new(future::ok(Loop::Continue((stream, Some(message)))));
+        }
+
+        let request = match String::from_utf8(message.data) {
+            Ok(request) => request,
+            Err(_) => return close(stream, frame::CLOSE_INVALID_DATA),
+        };
+        let opcode = message.opcode;
+        let response = self.handler
+            .handle_request(&request, meta)
+            .map_err(|()| io::Error::new(io::ErrorKind::Other, "Unable to handle request"));
+        Box::new(response.and_then(move |response| -> IoFuture<_> {
+            match response {
+                Some(response) => {
+                    let response = frame::encode(opcode, response.as_bytes(), None);
+                    Box::new(write_all(stream, response).map(|(stream, _)| {
+                        Loop::Continue((stream, None))
+                    }))
+                }
+                None => Box::new(future::ok(Loop::Continue((stream, None)))),
+            }
+        }))
+    }
+}
+
+/// Sends a close frame with the given status code and ends the connection.
+fn close<S: 'static>(stream: TcpStream, code: u16) -> IoFuture<Loop<(), S>> {
+    let close = frame::encode_close(code, None);
+    Box::new(write_all(stream, close).map(|_| Loop::Break(())))
+}
+
+/// Returns a future reading the opening handshake request. Resolves to `None` if the request is
+/// not a valid WebSocket handshake.
+fn read_handshake(stream: TcpStream) -> IoFuture<(TcpStream, Option<(Request, String)>)> {
+    let handshake = future::loop_fn((stream, Vec::new()), |(stream, mut buffer)| {
+        read(stream, vec![0; 1024]).and_then(move |(stream, chunk, len)| {
+            if len == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            buffer.extend_from_slice(&chunk[..len]);
+            match parse_handshake(&buffer) {
+                Ok(handshake) => Ok(Loop::Break((stream, handshake))),
+                Err(()) if buffer.len() < MAX_HANDSHAKE_LEN => {
+                    Ok(Loop::Continue((stream, buffer)))
+                }
+                Err(()) => Ok(Loop::Break((stream, None))),
+            }
+        })
+    });
+    Box::new(handshake)
+}
+
+/// Parses the opening handshake request in `data`. Returns the request together with the
+/// `Sec-WebSocket-Accept` value to answer it with, or `None` if it is not a valid handshake. Fails
+/// if the request is not complete yet.
+///
+/// The request is passed to the `MetaExtractor`. Any `TRUSTED_HEADERS` in it are left out, so the
+/// client can't forge them.
+fn parse_handshake(data: &[u8]) -> Result<Option<(Request, String)>, ()> {
+    let mut headers = [httparse::EMPTY_HEADER; MAX_HEADERS];
+    let mut parsed = httparse::Request::new(&mut headers);
+    match parsed.parse(data) {
+        Ok(httparse::Status::Complete(len)) if len == data.len() => (),
+        Ok(httparse::Status::Partial) => return Err(()),
+        _ => return Ok(None),
+    }
+    if parsed.method != Some("GET") {
+        return Ok(None);
+    }
+    let uri = match parsed.path.and_then(|path| path.parse::<Uri>().ok()) {
+        Some(uri) => uri,
+        None => return Ok(None),
+    };
+
+    let header = |name: &str| {
+        parsed
+            .headers
+            .iter()
+            .find(|header| header.name.eq_ignore_ascii_case(name))
+            .and_then(|header| str::from_utf8(header.value).ok())
+    };
+    let has_token = |name: &str, token: &str| {
+        match header(name) {
+            Some(value) => value
+                .split(',')
+                .any(|value| value.trim().eq_ignore_ascii_case(token)),
+            None => false,
+        }
+    };
+    if !has_token("Upgrade", "websocket") || !has_token("Connection", "upgrade")
+        || header("Sec-WebSocket-Version") != Some(frame::VERSION)
+    {
+        return Ok(None);
+    }
+    let accept = match header("Sec-WebSocket-Key") {
+        Some(key) => frame::accept_key(key),
+        None => return Ok(None),
+    };
+
+    let mut request = Request::new(Method::Get, uri);
+    for header in parsed.headers.iter() {
+        let trusted = TRUSTED_HEADERS
+            .iter()
+            .any(|name| header.name.eq_ignore_ascii_case(name));
+        if !trusted {
+            request
+                .headers_mut()
+                .append_raw(header.name.to_owned(), header.value.to_vec());
+        }
+    }
+    Ok(Some((request, accept)))
+}
+
+/// Returns a future reading a frame from a client, with at most `max_len` bytes of payload.
+fn read_frame(stream: TcpStream, max_len: Option<u64>) -> IoFuture<Received> {
+    let frame = read_exact(stream, [0; 2]).then(move |result| -> IoFuture<Received> {
+        let (stream, start) = match result {
+            Ok(read) => read,
+            Err(ref error) if error.kind() == io::ErrorKind::UnexpectedEof => {
+                return Box::new(future::ok(Received::Closed));
+            }
+            Err(error) => return Box::new(future::err(error)),
+        };
+        let rest = vec![0; FrameHeader::remaining_len(start)];
+        Box::new(read_exact(stream, rest).and_then(move |(stream, rest)| -> IoFuture<_> {
+            let header = FrameHeader::decode(start, &rest);
+            // Clients must mask all their frames.
+            let (header, mask) = match header.and_then(|header| Some((header, header.mask?))) {
+                Some(header) => header,
+                None => {
+                    let invalid = Received::Invalid(stream, frame::CLOSE_PROTOCOL_ERROR);
+                    return Box::new(future::ok(invalid));
+                }
+            };
+            if let Some(max_len) = max_len {
+                if header.len > max_len {
+                    let invalid = Received::Invalid(stream, frame::CLOSE_TOO_BIG);
+                    return Box::new(future::ok(invalid));
+                }
+            }
+            let payload = read_to_end(stream.take(header.len), Vec::new());
+            Box::new(payload.map(move |(stream, mut payload)| {
+                if payload.len() as u64 != header.len {
+                    return Received::Closed;
+                }
+                frame::apply_mask(&mut payload, mask);
+                Received::Frame(stream.into_inner(), header, payload)
+            }))
+        }))
+    });
+    Box::new(frame)
+}
@@ -0,0 +1,184 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+extern crate oqs_kex_rpc;
+
+use oqs_kex_rpc::{binary, client, error_codes, server, ConstraintViolation, ExchangedKeys,
+                  OqsKexAlg};
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::str::FromStr;
+use std::sync::{mpsc, Mutex};
+use std::time::Duration;
+
+static ALGOS: &[OqsKexAlg] = &[OqsKexAlg::MlKem768, OqsKexAlg::FrodoKem640Aes];
+
+fn start_server(
+    constraints: server::ServerConstraints,
+) -> (server::Server, mpsc::Receiver<(Metadata, ExchangedKeys)>) {
+    let (tx, rx) = mpsc::channel();
+    let tx = Mutex::new(tx);
+    let on_kex = move |meta: Metadata, keys| {
+        tx.lock().unwrap().send((meta, keys)).unwrap();
+        Ok(()) as Result<(), ::std::io::Error>
+    };
+    let addr = SocketAddr::from_str("127.0.0.1:0").unwrap();
+
+    let server = binary::start(addr, meta_extractor, on_kex, constraints).unwrap();
+    (server, rx)
+}
+
+fn connect(server: &server::Server) -> binary::BinaryKexClient {
+    binary::BinaryKexClient::connect(*server.address().tcp().unwrap()).unwrap()
+}
+
+#[test]
+fn test_binary_request() {
+    let (server, server_channel) = start_server(server::ServerConstraints::default());
+    let mut client = connect(&server);
+
+    let client_keys = client.kex(ALGOS).expect("Error in client during exchange");
+    let (meta, server_keys) = server_channel
+        .recv_timeout(Duration::from_secs(1))
+        .expect("Server did not output keys");
+    assert_eq!(client_keys, server_keys);
+    assert_eq!(meta.peer_addr.unwrap().ip(), server.address().tcp().unwrap().ip());
+}
+
+#[test]
+fn test_binary_several_requests_per_connection() {
+    let (server, server_channel) = start_server(server::ServerConstraints::default());
+    let mut client = connect(&server);
+
+    for _ in 0..3 {
+        let client_keys = client.kex(ALGOS).expect("Error in client during exchange");
+        let (_meta, server_keys) = server_channel
+            .recv_timeout(Duration::from_secs(1))
+            .expect("Server did not output keys");
+        assert_eq!(client_keys, server_keys);
+    }
+}
+
+#[test]
+fn test_binary_confirmed_request() {
+    let (server, server_channel) = start_server(server::ServerConstraints::default());
+    let mut client = connect(&server);
+
+    let client_keys = client
+        .kex_with_confirmation(ALGOS)
+        .expect("Error in client during confirmed exchange");
+    let (_meta, server_keys) = server_channel
+        .recv_timeout(Duration::from_secs(1))
+        .expect("Server did not output keys");
+    assert_eq!(client_keys, server_keys);
+}
+
+#[test]
+fn test_binary_constraint_violation() {
+    let constraints = server::ServerConstraints {
+        max_algorithms: Some(1),
+        ..server::ServerConstraints::default()
+    };
+    let (server, server_channel) = start_server(constraints);
+    let mut client = connect(&server);
+
+    match client.kex(ALGOS) {
+        Err(client::Error(client::ErrorKind::ConstraintError(violation), _)) => assert_eq!(
+            violation,
+            ConstraintViolation::TooManyAlgorithms { count: 2, max: 1 }
+        ),
+        Err(e) => panic!("Exchange failed with unexpected error: {}", e),
+        Ok(_) => panic!("An expected constraint violation did NOT occur"),
+    }
+    assert!(server_channel.try_recv().is_err());
+
+    // The connection is still usable after an error.
+    client.kex(&ALGOS[..1]).expect("Error in client during exchange");
+}
+
+#[test]
+fn test_binary_invalid_frame() {
+    let (server, _server_channel) = start_server(server::ServerConstraints::default());
+    let mut stream = TcpStream::connect(server.address().tcp().unwrap()).unwrap();
+
+    // A frame of an unknown version.
+    stream.write_all(&[0xee, 0x01, 0, 0, 0, 0]).unwrap();
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).unwrap();
+
+    let mut expected = vec![1, 0xff, 0, 0, 0, 9];
+    expected.extend_from_slice(&(-32600i64).to_be_bytes());
+    expected.push(0);
+    assert_eq!(response, expected);
+}
+
+#[test]
+fn test_binary_frame_too_large() {
+    let constraints = server::ServerConstraints {
+        max_request_size: Some(1024),
+        ..server::ServerConstraints::default()
+    };
+    let (server, server_channel) = start_server(constraints);
+    let mut client = connect(&server);
+
+    // A single ML-KEM-768 public key is larger than 1024 bytes.
+    assert!(client.kex(&[OqsKexAlg::MlKem768]).is_err());
+    assert!(server_channel.try_recv().is_err());
+}
+
+#[test]
+fn test_binary_frame_too_large_without_max_size() {
+    let (server, _server_channel) = start_server(server::ServerConstraints::default());
+    let mut stream = TcpStream::connect(server.address().tcp().unwrap()).unwrap();
+
+    // A key exchange frame announcing a 4 GiB payload is rejected before any payload is read.
+    stream.write_all(&[1, 0x01, 0xff, 0xff, 0xff, 0xff]).unwrap();
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).unwrap();
+
+    let mut expected = vec![1, 0xff, 0, 0, 0, 9];
+    expected.extend_from_slice(&(-32600i64).to_be_bytes());
+    expected.push(0);
+    assert_eq!(response, expected);
+}
+
+#[test]
+fn test_binary_error_codes_shared_with_json_rpc() {
+    let constraints = server::ServerConstraints {
+        require_confirmation: true,
+        ..server::ServerConstraints::default()
+    };
+    let (server, _server_channel) = start_server(constraints);
+    let mut stream = TcpStream::connect(server.address().tcp().unwrap()).unwrap();
+
+    // A key exchange request without any messages.
+    stream.write_all(&[1, 0x01, 0, 0, 0, 2, 0, 0]).unwrap();
+    let mut response = [0; 6 + 9];
+    stream.read_exact(&mut response).unwrap();
+
+    let mut code = [0; 8];
+    code.copy_from_slice(&response[6..14]);
+    assert_eq!(i64::from_be_bytes(code), error_codes::CONSTRAINT_ERROR);
+    // `ConfirmationRequired` is the fifth constraint violation.
+    assert_eq!(response[14], 5);
+}
+
+
+fn meta_extractor(peer_addr: &SocketAddr) -> Metadata {
+    Metadata {
+        peer_addr: Some(*peer_addr),
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Metadata {
+    peer_addr: Option<SocketAddr>,
+}
+
+impl server::Metadata for Metadata {}
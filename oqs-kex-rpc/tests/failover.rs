@@ -0,0 +1,125 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+extern crate oqs_kex_rpc;
+
+use oqs_kex_rpc::{client, server, ConstraintViolation, ExchangedKeys, OqsKexAlg};
+
+use std::net::{SocketAddr, TcpListener};
+use std::str::FromStr;
+use std::sync::{mpsc, Mutex};
+use std::time::Duration;
+
+static ALGOS: &[OqsKexAlg] = &[OqsKexAlg::MlKem768, OqsKexAlg::FrodoKem640Aes];
+
+fn start_server(
+    constraints: server::ServerConstraints,
+) -> (server::Server, String, mpsc::Receiver<ExchangedKeys>) {
+    let (tx, rx) = mpsc::channel();
+    let tx = Mutex::new(tx);
+    let on_kex = move |_meta: Metadata, keys| {
+        tx.lock().unwrap().send(keys).unwrap();
+        Ok(()) as Result<(), ::std::io::Error>
+    };
+    let addr = SocketAddr::from_str("127.0.0.1:0").unwrap();
+
+    let server = server::start(addr, meta_extractor, on_kex, constraints).unwrap();
+    let uri = format!("http://{}", server.address());
+    (server, uri, rx)
+}
+
+/// Returns the address of a port nothing listens on.
+fn closed_port_uri() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    format!("http://{}", listener.local_addr().unwrap())
+}
+
+fn build_client(server_uris: &[&str]) -> client::FailoverClient {
+    client::OqsKexClient::builder()
+        .connect_timeout(Duration::from_secs(1))
+        .request_timeout(Duration::from_secs(5))
+        .build_failover(server_uris)
+        .unwrap()
+}
+
+#[test]
+fn test_first_server_used() {
+    let (_server1, uri1, channel1) = start_server(server::ServerConstraints::default());
+    let (_server2, uri2, channel2) = start_server(server::ServerConstraints::default());
+    let mut client = build_client(&[&uri1, &uri2]);
+
+    let result = client.kex(ALGOS).expect("Error in client during exchange");
+    assert_eq!(result.server, 0);
+    let server_keys = channel1
+        .recv_timeout(Duration::from_secs(1))
+        .expect("Server did not output keys");
+    assert_eq!(result.keys, server_keys);
+    assert!(channel2.try_recv().is_err());
+}
+
+#[test]
+fn test_fail_over_to_next_server() {
+    let (_server, uri, channel) = start_server(server::ServerConstraints::default());
+    let mut client = build_client(&[&closed_port_uri(), &closed_port_uri(), &uri]);
+
+    let result = client
+        .kex_with_confirmation(ALGOS)
+        .expect("Error in client during exchange");
+    assert_eq!(result.server, 2);
+    let server_keys = channel
+        .recv_timeout(Duration::from_secs(1))
+        .expect("Server did not output keys");
+    assert_eq!(result.keys, server_keys);
+}
+
+#[test]
+fn test_all_servers_unavailable() {
+    let mut client = build_client(&[&closed_port_uri(), &closed_port_uri()]);
+
+    match client.kex(ALGOS) {
+        Err(client::Error(client::ErrorKind::RpcError, _)) => (),
+        Err(e) => panic!("Exchange failed with unexpected error: {}", e),
+        Ok(_) => panic!("Exchange without any server succeeded"),
+    }
+}
+
+#[test]
+fn test_no_fail_over_on_constraint_violation() {
+    let constraints = server::ServerConstraints {
+        max_algorithms: Some(1),
+        ..server::ServerConstraints::default()
+    };
+    let (_server1, uri1, _channel1) = start_server(constraints);
+    let (_server2, uri2, channel2) = start_server(server::ServerConstraints::default());
+    let mut client = build_client(&[&uri1, &uri2]);
+
+    match client.kex(ALGOS) {
+        Err(client::Error(client::ErrorKind::ConstraintError(violation), _)) => assert_eq!(
+            violation,
+            ConstraintViolation::TooManyAlgorithms { count: 2, max: 1 }
+        ),
+        Err(e) => panic!("Exchange failed with unexpected error: {}", e),
+        Ok(_) => panic!("An expected constraint violation did NOT occur"),
+    }
+    assert!(channel2.try_recv().is_err());
+}
+
+#[test]
+fn test_empty_server_list() {
+    assert!(client::OqsKexClient::builder().build_failover(&[]).is_err());
+}
+
+
+fn meta_extractor(_request: &server::Request) -> Metadata {
+    Metadata
+}
+
+#[derive(Debug, Clone, Default)]
+struct Metadata;
+
+impl server::Metadata for Metadata {}
@@ -6,26 +6,45 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+extern crate futures;
+extern crate oqs;
 extern crate oqs_kex_rpc;
 
 #[macro_use]
 extern crate lazy_static;
 
-use oqs_kex_rpc::{client, server, OqsKexAlg, SharedKey};
+use futures::sync::oneshot;
+use oqs::rand::{OqsRand, OqsRandAlg};
+use oqs::sig::OqsSig;
+use oqs_kex_rpc::{client, server, AlgorithmCosts, ConstraintViolation, ExchangedKeys, OqsKexAlg,
+                  OqsSigAlg, PublicKey, SecretKey};
 
-use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::io::{Read, Write};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpStream};
 use std::str::FromStr;
 use std::sync::{mpsc, Mutex};
+use std::thread;
 use std::time::Duration;
 
+type Verifier = fn(
+    &mut client::OqsKexClient,
+    &[OqsKexAlg],
+    &mpsc::Receiver<(Metadata, ExchangedKeys)>,
+);
+
 fn test_helper(
     algorithms: &[OqsKexAlg],
     constraints: &server::ServerConstraints,
-    verifier: fn(
-        &mut client::OqsKexClient,
-        &[OqsKexAlg],
-        &mpsc::Receiver<(Metadata, Vec<SharedKey>)>,
-    ),
+    verifier: Verifier,
+) {
+    test_helper_with_server_key(algorithms, constraints, None, verifier)
+}
+
+fn test_helper_with_server_key(
+    algorithms: &[OqsKexAlg],
+    constraints: &server::ServerConstraints,
+    server_key: Option<&PublicKey>,
+    verifier: Verifier,
 ) {
     let local_addr = SocketAddr::from_str("127.0.0.1:0").unwrap();
 
@@ -41,12 +60,87 @@ fn test_helper(
     let http_addr = format!("http://{}", server.address());
     println!("kex server listening on {}", http_addr);
 
-    let mut client = client::OqsKexClient::new(&http_addr).unwrap();
+    let mut client = match server_key {
+        Some(server_key) => {
+            client::OqsKexClient::with_server_key(&http_addr, server_key.clone()).unwrap()
+        }
+        None => client::OqsKexClient::new(&http_addr).unwrap(),
+    };
 
     verifier(&mut client, algorithms, &rx);
 }
 
+fn start_async_server(
+    fail: bool,
+) -> (server::Server, mpsc::Receiver<(Metadata, ExchangedKeys)>) {
+    let local_addr = SocketAddr::from_str("127.0.0.1:0").unwrap();
+
+    let (tx, rx) = mpsc::channel();
+    let tx = Mutex::new(tx);
+    // Completes the callback from another thread, so the server has to wait for the future.
+    // Dropping `done_tx` without sending fails the future with `Canceled`.
+    let on_kex = move |meta: Metadata, keys| {
+        let (done_tx, done_rx) = oneshot::channel();
+        let tx = tx.lock().unwrap().clone();
+        thread::spawn(move || if !fail {
+            tx.send((meta, keys)).unwrap();
+            done_tx.send(()).unwrap();
+        });
+        done_rx
+    };
+
+    let server =
+        server::start_async(local_addr, meta_extractor, on_kex, CONSTRAINTS_NONE.clone()).unwrap();
+    (server, rx)
+}
+
+fn start_raw_server(constraints: &server::ServerConstraints) -> server::Server {
+    let local_addr = SocketAddr::from_str("127.0.0.1:0").unwrap();
+    let on_kex = |_meta: Metadata, _keys| Ok(()) as Result<(), ::std::io::Error>;
+    server::start(local_addr, meta_extractor, on_kex, constraints.clone()).unwrap()
+}
+
+/// Sends an HTTP request with the given extra headers and body, without any framing of its own,
+/// and returns the server's raw response.
+fn send_raw_http_request(server: &server::Server, headers: &str, body: &[u8]) -> String {
+    let addr = server.address().tcp().unwrap();
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    write!(
+        stream,
+        "POST / HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\n\
+         Connection: close\r\n{}\r\n",
+        addr,
+        headers
+    ).unwrap();
+    // The server may respond and close the connection before reading the whole body.
+    let _ = stream.write_all(body);
+    let mut response = Vec::new();
+    let _ = stream.read_to_end(&mut response);
+    String::from_utf8_lossy(&response).into_owned()
+}
+
+/// Encodes `chunks` as a chunked HTTP body.
+fn encode_chunked(chunks: &[&[u8]]) -> Vec<u8> {
+    let mut body = Vec::new();
+    for chunk in chunks {
+        body.extend_from_slice(format!("{:x}\r\n", chunk.len()).as_bytes());
+        body.extend_from_slice(chunk);
+        body.extend_from_slice(b"\r\n");
+    }
+    body.extend_from_slice(b"0\r\n\r\n");
+    body
+}
+
 lazy_static! {
+    static ref SERVER_KEYS: (PublicKey, SecretKey) = generate_server_keys();
+    static ref OTHER_SERVER_KEYS: (PublicKey, SecretKey) = generate_server_keys();
+    static ref CONSTRAINTS_SIGNING: server::ServerConstraints = server::ServerConstraints {
+        signing_key: Some(SERVER_KEYS.1.clone()),
+        ..server::ServerConstraints::default()
+    };
     static ref CONSTRAINTS_NONE: server::ServerConstraints =
         server::ServerConstraints::default();
     static ref CONSTRAINTS_DEFAULT: server::ServerConstraints =
@@ -56,8 +150,43 @@ lazy_static! {
             Some(ALGOS_DEFAULT.len()),
             Some(1),
         );
-    static ref CONSTRAINTS_SINGLE_NEWHOPE: server::ServerConstraints =
-        server::ServerConstraints::new(None, Some(vec![OqsKexAlg::RlweNewhope]), Some(1), Some(1));
+    static ref CONSTRAINTS_SINGLE_MLKEM: server::ServerConstraints =
+        server::ServerConstraints::new(None, Some(vec![OqsKexAlg::MlKem768]), Some(1), Some(1));
+    static ref CONSTRAINTS_TWO_FRODO: server::ServerConstraints =
+        server::ServerConstraints::new(
+            None,
+            Some(vec![OqsKexAlg::FrodoKem640Aes]),
+            Some(2),
+            Some(2),
+        );
+    static ref CONSTRAINTS_REQUIRE_CONFIRMATION: server::ServerConstraints =
+        server::ServerConstraints {
+            require_confirmation: true,
+            ..server::ServerConstraints::default()
+        };
+    static ref CONSTRAINTS_SINGLE_WORKER: server::ServerConstraints =
+        server::ServerConstraints {
+            worker_threads: Some(1),
+            max_queued_exchanges: Some(0),
+            ..server::ServerConstraints::default()
+        };
+    static ref CONSTRAINTS_RATE_LIMITED: server::ServerConstraints =
+        server::ServerConstraints {
+            algorithm_costs: MLKEM_COSTS_THREE.clone(),
+            rate_limit: Some(server::RateLimit::new(6, 0)),
+            ..server::ServerConstraints::default()
+        };
+    static ref CONSTRAINTS_MAX_COST_FIVE: server::ServerConstraints =
+        server::ServerConstraints {
+            algorithm_costs: MLKEM_COSTS_THREE.clone(),
+            max_request_cost: Some(5),
+            ..server::ServerConstraints::default()
+        };
+    static ref MLKEM_COSTS_THREE: AlgorithmCosts = {
+        let mut costs = AlgorithmCosts::new();
+        costs.set_cost(OqsKexAlg::MlKem768, 3);
+        costs
+    };
     static ref CONSTRAINTS_MAX_TWO_ALGOS: server::ServerConstraints =
         server::ServerConstraints::new(None, None, Some(2), None);
     static ref CONSTRAINTS_REQUEST_MAX_10KB: server::ServerConstraints =
@@ -66,19 +195,29 @@ lazy_static! {
         server::ServerConstraints::new(Some(1024), None, None, None);
 }
 
+static CAPABILITIES_REQUEST: &[u8] =
+    br#"{"jsonrpc":"2.0","method":"capabilities","params":[],"id":1}"#;
+
 static ALGOS_NONE: &[OqsKexAlg] = &[];
 static ALGOS_DEFAULT: &[OqsKexAlg] = &[
-    OqsKexAlg::RlweNewhope,
-    OqsKexAlg::CodeMcbits,
-    OqsKexAlg::SidhCln16,
+    OqsKexAlg::MlKem768,
+    OqsKexAlg::FrodoKem640Aes,
+    OqsKexAlg::Hqc128,
+];
+static ALGOS_EXOTIC: &[OqsKexAlg] = &[OqsKexAlg::MlKem1024, OqsKexAlg::FrodoKem976Shake];
+static ALGOS_SINGLE_MLKEM: &[OqsKexAlg] = &[OqsKexAlg::MlKem768];
+static ALGOS_TWO_MLKEM: &[OqsKexAlg] = &[OqsKexAlg::MlKem768, OqsKexAlg::MlKem768];
+static ALGOS_TWO_FRODO: &[OqsKexAlg] = &[OqsKexAlg::FrodoKem640Aes, OqsKexAlg::FrodoKem640Aes];
+static ALGOS_PREFERENCES: &[OqsKexAlg] = &[
+    OqsKexAlg::MlKem1024,
+    OqsKexAlg::FrodoKem976Shake,
+    OqsKexAlg::MlKem768,
+    OqsKexAlg::MlKem768,
 ];
-static ALGOS_EXOTIC: &[OqsKexAlg] = &[OqsKexAlg::MlweKyber, OqsKexAlg::Ntru];
-static ALGOS_SINGLE_NEWHOPE: &[OqsKexAlg] = &[OqsKexAlg::RlweNewhope];
-static ALGOS_TWO_NEWHOPE: &[OqsKexAlg] = &[OqsKexAlg::RlweNewhope, OqsKexAlg::RlweNewhope];
-static ALGOS_THREE_NEWHOPE: &[OqsKexAlg] = &[
-    OqsKexAlg::RlweNewhope,
-    OqsKexAlg::RlweNewhope,
-    OqsKexAlg::RlweNewhope,
+static ALGOS_THREE_MLKEM: &[OqsKexAlg] = &[
+    OqsKexAlg::MlKem768,
+    OqsKexAlg::MlKem768,
+    OqsKexAlg::MlKem768,
 ];
 
 #[test]
@@ -103,31 +242,226 @@ fn test_regular_request_constrained() {
 
 #[test]
 fn test_only_enabled_algos_allowed() {
-    test_helper(ALGOS_EXOTIC, &CONSTRAINTS_DEFAULT, verify_kex_fails)
+    test_helper(ALGOS_EXOTIC, &CONSTRAINTS_DEFAULT, |client, algorithms, _| {
+        verify_kex_violates(
+            client,
+            algorithms,
+            ConstraintViolation::DisallowedAlgorithm(OqsKexAlg::MlKem1024),
+        )
+    })
+}
+
+#[test]
+fn test_allow_listed_frodo_with_fresh_seeds() {
+    test_helper(ALGOS_TWO_FRODO, &CONSTRAINTS_TWO_FRODO, verify_kex_succeeds)
+}
+
+#[test]
+fn test_confirmed_request() {
+    test_helper(ALGOS_DEFAULT, &CONSTRAINTS_NONE, verify_confirmed_kex_succeeds)
+}
+
+#[test]
+fn test_required_confirmation_permits_confirmed_request() {
+    test_helper(
+        ALGOS_DEFAULT,
+        &CONSTRAINTS_REQUIRE_CONFIRMATION,
+        verify_confirmed_kex_succeeds,
+    )
+}
+
+#[test]
+fn test_required_confirmation_rejects_unconfirmed_request() {
+    test_helper(
+        ALGOS_DEFAULT,
+        &CONSTRAINTS_REQUIRE_CONFIRMATION,
+        |client, algorithms, _| {
+            verify_kex_violates(client, algorithms, ConstraintViolation::ConfirmationRequired)
+        },
+    )
+}
+
+#[test]
+fn test_signed_request() {
+    test_helper_with_server_key(
+        ALGOS_DEFAULT,
+        &CONSTRAINTS_SIGNING,
+        Some(&SERVER_KEYS.0),
+        verify_kex_succeeds,
+    )
+}
+
+#[test]
+fn test_signed_confirmed_request() {
+    test_helper_with_server_key(
+        ALGOS_DEFAULT,
+        &CONSTRAINTS_SIGNING,
+        Some(&SERVER_KEYS.0),
+        verify_confirmed_kex_succeeds,
+    )
+}
+
+#[test]
+fn test_other_server_key_rejected() {
+    test_helper_with_server_key(
+        ALGOS_DEFAULT,
+        &CONSTRAINTS_SIGNING,
+        Some(&OTHER_SERVER_KEYS.0),
+        verify_kex_fails,
+    )
+}
+
+#[test]
+fn test_unsigned_request_rejected_by_pinning_client() {
+    test_helper_with_server_key(
+        ALGOS_DEFAULT,
+        &CONSTRAINTS_NONE,
+        Some(&SERVER_KEYS.0),
+        verify_kex_fails,
+    )
+}
+
+#[test]
+fn test_single_worker_serves_sequential_requests() {
+    test_helper(ALGOS_DEFAULT, &CONSTRAINTS_SINGLE_WORKER, |client, algorithms, rx| {
+        for _ in 0..3 {
+            verify_kex_succeeds(client, algorithms, rx);
+        }
+        verify_confirmed_kex_succeeds(client, algorithms, rx);
+    })
+}
+
+#[test]
+fn test_rate_limit() {
+    test_helper(
+        ALGOS_SINGLE_MLKEM,
+        &CONSTRAINTS_RATE_LIMITED,
+        |client, algorithms, rx| {
+            verify_kex_succeeds(client, algorithms, rx);
+            verify_confirmed_kex_succeeds(client, algorithms, rx);
+            match client.kex(algorithms) {
+                Err(client::Error(client::ErrorKind::RateLimited, _)) => (),
+                Err(e) => panic!("Exchange failed with unexpected error: {}", e),
+                Ok(_) => panic!("An expected rate limit failure did NOT occur"),
+            }
+        },
+    )
+}
+
+#[test]
+fn test_async_callback() {
+    let (server, rx) = start_async_server(false);
+    let mut client = client::OqsKexClient::new(&format!("http://{}", server.address())).unwrap();
+    verify_kex_succeeds(&mut client, ALGOS_DEFAULT, &rx);
+    verify_confirmed_kex_succeeds(&mut client, ALGOS_DEFAULT, &rx);
+}
+
+#[test]
+fn test_async_callback_error() {
+    let (server, _rx) = start_async_server(true);
+    let mut client = client::OqsKexClient::new(&format!("http://{}", server.address())).unwrap();
+    match client.kex(ALGOS_DEFAULT) {
+        Err(client::Error(client::ErrorKind::CallbackError, _)) => (),
+        Err(e) => panic!("Exchange failed with unexpected error: {}", e),
+        Ok(_) => panic!("An expected callback failure did NOT occur"),
+    }
+}
+
+#[test]
+fn test_capabilities() {
+    test_helper(
+        ALGOS_DEFAULT,
+        &CONSTRAINTS_DEFAULT,
+        verify_default_capabilities,
+    )
+}
+
+#[test]
+fn test_negotiated_request() {
+    test_helper(
+        ALGOS_PREFERENCES,
+        &CONSTRAINTS_SINGLE_MLKEM,
+        verify_negotiated_kex_selects_mlkem,
+    )
+}
+
+#[test]
+fn test_negotiated_request_confirmation_required() {
+    test_helper(
+        ALGOS_PREFERENCES,
+        &CONSTRAINTS_REQUIRE_CONFIRMATION,
+        verify_negotiated_kex_selects_all,
+    )
+}
+
+#[test]
+fn test_negotiated_request_nothing_in_common() {
+    test_helper(
+        ALGOS_EXOTIC,
+        &CONSTRAINTS_SINGLE_MLKEM,
+        verify_negotiated_kex_fails,
+    )
 }
 
 #[test]
 fn test_max_algorithm_constraint() {
     test_helper(
-        ALGOS_THREE_NEWHOPE,
+        ALGOS_THREE_MLKEM,
         &CONSTRAINTS_MAX_TWO_ALGOS,
-        verify_kex_fails,
+        |client, algorithms, _| {
+            verify_kex_violates(
+                client,
+                algorithms,
+                ConstraintViolation::TooManyAlgorithms { count: 3, max: 2 },
+            )
+        },
     )
 }
 
 #[test]
 fn test_max_occurrences_constraint() {
     test_helper(
-        ALGOS_TWO_NEWHOPE,
-        &CONSTRAINTS_SINGLE_NEWHOPE,
+        ALGOS_TWO_MLKEM,
+        &CONSTRAINTS_SINGLE_MLKEM,
         verify_kex_fails,
     )
 }
 
+#[test]
+fn test_max_occurrences_violation_reported() {
+    test_helper(ALGOS_TWO_MLKEM, &CONSTRAINTS_DEFAULT, |client, algorithms, _| {
+        verify_kex_violates(
+            client,
+            algorithms,
+            ConstraintViolation::TooManyOccurrences {
+                algorithm: OqsKexAlg::MlKem768,
+                count: 2,
+                max: 1,
+            },
+        )
+    })
+}
+
+#[test]
+fn test_max_request_cost_constraint() {
+    test_helper(
+        ALGOS_SINGLE_MLKEM,
+        &CONSTRAINTS_MAX_COST_FIVE,
+        verify_kex_succeeds,
+    );
+    test_helper(ALGOS_TWO_MLKEM, &CONSTRAINTS_MAX_COST_FIVE, |client, algorithms, _| {
+        verify_kex_violates(
+            client,
+            algorithms,
+            ConstraintViolation::TooExpensive { cost: 6, max: 5 },
+        )
+    })
+}
+
 #[test]
 fn test_large_request_max_size_permits_request() {
     test_helper(
-        ALGOS_SINGLE_NEWHOPE,
+        ALGOS_SINGLE_MLKEM,
         &CONSTRAINTS_REQUEST_MAX_10KB,
         verify_kex_succeeds,
     )
@@ -136,17 +470,87 @@ fn test_large_request_max_size_permits_request() {
 #[test]
 fn test_small_request_max_size_rejects_request() {
     test_helper(
-        ALGOS_THREE_NEWHOPE,
+        ALGOS_THREE_MLKEM,
         &CONSTRAINTS_REQUEST_MAX_1KB,
         verify_kex_fails,
     )
 }
 
+#[test]
+fn test_max_size_rejects_large_content_length() {
+    let server = start_raw_server(&CONSTRAINTS_REQUEST_MAX_1KB);
+    let body = vec![b' '; 2048];
+    let headers = format!("Content-Length: {}\r\n", body.len());
+    let response = send_raw_http_request(&server, &headers, &body);
+    assert!(response.starts_with("HTTP/1.1 413"), "Unexpected response: {}", response);
+}
+
+#[test]
+fn test_max_size_ignores_bytes_past_content_length() {
+    let server = start_raw_server(&CONSTRAINTS_REQUEST_MAX_1KB);
+    let mut body = CAPABILITIES_REQUEST.to_vec();
+    body.extend_from_slice(&[b' '; 2048]);
+    let headers = format!("Content-Length: {}\r\n", CAPABILITIES_REQUEST.len());
+    let response = send_raw_http_request(&server, &headers, &body);
+    assert!(response.starts_with("HTTP/1.1 200"), "Unexpected response: {}", response);
+    assert!(response.contains(r#""result""#), "Unexpected response: {}", response);
+}
+
+#[test]
+fn test_max_size_permits_small_chunked_request() {
+    let server = start_raw_server(&CONSTRAINTS_REQUEST_MAX_1KB);
+    let (start, end) = CAPABILITIES_REQUEST.split_at(CAPABILITIES_REQUEST.len() / 2);
+    let body = encode_chunked(&[start, end]);
+    let response = send_raw_http_request(&server, "Transfer-Encoding: chunked\r\n", &body);
+    assert!(response.starts_with("HTTP/1.1 200"), "Unexpected response: {}", response);
+    assert!(response.contains(r#""result""#), "Unexpected response: {}", response);
+}
+
+#[test]
+fn test_max_size_rejects_large_chunked_request() {
+    let server = start_raw_server(&CONSTRAINTS_REQUEST_MAX_1KB);
+    let chunk = [b' '; 512];
+    let body = encode_chunked(&[CAPABILITIES_REQUEST, &chunk, &chunk, &chunk]);
+    let response = send_raw_http_request(&server, "Transfer-Encoding: chunked\r\n", &body);
+    assert!(response.starts_with("HTTP/1.1 413"), "Unexpected response: {}", response);
+}
+
+#[test]
+fn test_no_max_size_permits_chunked_request() {
+    let server = start_raw_server(&CONSTRAINTS_NONE);
+    let body = encode_chunked(&[CAPABILITIES_REQUEST]);
+    let response = send_raw_http_request(&server, "Transfer-Encoding: chunked\r\n", &body);
+    assert!(response.starts_with("HTTP/1.1 200"), "Unexpected response: {}", response);
+    assert!(response.contains(r#""result""#), "Unexpected response: {}", response);
+}
+
+#[test]
+fn test_forged_peer_certificate_rejected() {
+    let server = start_raw_server(&CONSTRAINTS_NONE);
+    let headers = format!(
+        "X-Oqs-Kex-Peer-Certificate: AAAA\r\nContent-Length: {}\r\n",
+        CAPABILITIES_REQUEST.len()
+    );
+    let response = send_raw_http_request(&server, &headers, CAPABILITIES_REQUEST);
+    assert!(response.starts_with("HTTP/1.1 400"), "Unexpected response: {}", response);
+}
+
+#[test]
+fn test_forged_peer_credentials_rejected() {
+    let server = start_raw_server(&CONSTRAINTS_NONE);
+    let headers = format!(
+        "X-Oqs-Kex-Peer-Credentials: 0 0 1\r\nContent-Length: {}\r\n",
+        CAPABILITIES_REQUEST.len()
+    );
+    let response = send_raw_http_request(&server, &headers, CAPABILITIES_REQUEST);
+    assert!(response.starts_with("HTTP/1.1 400"), "Unexpected response: {}", response);
+}
+
 
 fn verify_kex_succeeds(
     client: &mut client::OqsKexClient,
     algorithms: &[OqsKexAlg],
-    server_channel: &mpsc::Receiver<(Metadata, Vec<SharedKey>)>,
+    server_channel: &mpsc::Receiver<(Metadata, ExchangedKeys)>,
 ) {
     let client_keys = client
         .kex(algorithms)
@@ -155,17 +559,94 @@ fn verify_kex_succeeds(
         .recv_timeout(Duration::from_secs(1))
         .expect("Server did not output keys");
 
-    assert_eq!(client_keys.len(), algorithms.len());
+    assert_eq!(client_keys.keys.len(), algorithms.len());
     assert_eq!(client_keys, server_keys);
-    for key in client_keys {
+    for key in client_keys.keys {
         assert!(!key.data().is_empty());
     }
 }
 
+fn verify_confirmed_kex_succeeds(
+    client: &mut client::OqsKexClient,
+    algorithms: &[OqsKexAlg],
+    server_channel: &mpsc::Receiver<(Metadata, ExchangedKeys)>,
+) {
+    let client_keys = client
+        .kex_with_confirmation(algorithms)
+        .expect("Error in client during confirmed exchange");
+    let (_meta, server_keys) = server_channel
+        .recv_timeout(Duration::from_secs(1))
+        .expect("Server did not output keys");
+
+    assert_eq!(client_keys.keys.len(), algorithms.len());
+    assert_eq!(client_keys, server_keys);
+}
+
+fn verify_default_capabilities(
+    client: &mut client::OqsKexClient,
+    _algorithms: &[OqsKexAlg],
+    _server_channel: &mpsc::Receiver<(Metadata, ExchangedKeys)>,
+) {
+    let capabilities = client
+        .capabilities()
+        .expect("Error in client fetching capabilities");
+    assert_eq!(capabilities, CONSTRAINTS_DEFAULT.capabilities());
+    assert_eq!(capabilities.algorithms, Some(ALGOS_DEFAULT.to_vec()));
+    assert_eq!(capabilities.max_algorithms, Some(ALGOS_DEFAULT.len()));
+    assert_eq!(capabilities.max_occurrences, Some(1));
+    assert_eq!(capabilities.max_request_size, None);
+}
+
+fn verify_negotiated_kex_selects_mlkem(
+    client: &mut client::OqsKexClient,
+    preferences: &[OqsKexAlg],
+    server_channel: &mpsc::Receiver<(Metadata, ExchangedKeys)>,
+) {
+    verify_negotiated_kex_selects(client, preferences, server_channel, &[OqsKexAlg::MlKem768])
+}
+
+fn verify_negotiated_kex_selects_all(
+    client: &mut client::OqsKexClient,
+    preferences: &[OqsKexAlg],
+    server_channel: &mpsc::Receiver<(Metadata, ExchangedKeys)>,
+) {
+    verify_negotiated_kex_selects(client, preferences, server_channel, preferences)
+}
+
+fn verify_negotiated_kex_selects(
+    client: &mut client::OqsKexClient,
+    preferences: &[OqsKexAlg],
+    server_channel: &mpsc::Receiver<(Metadata, ExchangedKeys)>,
+    expected: &[OqsKexAlg],
+) {
+    let client_keys = client
+        .kex_negotiated(preferences, preferences.len())
+        .expect("Error in client during negotiated exchange");
+    let (_meta, server_keys) = server_channel
+        .recv_timeout(Duration::from_secs(1))
+        .expect("Server did not output keys");
+
+    let algorithms: Vec<OqsKexAlg> = client_keys.keys.iter().map(|key| key.algorithm()).collect();
+    assert_eq!(algorithms, expected);
+    assert_eq!(client_keys, server_keys);
+}
+
+fn verify_negotiated_kex_fails(
+    client: &mut client::OqsKexClient,
+    preferences: &[OqsKexAlg],
+    _server_channel: &mpsc::Receiver<(Metadata, ExchangedKeys)>,
+) {
+    match client.kex_negotiated(preferences, preferences.len()) {
+        Err(client::Error(client::ErrorKind::NegotiationError, _)) => (),
+        Err(e) => panic!("Negotiation failed with unexpected error: {}", e),
+        Ok(_) => panic!("An expected failure in negotiation did NOT occur"),
+    }
+}
+
 fn verify_kex_fails(
     client: &mut client::OqsKexClient,
     algorithms: &[OqsKexAlg],
-    _server_channel: &mpsc::Receiver<(Metadata, Vec<SharedKey>)>,
+    _server_channel: &mpsc::Receiver<(Metadata, ExchangedKeys)>,
 ) {
     assert!(
         client.kex(algorithms).is_err(),
@@ -173,6 +654,26 @@ fn verify_kex_fails(
     );
 }
 
+fn verify_kex_violates(
+    client: &mut client::OqsKexClient,
+    algorithms: &[OqsKexAlg],
+    expected: ConstraintViolation,
+) {
+    match client.kex(algorithms) {
+        Err(client::Error(client::ErrorKind::ConstraintError(violation), _)) => {
+            assert_eq!(violation, expected)
+        }
+        Err(e) => panic!("Exchange failed with unexpected error: {}", e),
+        Ok(_) => panic!("An expected constraint violation did NOT occur"),
+    }
+}
+
+fn generate_server_keys() -> (PublicKey, SecretKey) {
+    let rand = OqsRand::new(OqsRandAlg::default()).unwrap();
+    let sig = OqsSig::new(&rand, OqsSigAlg::default()).unwrap();
+    sig.keypair().unwrap()
+}
+
 fn meta_extractor(request: &oqs_kex_rpc::server::Request) -> Metadata {
     Metadata {
         remote_addr: request.remote_addr().unwrap(),
@@ -0,0 +1,106 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+extern crate oqs_kex_rpc;
+
+use oqs_kex_rpc::{client, server, ConstraintViolation, ExchangedKeys, OqsKexAlg};
+
+use std::sync::{mpsc, Mutex};
+use std::time::Duration;
+
+static ALGOS: &[OqsKexAlg] = &[OqsKexAlg::MlKem768, OqsKexAlg::FrodoKem640Aes];
+
+fn loopback_server(
+    constraints: server::ServerConstraints,
+) -> (server::LoopbackServer<Metadata>, mpsc::Receiver<(Metadata, ExchangedKeys)>) {
+    let (tx, rx) = mpsc::channel();
+    let tx = Mutex::new(tx);
+    let on_kex = move |meta: Metadata, keys| {
+        tx.lock().unwrap().send((meta, keys)).unwrap();
+        Ok(()) as Result<(), ::std::io::Error>
+    };
+    (server::LoopbackServer::new(on_kex, constraints).unwrap(), rx)
+}
+
+fn loopback_client(
+    server: &server::LoopbackServer<Metadata>,
+    client_id: u32,
+) -> client::OqsKexClient<client::LoopbackTransport<Metadata>> {
+    let transport = client::LoopbackTransport::new(server, Metadata { client_id });
+    client::OqsKexClient::with_transport(transport)
+}
+
+#[test]
+fn test_loopback_request() {
+    let (server, server_channel) = loopback_server(server::ServerConstraints::default());
+    let mut client = loopback_client(&server, 1);
+
+    let client_keys = client.kex(ALGOS).expect("Error in client during exchange");
+    let (meta, server_keys) = server_channel
+        .recv_timeout(Duration::from_secs(1))
+        .expect("Server did not output keys");
+    assert_eq!(client_keys, server_keys);
+    assert_eq!(meta.client_id, 1);
+}
+
+#[test]
+fn test_loopback_metadata_per_transport() {
+    let (server, server_channel) = loopback_server(server::ServerConstraints::default());
+    let mut client1 = loopback_client(&server, 1);
+    let mut client2 = loopback_client(&server, 2);
+
+    client2.kex(ALGOS).unwrap();
+    client1.kex(ALGOS).unwrap();
+    let client_ids: Vec<u32> = server_channel
+        .try_iter()
+        .map(|(meta, _keys)| meta.client_id)
+        .collect();
+    assert_eq!(client_ids, [2, 1]);
+}
+
+#[test]
+fn test_loopback_confirmed_request() {
+    let (server, server_channel) = loopback_server(server::ServerConstraints::default());
+    let mut client = loopback_client(&server, 1);
+
+    let client_keys = client
+        .kex_with_confirmation(ALGOS)
+        .expect("Error in client during confirmed exchange");
+    let (_meta, server_keys) = server_channel
+        .recv_timeout(Duration::from_secs(1))
+        .expect("Server did not output keys");
+    assert_eq!(client_keys, server_keys);
+}
+
+#[test]
+fn test_loopback_constraint_violation() {
+    let constraints = server::ServerConstraints {
+        algorithms: Some(vec![OqsKexAlg::MlKem768]),
+        ..server::ServerConstraints::default()
+    };
+    let (server, server_channel) = loopback_server(constraints);
+    let mut client = loopback_client(&server, 1);
+
+    match client.kex(ALGOS) {
+        Err(client::Error(client::ErrorKind::ConstraintError(violation), _)) => assert_eq!(
+            violation,
+            ConstraintViolation::DisallowedAlgorithm(OqsKexAlg::FrodoKem640Aes)
+        ),
+        Err(e) => panic!("Exchange failed with unexpected error: {}", e),
+        Ok(_) => panic!("An expected constraint violation did NOT occur"),
+    }
+    assert!(server_channel.try_recv().is_err());
+}
+
+
+#[derive(Debug, Clone, Default)]
+struct Metadata {
+    client_id: u32,
+}
+
+impl server::Metadata for Metadata {}
@@ -0,0 +1,144 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+extern crate oqs_kex_rpc;
+
+use oqs_kex_rpc::{client, server, AlgorithmCosts, ExchangedKeys, OqsKexAlg};
+
+use std::net::{SocketAddr, TcpListener};
+use std::str::FromStr;
+use std::sync::{mpsc, Mutex};
+use std::time::{Duration, Instant};
+
+static ALGOS: &[OqsKexAlg] = &[OqsKexAlg::MlKem768];
+
+fn start_server(
+    constraints: server::ServerConstraints,
+) -> (server::Server, mpsc::Receiver<(Metadata, ExchangedKeys)>) {
+    let (tx, rx) = mpsc::channel();
+    let tx = Mutex::new(tx);
+    let on_kex = move |meta: Metadata, keys| {
+        tx.lock().unwrap().send((meta, keys)).unwrap();
+        Ok(()) as Result<(), ::std::io::Error>
+    };
+    let addr = SocketAddr::from_str("127.0.0.1:0").unwrap();
+
+    let server = server::start(addr, meta_extractor, on_kex, constraints).unwrap();
+    (server, rx)
+}
+
+/// Allows one exchange with `ALGOS` per second.
+fn rate_limited_constraints() -> server::ServerConstraints {
+    let mut costs = AlgorithmCosts::new();
+    costs.set_cost(OqsKexAlg::MlKem768, 3);
+    server::ServerConstraints {
+        algorithm_costs: costs,
+        rate_limit: Some(server::RateLimit::new(3, 3)),
+        ..server::ServerConstraints::default()
+    }
+}
+
+#[test]
+fn test_request_timeout() {
+    // Accepts connections, but never responds to anything sent over them.
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let uri = format!("http://{}", listener.local_addr().unwrap());
+    let mut client = client::OqsKexClient::builder()
+        .request_timeout(Duration::from_millis(200))
+        .build(&uri)
+        .unwrap();
+
+    let start = Instant::now();
+    match client.kex(ALGOS) {
+        Err(client::Error(client::ErrorKind::RpcError, _)) => (),
+        Err(e) => panic!("Exchange failed with unexpected error: {}", e),
+        Ok(_) => panic!("Exchange with an unresponsive server succeeded"),
+    }
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn test_timeouts_with_responsive_server() {
+    let (server, server_channel) = start_server(server::ServerConstraints::default());
+    let uri = format!("http://{}", server.address());
+    let mut client = client::OqsKexClient::builder()
+        .connect_timeout(Duration::from_secs(5))
+        .request_timeout(Duration::from_secs(5))
+        .build(&uri)
+        .unwrap();
+
+    let client_keys = client.kex(ALGOS).expect("Error in client during exchange");
+    let (_meta, server_keys) = server_channel
+        .recv_timeout(Duration::from_secs(1))
+        .expect("Server did not output keys");
+    assert_eq!(client_keys, server_keys);
+}
+
+#[test]
+fn test_retry_rate_limited() {
+    let (server, server_channel) = start_server(rate_limited_constraints());
+    let uri = format!("http://{}", server.address());
+    let mut client = client::OqsKexClient::builder()
+        .retry_policy(client::RetryPolicy::new(4, Duration::from_millis(400)))
+        .build(&uri)
+        .unwrap();
+
+    // The second exchange is rate limited until the bucket refills, and succeeds when retried.
+    for _ in 0..2 {
+        let client_keys = client.kex(ALGOS).expect("Error in client during exchange");
+        let (_meta, server_keys) = server_channel
+            .recv_timeout(Duration::from_secs(1))
+            .expect("Server did not output keys");
+        assert_eq!(client_keys, server_keys);
+    }
+}
+
+#[test]
+fn test_no_retry_by_default() {
+    let (server, _server_channel) = start_server(rate_limited_constraints());
+    let mut client = client::OqsKexClient::new(&format!("http://{}", server.address())).unwrap();
+
+    client.kex(ALGOS).expect("Error in client during exchange");
+    match client.kex(ALGOS) {
+        Err(client::Error(client::ErrorKind::RateLimited, _)) => (),
+        Err(e) => panic!("Exchange failed with unexpected error: {}", e),
+        Ok(_) => panic!("An expected rate limit failure did NOT occur"),
+    }
+}
+
+#[test]
+fn test_no_retry_of_constraint_violation() {
+    let constraints = server::ServerConstraints {
+        max_algorithms: Some(1),
+        ..server::ServerConstraints::default()
+    };
+    let (server, _server_channel) = start_server(constraints);
+    let uri = format!("http://{}", server.address());
+    let mut client = client::OqsKexClient::builder()
+        .retry_policy(client::RetryPolicy::new(3, Duration::from_secs(10)))
+        .build(&uri)
+        .unwrap();
+
+    let start = Instant::now();
+    match client.kex(&[OqsKexAlg::MlKem768, OqsKexAlg::MlKem512]) {
+        Err(client::Error(client::ErrorKind::ConstraintError(_), _)) => (),
+        Err(e) => panic!("Exchange failed with unexpected error: {}", e),
+        Ok(_) => panic!("An expected constraint violation did NOT occur"),
+    }
+    assert!(start.elapsed() < Duration::from_secs(10));
+}
+
+
+fn meta_extractor(_request: &server::Request) -> Metadata {
+    Metadata
+}
+
+#[derive(Debug, Clone, Default)]
+struct Metadata;
+
+impl server::Metadata for Metadata {}
@@ -0,0 +1,139 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+#![cfg(feature = "tls")]
+
+extern crate oqs_kex_rpc;
+
+use oqs_kex_rpc::{client, server, tls, ExchangedKeys, OqsKexAlg};
+
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::{mpsc, Mutex};
+use std::time::Duration;
+
+static ALGOS: &[OqsKexAlg] = &[OqsKexAlg::MlKem768, OqsKexAlg::FrodoKem640Aes];
+
+fn certificates(name: &str) -> Vec<tls::Certificate> {
+    tls::load_certificates(test_file(&format!("{}.pem", name))).unwrap()
+}
+
+fn private_key(name: &str) -> tls::PrivateKey {
+    tls::load_private_key(test_file(&format!("{}.key", name))).unwrap()
+}
+
+fn test_file(name: &str) -> String {
+    format!("{}/tests/tls/{}", env!("CARGO_MANIFEST_DIR"), name)
+}
+
+fn server_tls_config() -> tls::ServerTlsConfig {
+    tls::ServerTlsConfig::new(certificates("server"), private_key("server"))
+}
+
+fn mutual_server_tls_config() -> tls::ServerTlsConfig {
+    tls::ServerTlsConfig::with_client_auth(
+        certificates("server"),
+        private_key("server"),
+        certificates("ca"),
+    )
+}
+
+fn start_server(
+    tls_config: tls::ServerTlsConfig,
+) -> (server::Server, mpsc::Receiver<(Metadata, ExchangedKeys)>) {
+    let local_addr = SocketAddr::from_str("127.0.0.1:0").unwrap();
+
+    let (tx, rx) = mpsc::channel();
+    let tx = Mutex::new(tx);
+    let on_kex = move |meta: Metadata, keys| {
+        tx.lock().unwrap().send((meta, keys)).unwrap();
+        Ok(()) as Result<(), ::std::io::Error>
+    };
+    let constraints = server::ServerConstraints {
+        tls: Some(tls_config),
+        ..server::ServerConstraints::default()
+    };
+
+    let server = server::start(local_addr, meta_extractor, on_kex, constraints).unwrap();
+    (server, rx)
+}
+
+fn https_addr(server: &server::Server) -> String {
+    format!("https://localhost:{}", server.address().tcp().unwrap().port())
+}
+
+#[test]
+fn test_tls_request() {
+    let (server, server_channel) = start_server(server_tls_config());
+    let tls_config = tls::ClientTlsConfig::new(&certificates("ca")).unwrap();
+    let mut client = client::OqsKexClient::with_tls(&https_addr(&server), &tls_config).unwrap();
+
+    let (meta, server_keys) = verify_kex_succeeds(&mut client, &server_channel);
+    assert_eq!(meta.peer_certificate, None);
+    assert_eq!(server_keys.keys.len(), ALGOS.len());
+}
+
+#[test]
+fn test_tls_client_rejects_server_signed_by_other_ca() {
+    let (server, server_channel) = start_server(server_tls_config());
+    let tls_config = tls::ClientTlsConfig::new(&certificates("other-ca")).unwrap();
+    let mut client = client::OqsKexClient::with_tls(&https_addr(&server), &tls_config).unwrap();
+
+    assert!(client.kex(ALGOS).is_err(), "Client accepted an untrusted server");
+    assert!(server_channel.try_recv().is_err());
+}
+
+#[test]
+fn test_mutual_tls_peer_certificate_available_to_meta_extractor() {
+    let (server, server_channel) = start_server(mutual_server_tls_config());
+    let tls_config = tls::ClientTlsConfig::with_client_certificate(
+        &certificates("ca"),
+        certificates("client"),
+        private_key("client"),
+    ).unwrap();
+    let mut client = client::OqsKexClient::with_tls(&https_addr(&server), &tls_config).unwrap();
+
+    let (meta, _server_keys) = verify_kex_succeeds(&mut client, &server_channel);
+    assert_eq!(meta.peer_certificate, Some(certificates("client")[0].clone()));
+}
+
+#[test]
+fn test_mutual_tls_rejects_client_without_certificate() {
+    let (server, server_channel) = start_server(mutual_server_tls_config());
+    let tls_config = tls::ClientTlsConfig::new(&certificates("ca")).unwrap();
+    let mut client = client::OqsKexClient::with_tls(&https_addr(&server), &tls_config).unwrap();
+
+    assert!(client.kex(ALGOS).is_err(), "Server accepted a client without certificate");
+    assert!(server_channel.try_recv().is_err());
+}
+
+
+fn verify_kex_succeeds(
+    client: &mut client::OqsKexClient,
+    server_channel: &mpsc::Receiver<(Metadata, ExchangedKeys)>,
+) -> (Metadata, ExchangedKeys) {
+    let client_keys = client.kex(ALGOS).expect("Error in client during exchange");
+    let (meta, server_keys) = server_channel
+        .recv_timeout(Duration::from_secs(1))
+        .expect("Server did not output keys");
+    assert_eq!(client_keys, server_keys);
+    (meta, server_keys)
+}
+
+fn meta_extractor(request: &server::Request) -> Metadata {
+    Metadata {
+        peer_certificate: tls::peer_certificate(request),
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Metadata {
+    peer_certificate: Option<tls::Certificate>,
+}
+
+impl server::Metadata for Metadata {}
@@ -0,0 +1,51 @@
+#!/usr/bin/env bash
+
+# Generates the certificates used by the TLS tests in `localhost.rs`. A test CA signing a server
+# certificate for `localhost` and a client certificate, and another CA signing nothing.
+
+set -e
+
+cd "$(dirname "$0")"
+tmp=$(mktemp -d)
+trap 'rm -rf "$tmp"' EXIT
+
+# RSA keys in PKCS#1 format, since that is what older rustls versions can load.
+generate_key() {
+    openssl genpkey -algorithm RSA -pkeyopt rsa_keygen_bits:2048 | openssl rsa -traditional -out "$1"
+}
+
+cat > "$tmp/ca.ext" <<EXT
+basicConstraints = critical, CA:TRUE
+keyUsage = critical, keyCertSign, cRLSign
+EXT
+cat > "$tmp/server.ext" <<EXT
+basicConstraints = critical, CA:FALSE
+keyUsage = critical, digitalSignature
+extendedKeyUsage = serverAuth
+subjectAltName = DNS:localhost
+EXT
+cat > "$tmp/client.ext" <<EXT
+basicConstraints = critical, CA:FALSE
+keyUsage = critical, digitalSignature
+extendedKeyUsage = clientAuth
+EXT
+
+for ca in ca other-ca; do
+    generate_key "$tmp/$ca.key"
+    openssl req -new -key "$tmp/$ca.key" -subj "/CN=oqs-kex-rpc test $ca" -out "$tmp/$ca.csr"
+    openssl x509 -req -in "$tmp/$ca.csr" -signkey "$tmp/$ca.key" -days 36500 -sha256 \
+        -extfile "$tmp/ca.ext" -out "$ca.pem"
+done
+
+serial=2
+for leaf in server client; do
+    subject="/CN=oqs-kex-rpc test client"
+    if [ "$leaf" = "server" ]; then
+        subject="/CN=localhost"
+    fi
+    generate_key "$leaf.key"
+    openssl req -new -key "$leaf.key" -subj "$subject" -out "$tmp/$leaf.csr"
+    openssl x509 -req -in "$tmp/$leaf.csr" -CA ca.pem -CAkey "$tmp/ca.key" -set_serial $serial \
+        -days 36500 -sha256 -extfile "$tmp/$leaf.ext" -out "$leaf.pem"
+    serial=$((serial + 1))
+done
@@ -0,0 +1,113 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+#![cfg(unix)]
+
+extern crate oqs_kex_rpc;
+
+use oqs_kex_rpc::{client, server, unix, ExchangedKeys, OqsKexAlg};
+
+use std::env;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::PathBuf;
+use std::process;
+use std::sync::{mpsc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+use std::time::Duration;
+
+static ALGOS: &[OqsKexAlg] = &[OqsKexAlg::MlKem768, OqsKexAlg::FrodoKem640Aes];
+
+static SOCKET_COUNTER: AtomicUsize = ATOMIC_USIZE_INIT;
+
+/// Returns a path in the temp dir that no other test uses.
+fn socket_path() -> PathBuf {
+    let index = SOCKET_COUNTER.fetch_add(1, Ordering::SeqCst);
+    env::temp_dir().join(format!("oqs-kex-rpc-test-{}-{}.sock", process::id(), index))
+}
+
+fn start_server(path: PathBuf) -> (server::Server, mpsc::Receiver<(Metadata, ExchangedKeys)>) {
+    let (tx, rx) = mpsc::channel();
+    let tx = Mutex::new(tx);
+    let on_kex = move |meta: Metadata, keys| {
+        tx.lock().unwrap().send((meta, keys)).unwrap();
+        Ok(()) as Result<(), ::std::io::Error>
+    };
+    let constraints = server::ServerConstraints::default();
+
+    let server = server::start(path, meta_extractor, on_kex, constraints).unwrap();
+    (server, rx)
+}
+
+#[test]
+fn test_unix_socket_request() {
+    let path = socket_path();
+    let (server, server_channel) = start_server(path.clone());
+    assert_eq!(server.address().unix(), Some(path.as_path()));
+    let mut client = client::OqsKexClient::with_unix_socket(&path);
+
+    let (meta, server_keys) = verify_kex_succeeds(&mut client, &server_channel);
+    assert_eq!(server_keys.keys.len(), ALGOS.len());
+
+    let credentials = meta.peer_credentials.expect("No peer credentials");
+    let socket_metadata = fs::metadata(&path).unwrap();
+    assert_eq!(credentials.uid, socket_metadata.uid());
+    if cfg!(any(target_os = "linux", target_os = "android")) {
+        assert_eq!(credentials.pid, Some(process::id()));
+    }
+}
+
+#[test]
+fn test_unix_socket_removed_on_close() {
+    let path = socket_path();
+    let (server, _server_channel) = start_server(path.clone());
+    assert!(path.exists());
+    server.close();
+    assert!(!path.exists());
+}
+
+#[test]
+fn test_unix_socket_in_use() {
+    let path = socket_path();
+    let (_server, _server_channel) = start_server(path.clone());
+    let on_kex = |_meta: Metadata, _keys| Ok(()) as Result<(), ::std::io::Error>;
+    let constraints = server::ServerConstraints::default();
+    assert!(server::start(path, meta_extractor, on_kex, constraints).is_err());
+}
+
+#[test]
+fn test_unix_socket_no_server() {
+    let mut client = client::OqsKexClient::with_unix_socket(socket_path());
+    assert!(client.kex(ALGOS).is_err());
+}
+
+
+fn verify_kex_succeeds(
+    client: &mut client::OqsKexClient,
+    server_channel: &mpsc::Receiver<(Metadata, ExchangedKeys)>,
+) -> (Metadata, ExchangedKeys) {
+    let client_keys = client.kex(ALGOS).expect("Error in client during exchange");
+    let (meta, server_keys) = server_channel
+        .recv_timeout(Duration::from_secs(1))
+        .expect("Server did not output keys");
+    assert_eq!(client_keys, server_keys);
+    (meta, server_keys)
+}
+
+fn meta_extractor(request: &server::Request) -> Metadata {
+    Metadata {
+        peer_credentials: unix::peer_credentials(request),
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Metadata {
+    peer_credentials: Option<unix::PeerCredentials>,
+}
+
+impl server::Metadata for Metadata {}
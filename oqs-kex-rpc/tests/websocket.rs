@@ -0,0 +1,151 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+extern crate oqs_kex_rpc;
+
+use oqs_kex_rpc::{client, server, websocket, ConstraintViolation, ExchangedKeys, OqsKexAlg};
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::str::FromStr;
+use std::sync::{mpsc, Mutex};
+use std::time::Duration;
+
+static ALGOS: &[OqsKexAlg] = &[OqsKexAlg::MlKem768, OqsKexAlg::FrodoKem640Aes];
+
+fn start_server(
+    constraints: server::ServerConstraints,
+) -> (server::Server, mpsc::Receiver<(Metadata, ExchangedKeys)>) {
+    let (tx, rx) = mpsc::channel();
+    let tx = Mutex::new(tx);
+    let on_kex = move |meta: Metadata, keys| {
+        tx.lock().unwrap().send((meta, keys)).unwrap();
+        Ok(()) as Result<(), ::std::io::Error>
+    };
+    let addr = SocketAddr::from_str("127.0.0.1:0").unwrap();
+
+    let server = websocket::start(addr, meta_extractor, on_kex, constraints).unwrap();
+    (server, rx)
+}
+
+fn connect(
+    server: &server::Server,
+    path: &str,
+) -> client::OqsKexClient<client::WebSocketTransport> {
+    let uri = format!("ws://{}{}", server.address().tcp().unwrap(), path);
+    client::OqsKexClient::with_websocket(&uri).unwrap()
+}
+
+#[test]
+fn test_websocket_request() {
+    let (server, server_channel) = start_server(server::ServerConstraints::default());
+    let mut client = connect(&server, "/kex");
+
+    let client_keys = client.kex(ALGOS).expect("Error in client during exchange");
+    let (meta, server_keys) = server_channel
+        .recv_timeout(Duration::from_secs(1))
+        .expect("Server did not output keys");
+    assert_eq!(client_keys, server_keys);
+    assert_eq!(meta.path, "/kex");
+}
+
+#[test]
+fn test_websocket_several_requests_per_connection() {
+    let (server, server_channel) = start_server(server::ServerConstraints::default());
+    let mut client = connect(&server, "/");
+
+    for _ in 0..3 {
+        let client_keys = client.kex(ALGOS).expect("Error in client during exchange");
+        let (_meta, server_keys) = server_channel
+            .recv_timeout(Duration::from_secs(1))
+            .expect("Server did not output keys");
+        assert_eq!(client_keys, server_keys);
+    }
+}
+
+#[test]
+fn test_websocket_confirmed_request() {
+    let (server, server_channel) = start_server(server::ServerConstraints::default());
+    let mut client = connect(&server, "/");
+
+    let client_keys = client
+        .kex_with_confirmation(ALGOS)
+        .expect("Error in client during confirmed exchange");
+    let (_meta, server_keys) = server_channel
+        .recv_timeout(Duration::from_secs(1))
+        .expect("Server did not output keys");
+    assert_eq!(client_keys, server_keys);
+}
+
+#[test]
+fn test_websocket_constraint_violation() {
+    let constraints = server::ServerConstraints {
+        max_algorithms: Some(1),
+        ..server::ServerConstraints::default()
+    };
+    let (server, server_channel) = start_server(constraints);
+    let mut client = connect(&server, "/");
+
+    match client.kex(ALGOS) {
+        Err(client::Error(client::ErrorKind::ConstraintError(violation), _)) => assert_eq!(
+            violation,
+            ConstraintViolation::TooManyAlgorithms { count: 2, max: 1 }
+        ),
+        Err(e) => panic!("Exchange failed with unexpected error: {}", e),
+        Ok(_) => panic!("An expected constraint violation did NOT occur"),
+    }
+    assert!(server_channel.try_recv().is_err());
+
+    // The connection is still usable after an error.
+    client.kex(&ALGOS[..1]).expect("Error in client during exchange");
+}
+
+#[test]
+fn test_websocket_plain_http_request() {
+    let (server, _server_channel) = start_server(server::ServerConstraints::default());
+    let mut stream = TcpStream::connect(server.address().tcp().unwrap()).unwrap();
+
+    stream
+        .write_all(b"POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length: 0\r\n\r\n")
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 400 "));
+}
+
+#[test]
+fn test_websocket_message_too_large() {
+    let constraints = server::ServerConstraints {
+        max_request_size: Some(1024),
+        ..server::ServerConstraints::default()
+    };
+    let (server, server_channel) = start_server(constraints);
+    let mut client = connect(&server, "/");
+
+    // A single ML-KEM-768 public key is larger than 1024 bytes.
+    match client.kex(&[OqsKexAlg::MlKem768]) {
+        Err(client::Error(client::ErrorKind::RpcError, _)) => (),
+        Err(e) => panic!("Exchange failed with unexpected error: {}", e),
+        Ok(_) => panic!("An oversized message was accepted"),
+    }
+    assert!(server_channel.try_recv().is_err());
+}
+
+
+fn meta_extractor(request: &server::Request) -> Metadata {
+    Metadata {
+        path: request.uri().path().to_owned(),
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Metadata {
+    path: String,
+}
+
+impl server::Metadata for Metadata {}
@@ -4,36 +4,24 @@ set -e
 
 cd liboqs
 
-# Enabled algorithms for liboqs
-enabled_algorithms=(
-    --enable-aes-ni
-    --enable-kex-lwe-frodo
-    --enable-kex-mlwe-kyber
-    --enable-kex-ntru
-    --enable-kex-rlwe-msrln16
-    --enable-kex-rlwe-newhope
-    --enable-kex-sidh-cln16
-)
-
-if [[ $OQS_WITH_SODIUM -eq 1 ]]; then
-    echo "Building with libsodium"
-    enabled_algorithms+=(
-        --enable-kex-code-mcbits
-    )
-fi
-
-if [[ $OQS_WITH_GMP -eq 1 ]]; then
-    echo "Building with libgmp"
-    enabled_algorithms+=(
-        --enable-kex-sidh-iqc-ref
-    )
+# All algorithms are enabled by default in liboqs. The ones exposed by the oqs crate are ML-KEM,
+# FrodoKEM, Classic McEliece and HQC.
+
+if [[ $OQS_WITH_OPENSSL -eq 1 ]]; then
+    echo "Building with OpenSSL"
+    use_openssl=ON
+else
+    use_openssl=OFF
 fi
 
-autoreconf -i
-
 # Building with -fPIC is needed for linking with Rust
 
-./configure AM_CPPFLAGS="-fPIC" "${enabled_algorithms[@]}"
+rm -rf build
+cmake -S . -B build \
+    -DCMAKE_BUILD_TYPE=Release \
+    -DCMAKE_POSITION_INDEPENDENT_CODE=ON \
+    -DBUILD_SHARED_LIBS=OFF \
+    -DOQS_BUILD_ONLY_LIB=ON \
+    -DOQS_USE_OPENSSL=$use_openssl
 
-make clean
-make
+cmake --build build
@@ -17,36 +17,50 @@ fn main() {
     let oqs_dir = env::var("OQS_DIR")
         .map(PathBuf::from)
         .expect("Set the environment variable OQS_DIR to the absolute path to your liboqs dir");
-    let oqs_include_dir = oqs_dir.join("include");
+    let oqs_build_dir = oqs_dir.join("build");
+    let oqs_include_dir = oqs_build_dir.join("include");
 
     println!("cargo:rustc-link-lib=oqs");
 
-    if option_env!("OQS_WITH_SODIUM") == Some("1") {
-        println!("cargo:rustc-link-lib=sodium");
-    }
-
-    if option_env!("OQS_WITH_GMP") == Some("1")  {
-        println!("cargo:rustc-link-lib=gmp");
+    if option_env!("OQS_WITH_OPENSSL") == Some("1") {
+        println!("cargo:rustc-link-lib=crypto");
     }
 
     println!(
         "cargo:rustc-link-search=native={}",
-        oqs_dir.to_string_lossy()
+        oqs_build_dir.join("lib").to_string_lossy()
     );
 
     let _ = bindgen::builder()
-        .header(format!("{}/oqs/kex.h", oqs_include_dir.to_string_lossy()))
+        .header(format!("{}/oqs/kem.h", oqs_include_dir.to_string_lossy()))
         .clang_arg(format!("-I{}", oqs_include_dir.to_string_lossy()))
         .link_static("oqs")
         .use_core()
         .ctypes_prefix("::libc")
         .whitelist_recursively(false)
-        .whitelisted_type("OQS_KEX.*")
-        .whitelisted_function("OQS_KEX_.*")
-        .raw_line("use ::rand::OQS_RAND;")
+        .whitelisted_type("OQS_KEM")
+        .whitelisted_function("OQS_KEM_(new|free|keypair|encaps|decaps|alg_is_enabled)")
+        .whitelisted_var("OQS_KEM_alg_.*")
+        .raw_line("use ::common::OQS_STATUS;")
         .generate()
         .unwrap()
-        .write_to_file(out_dir.join("kex.rs"))
+        .write_to_file(out_dir.join("kem.rs"))
+        .unwrap();
+
+    let _ = bindgen::builder()
+        .header(format!("{}/oqs/sig.h", oqs_include_dir.to_string_lossy()))
+        .clang_arg(format!("-I{}", oqs_include_dir.to_string_lossy()))
+        .link_static("oqs")
+        .use_core()
+        .ctypes_prefix("::libc")
+        .whitelist_recursively(false)
+        .whitelisted_type("OQS_SIG")
+        .whitelisted_function("OQS_SIG_(new|free|keypair|sign|verify|alg_is_enabled)")
+        .whitelisted_var("OQS_SIG_alg_.*")
+        .raw_line("use ::common::OQS_STATUS;")
+        .generate()
+        .unwrap()
+        .write_to_file(out_dir.join("sig.rs"))
         .unwrap();
 
     let _ = bindgen::builder()
@@ -55,8 +69,10 @@ fn main() {
         .link_static("oqs")
         .use_core()
         .ctypes_prefix("::libc")
-        .whitelisted_type("OQS_RAND.*")
-        .whitelisted_function("OQS_RAND_.*")
+        .whitelist_recursively(false)
+        .whitelisted_function("OQS_randombytes.*")
+        .whitelisted_var("OQS_RAND_alg_.*")
+        .raw_line("use ::common::OQS_STATUS;")
         .generate()
         .unwrap()
         .write_to_file(out_dir.join("rand.rs"))
@@ -72,8 +88,8 @@ fn main() {
         .use_core()
         .ctypes_prefix("::libc")
         .whitelist_recursively(false)
-        .whitelisted_var("OQS_.*")
-        .whitelisted_function("OQS_.*")
+        .whitelisted_type("OQS_STATUS")
+        .whitelisted_function("OQS_(MEM_.*|init|destroy|version)")
         .generate()
         .unwrap()
         .write_to_file(out_dir.join("common.rs"))
@@ -18,7 +18,8 @@
 //!
 //! To make the buildscript for `oqs-sys` find [liboqs], both the required headers and the compiled
 //! library (`liboqs.a`), you must set the environment variable `OQS_DIR` to the **absolute**
-//! path to your [liboqs] directory.
+//! path to your [liboqs] directory. The library is expected to have been built with CMake into
+//! the `build` directory inside `OQS_DIR`.
 //!
 //! ```bash
 //! export OQS_DIR=/absolute/path/to/liboqs
@@ -40,21 +41,32 @@
 
 extern crate libc;
 
-/// The key exchange part of liboqs.
+/// The key encapsulation mechanism (KEM) part of liboqs.
 #[allow(non_camel_case_types)]
 #[allow(non_snake_case)]
-pub mod kex {
-    include!(concat!(env!("OUT_DIR"), "/kex.rs"));
+#[allow(non_upper_case_globals)]
+pub mod kem {
+    include!(concat!(env!("OUT_DIR"), "/kem.rs"));
 }
 
-/// The PRNG part of liboqs.
+/// The digital signature part of liboqs.
 #[allow(non_camel_case_types)]
 #[allow(non_snake_case)]
+#[allow(non_upper_case_globals)]
+pub mod sig {
+    include!(concat!(env!("OUT_DIR"), "/sig.rs"));
+}
+
+/// The entropy source part of liboqs.
+#[allow(non_camel_case_types)]
+#[allow(non_snake_case)]
+#[allow(non_upper_case_globals)]
 pub mod rand {
     include!(concat!(env!("OUT_DIR"), "/rand.rs"));
 }
 
 /// Common shared functionality and constants.
+#[allow(non_camel_case_types)]
 pub mod common {
     include!(concat!(env!("OUT_DIR"), "/common.rs"));
 }
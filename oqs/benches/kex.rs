@@ -70,7 +70,8 @@ macro_rules! bench_full_kex {
 
 mod kex_benches {
     use super::*;
-    bench_kex!(RlweNewhope);
-    bench_kex!(CodeMcbits);
-    bench_kex!(SidhCln16);
+    bench_kex!(MlKem768);
+    bench_kex!(FrodoKem640Aes);
+    bench_kex!(ClassicMcEliece348864);
+    bench_kex!(Hqc128);
 }
@@ -49,6 +49,5 @@ macro_rules! bench_rand_i {
 mod rand_benches {
     use super::*;
     bench_rand!(Default);
-    bench_rand!(UrandomChacha20);
-    bench_rand!(UrandomAesctr);
+    bench_rand!(System);
 }
@@ -0,0 +1,226 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//!
+//! The key exchange operations of some algorithms, like Classic McEliece, take milliseconds of
+//! CPU time or more. Calling them directly from an event loop blocks it for that long. The
+//! [`OqsKexPool`] runs the operations on a dedicated thread pool instead and returns futures
+//! resolving to the results.
+//!
+//! The steps of the key exchange are the same as for [`OqsKex`], but Alice's intermediate state
+//! is an owned [`AliceState`], so it can be moved between threads and kept across network round
+//! trips.
+//!
+//! [`OqsKexPool`]: struct.OqsKexPool.html
+//! [`OqsKex`]: ../kex/struct.OqsKex.html
+//! [`AliceState`]: ../kex/struct.AliceState.html
+
+use futures::{future, Future};
+use futures_cpupool::{Builder, CpuFuture, CpuPool};
+
+use kex::{self, AliceMsg, AliceState, BobMsg, Error, OqsKex, OqsKexAlg, OqsKexAlice, SharedKey};
+use rand::{self, OqsRand, OqsRandAlg};
+
+use std::boxed::Box;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+
+/// The future returned by the key exchange operations on an
+/// [`OqsKexPool`](struct.OqsKexPool.html).
+pub type KexFuture<T> = Box<dyn Future<Item = T, Error = Error> + Send>;
+
+/// A thread pool performing key exchange operations. Cloning the pool gives a new handle to the
+/// same worker threads and queue.
+///
+/// The pool selects its entropy source once, when it is created, and all operations on it draw
+/// from that source. Since the selection is process global, see [`OqsRand`], it stays active for
+/// as long as any handle to the pool is alive.
+///
+/// The queue of operations waiting for a free worker thread is unbounded, unless limited with
+/// [`set_max_queued`]. Operations arriving while a bounded queue is full are rejected instead of
+/// queued.
+///
+/// [`OqsRand`]: ../rand/struct.OqsRand.html
+/// [`set_max_queued`]: #method.set_max_queued
+#[derive(Clone)]
+pub struct OqsKexPool {
+    pool: CpuPool,
+    rand: Arc<OqsRand>,
+    size: usize,
+    in_flight: Arc<AtomicUsize>,
+    max_in_flight: Option<usize>,
+}
+
+impl OqsKexPool {
+    /// Creates a new pool with `size` worker threads, drawing entropy from the default source.
+    /// Fails if another entropy source is selected, see [`OqsRand::new`].
+    ///
+    /// [`OqsRand::new`]: ../rand/struct.OqsRand.html#method.new
+    pub fn new(size: usize) -> rand::Result<Self> {
+        let rand = OqsRand::new(OqsRandAlg::default())?;
+        Ok(Self::with_rand(size, rand))
+    }
+
+    /// Creates a new pool with `size` worker threads, drawing entropy from `rand`. This also
+    /// accepts a source registered with [`OqsRand::from_fn`].
+    ///
+    /// [`OqsRand::from_fn`]: ../rand/struct.OqsRand.html#method.from_fn
+    pub fn with_rand(size: usize, rand: OqsRand) -> Self {
+        let pool = Builder::new()
+            .pool_size(size)
+            .name_prefix("oqs-kex-")
+            .create();
+        OqsKexPool {
+            pool,
+            rand: Arc::new(rand),
+            size,
+            in_flight: Arc::new(AtomicUsize::new(0)),
+            max_in_flight: None,
+        }
+    }
+
+    /// Lets at most `max_queued` operations wait for a free worker thread, or any number of them
+    /// if `None`. Applies to this handle and to handles cloned from it afterwards.
+    pub fn set_max_queued(&mut self, max_queued: Option<usize>) {
+        self.max_in_flight = max_queued.map(|max_queued| self.size + max_queued);
+    }
+
+    /// Returns the PRNG all operations on the pool draw their entropy from.
+    pub fn rand(&self) -> &OqsRand {
+        &self.rand
+    }
+
+    /// Runs `f` on the pool, giving it the pool's PRNG. For work beyond the single key exchange
+    /// steps below, like performing several exchanges and signing their transcript in one job.
+    ///
+    /// Returns `None`, without running `f`, if the queue is full.
+    pub fn spawn_fn<F, T, E>(&self, f: F) -> Option<CpuFuture<T, E>>
+    where
+        F: FnOnce(&OqsRand) -> Result<T, E> + Send + 'static,
+        T: Send + 'static,
+        E: Send + 'static,
+    {
+        let permit = self.acquire()?;
+        let rand = self.rand.clone();
+        Some(self.pool.spawn_fn(move || {
+            let _permit = permit;
+            f(&rand)
+        }))
+    }
+
+    /// Performs Alice's first step in the key exchange on the pool. See
+    /// [`OqsKex::alice_0`](../kex/struct.OqsKex.html#method.alice_0).
+    ///
+    /// Fails if the queue is full, see [`spawn_fn`](#method.spawn_fn) to tell that apart from a
+    /// failed key exchange.
+    pub fn alice_0(&self, algorithm: OqsKexAlg) -> KexFuture<AliceState> {
+        self.spawn_kex(move |rand| -> kex::Result<AliceState> {
+            let kex = OqsKex::new(rand, algorithm)?;
+            Ok(kex.alice_0()?.detach())
+        })
+    }
+
+    /// Performs Bob's step in the key exchange on the pool. See
+    /// [`OqsKex::bob`](../kex/struct.OqsKex.html#method.bob).
+    ///
+    /// Fails if the queue is full, like [`alice_0`](#method.alice_0).
+    pub fn bob(&self, alice_msg: AliceMsg) -> KexFuture<(BobMsg, SharedKey)> {
+        self.spawn_kex(move |rand| -> kex::Result<(BobMsg, SharedKey)> {
+            let kex = OqsKex::new(rand, alice_msg.algorithm())?;
+            kex.bob(&alice_msg)
+        })
+    }
+
+    /// Performs Alice's second, and last, step in the key exchange on the pool. See
+    /// [`OqsKexAlice::alice_1`](../kex/struct.OqsKexAlice.html#method.alice_1).
+    ///
+    /// Fails if the queue is full, like [`alice_0`](#method.alice_0).
+    pub fn alice_1(&self, alice: AliceState, bob_msg: BobMsg) -> KexFuture<SharedKey> {
+        self.spawn_kex(move |rand| -> kex::Result<SharedKey> {
+            let kex = OqsKex::new(rand, alice.algorithm())?;
+            OqsKexAlice::attach(&kex, alice)?.alice_1(&bob_msg)
+        })
+    }
+
+    fn spawn_kex<F, T>(&self, f: F) -> KexFuture<T>
+    where
+        F: FnOnce(&OqsRand) -> kex::Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        match self.spawn_fn(f) {
+            Some(future) => Box::new(future),
+            None => Box::new(future::err(Error)),
+        }
+    }
+
+    fn acquire(&self) -> Option<Permit> {
+        let in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst);
+        let permit = Permit(self.in_flight.clone());
+        match self.max_in_flight {
+            Some(max_in_flight) if in_flight >= max_in_flight => None,
+            _ => Some(permit),
+        }
+    }
+}
+
+/// An operation that is either running or waiting for a worker thread. Frees its place in the
+/// pool when dropped.
+struct Permit(Arc<AtomicUsize>);
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec::Vec;
+
+    #[test]
+    fn full_kex() {
+        let pool = OqsKexPool::new(2).unwrap();
+        let alice = pool.alice_0(OqsKexAlg::MlKem768).wait().unwrap();
+        let (bob_msg, key1) = pool.bob(alice.get_alice_msg().clone()).wait().unwrap();
+        let key2 = pool.alice_1(alice, bob_msg).wait().unwrap();
+
+        assert!(!key1.data().is_empty());
+        assert_eq!(key1, key2);
+    }
+
+    #[test]
+    fn rejects_when_full() {
+        let mut pool = OqsKexPool::new(1).unwrap();
+        pool.set_max_queued(Some(1));
+        let running = pool.acquire().unwrap();
+        let _queued = pool.acquire().unwrap();
+        assert!(pool.acquire().is_none());
+        assert!(pool.alice_0(OqsKexAlg::MlKem768).wait().is_err());
+        drop(running);
+        assert!(pool.acquire().is_some());
+    }
+
+    #[test]
+    fn unbounded_queue() {
+        let pool = OqsKexPool::new(1).unwrap();
+        let permits: Vec<Permit> = (0..100).map(|_| pool.acquire().unwrap()).collect();
+        assert_eq!(permits.len(), 100);
+    }
+
+    #[test]
+    fn finished_job_frees_place() {
+        let mut pool = OqsKexPool::new(1).unwrap();
+        pool.set_max_queued(Some(0));
+        for i in 0..3 {
+            let result = pool.spawn_fn(move |_| Ok::<_, ()>(i)).unwrap().wait();
+            assert_eq!(result, Ok(i));
+        }
+    }
+}
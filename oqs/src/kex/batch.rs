@@ -0,0 +1,201 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//!
+//! Exchanging keys with several algorithms at once is common, since it protects the shared
+//! secret as long as at least one of the algorithms is unbroken. The functions in this module
+//! perform the same step of the key exchange for many algorithms at the same time, spread over at
+//! most one thread per available CPU. With enough CPUs the total time is thus that of the slowest
+//! algorithm rather than the sum of all of them.
+//!
+//! All functions return one result per input item, in the same order as the input. One failing
+//! item does not affect the others.
+
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Mutex;
+use std::thread;
+use std::vec::Vec;
+
+use kex::{AliceMsg, AliceState, BobMsg, Error, OqsKex, OqsKexAlg, OqsKexAlice, Result, SharedKey};
+use rand::OqsRand;
+
+
+/// Performs Alice's first step in the key exchange for each of the `algorithms` in parallel.
+/// See [`OqsKex::alice_0`](../struct.OqsKex.html#method.alice_0).
+pub fn alice_0(rand: &OqsRand, algorithms: &[OqsKexAlg]) -> Vec<Result<AliceState>> {
+    parallel_map(algorithms.iter().cloned(), |algorithm| {
+        let kex = OqsKex::new(rand, algorithm)?;
+        Ok(kex.alice_0()?.detach())
+    })
+}
+
+/// Performs Bob's step in the key exchange for each of the `alice_msgs` in parallel. See
+/// [`OqsKex::bob`](../struct.OqsKex.html#method.bob).
+pub fn bob(rand: &OqsRand, alice_msgs: &[AliceMsg]) -> Vec<Result<(BobMsg, SharedKey)>> {
+    parallel_map(alice_msgs.iter(), |alice_msg| {
+        let kex = OqsKex::new(rand, alice_msg.algorithm())?;
+        kex.bob(alice_msg)
+    })
+}
+
+/// Performs Alice's second, and last, step in the key exchange for each of the `alices` in
+/// parallel. The Bob message at position `n` in `bob_msgs` is used with the Alice state at
+/// position `n` in `alices`. Alice states without a corresponding Bob message result in an
+/// error. See [`OqsKexAlice::alice_1`](../struct.OqsKexAlice.html#method.alice_1).
+pub fn alice_1(
+    rand: &OqsRand,
+    alices: Vec<AliceState>,
+    bob_msgs: &[BobMsg],
+) -> Vec<Result<SharedKey>> {
+    parallel_map(alices.into_iter().enumerate(), |(i, alice)| {
+        let bob_msg = bob_msgs.get(i).ok_or(Error)?;
+        let kex = OqsKex::new(rand, alice.algorithm())?;
+        OqsKexAlice::attach(&kex, alice)?.alice_1(bob_msg)
+    })
+}
+
+/// Runs `operation` on every item, on at most one thread per available CPU. Returns the results
+/// in the same order as the items. A panicking operation results in an error for that item.
+fn parallel_map<I, T, F>(items: I, operation: F) -> Vec<Result<T>>
+where
+    I: Iterator,
+    I::Item: Send,
+    T: Send,
+    F: Fn(I::Item) -> Result<T> + Sync,
+{
+    let items: Vec<I::Item> = items.collect();
+    let item_count = items.len();
+    let thread_count = thread::available_parallelism()
+        .map(|threads| threads.get())
+        .unwrap_or(1)
+        .min(item_count);
+    let queue = Mutex::new(items.into_iter().enumerate());
+
+    let operation = &operation;
+    let queue = &queue;
+    let finished: Vec<(usize, Result<T>)> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..thread_count)
+            .map(|_| {
+                scope.spawn(move || {
+                    let mut finished = Vec::new();
+                    loop {
+                        let next = queue.lock().unwrap_or_else(|e| e.into_inner()).next();
+                        let (i, item) = match next {
+                            Some(next) => next,
+                            None => break,
+                        };
+                        let result = panic::catch_unwind(AssertUnwindSafe(|| operation(item)))
+                            .unwrap_or(Err(Error));
+                        finished.push((i, result));
+                    }
+                    finished
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().unwrap_or_default())
+            .collect()
+    });
+
+    let mut results: Vec<Option<Result<T>>> = (0..item_count).map(|_| None).collect();
+    for (i, result) in finished {
+        results[i] = Some(result);
+    }
+    results
+        .into_iter()
+        .map(|result| result.unwrap_or(Err(Error)))
+        .collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use buf::Buf;
+    use rand::OqsRandAlg;
+
+    static ALGORITHMS: &[OqsKexAlg] = &[
+        OqsKexAlg::MlKem768,
+        OqsKexAlg::FrodoKem640Aes,
+        OqsKexAlg::Hqc128,
+        OqsKexAlg::MlKem512,
+    ];
+
+    #[test]
+    fn full_kex() {
+        let rand = OqsRand::new(OqsRandAlg::default()).unwrap();
+        let alices = alice_0(&rand, ALGORITHMS)
+            .into_iter()
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        let alice_msgs: Vec<AliceMsg> = alices.iter().map(|a| a.get_alice_msg().clone()).collect();
+
+        let (bob_msgs, keys1): (Vec<_>, Vec<_>) = bob(&rand, &alice_msgs)
+            .into_iter()
+            .collect::<Result<Vec<_>>>()
+            .unwrap()
+            .into_iter()
+            .unzip();
+        let keys2 = alice_1(&rand, alices, &bob_msgs)
+            .into_iter()
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+
+        assert_eq!(keys1, keys2);
+        for (key, algorithm) in keys1.iter().zip(ALGORITHMS) {
+            assert_eq!(key.algorithm(), *algorithm);
+        }
+    }
+
+    #[test]
+    fn per_item_errors() {
+        let rand = OqsRand::new(OqsRandAlg::default()).unwrap();
+        let valid_msg = OqsKex::new(&rand, OqsKexAlg::MlKem768)
+            .unwrap()
+            .alice_0()
+            .unwrap()
+            .get_alice_msg()
+            .clone();
+        let invalid_msg = AliceMsg::new(OqsKexAlg::MlKem768, Buf::zeroed(10));
+
+        let results = bob(&rand, &[invalid_msg, valid_msg]);
+        assert_eq!(results.len(), 2);
+        assert!(results[0].is_err());
+        assert!(results[1].is_ok());
+    }
+
+    #[test]
+    fn alice_1_missing_bob_msg() {
+        let rand = OqsRand::new(OqsRandAlg::default()).unwrap();
+        let alices = alice_0(&rand, &[OqsKexAlg::MlKem768]);
+        let alices = alices.into_iter().collect::<Result<Vec<_>>>().unwrap();
+        let results = alice_1(&rand, alices, &[]);
+        assert_eq!(results.len(), 1);
+        assert!(results[0].is_err());
+    }
+
+    #[test]
+    fn more_items_than_threads() {
+        let item_count = 4 * thread::available_parallelism().unwrap().get() + 1;
+        let results = parallel_map(0..item_count, |i| if i % 2 == 0 { Ok(i) } else { Err(Error) });
+        assert_eq!(results.len(), item_count);
+        for (i, result) in results.into_iter().enumerate() {
+            assert_eq!(result.ok(), if i % 2 == 0 { Some(i) } else { None });
+        }
+    }
+
+    #[test]
+    fn panic_is_per_item_error() {
+        let results = parallel_map(0..3, |i| if i == 1 { panic!("item {}", i) } else { Ok(i) });
+        assert_eq!(results[0].as_ref().ok(), Some(&0));
+        assert!(results[1].is_err());
+        assert_eq!(results[2].as_ref().ok(), Some(&2));
+    }
+}
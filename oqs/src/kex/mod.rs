@@ -0,0 +1,606 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//!
+//! This module has the types used to perform key exchange between two parties. These two parties
+//! are denoted Alice and Bob in [liboqs] so this library will use the same terminology. Out of
+//! these two parties, Alice is the one initiating a key exchange operation.
+//!
+//! The key exchange is backed by the key encapsulation mechanisms (KEM) in [liboqs]. Alice
+//! generates a key pair and sends the public key to Bob, Bob encapsulates a shared secret to that
+//! public key and sends back the ciphertext, which Alice then decapsulates.
+//!
+//! See the [`OqsKex`] struct for details on key exchange.
+//!
+//! [liboqs]: https://github.com/open-quantum-safe/liboqs
+//! [`OqsKex`]: struct.OqsKex.html
+
+use core::ptr;
+use core::fmt;
+
+use oqs_sys::kem as ffi;
+use oqs_sys::common::OQS_STATUS;
+use rand::{self, OqsRand};
+use buf::Buf;
+
+/// Helpers performing key exchange operations for several algorithms in parallel.
+#[cfg(feature = "std")]
+pub mod batch;
+
+
+/// Enum representation of the supported key exchange algorithms. Used to select backing algorithm
+/// when creating [`OqsKex`](struct.OqsKex.html) instances.
+///
+/// The number in each variant name is the parameter set as named in [liboqs]. Which of the
+/// algorithms are available depends on which ones liboqs was built with.
+///
+/// [liboqs]: https://github.com/open-quantum-safe/liboqs
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[allow(missing_docs)]
+pub enum OqsKexAlg {
+    /// ML-KEM, the module lattice based KEM standardized by NIST in FIPS 203. Formerly Kyber.
+    MlKem512,
+    MlKem768,
+    MlKem1024,
+    /// FrodoKEM, a conservative KEM based on plain LWE. Each parameter set comes in one variant
+    /// generating its public matrix with AES and one with SHAKE. The seed of the public matrix is
+    /// drawn from the active [`OqsRand`](../rand/struct.OqsRand.html) source for every key pair
+    /// and travels inside Alice's message, so no seed has to be agreed upon out of band.
+    FrodoKem640Aes,
+    FrodoKem640Shake,
+    FrodoKem976Aes,
+    FrodoKem976Shake,
+    FrodoKem1344Aes,
+    FrodoKem1344Shake,
+    /// Classic McEliece, a code based KEM. Has very large public keys and slow key generation.
+    ClassicMcEliece348864,
+    ClassicMcEliece460896,
+    ClassicMcEliece6688128,
+    ClassicMcEliece6960119,
+    ClassicMcEliece8192128,
+    /// HQC, a code based KEM selected for standardization by NIST.
+    Hqc128,
+    Hqc192,
+    Hqc256,
+}
+
+impl Default for OqsKexAlg {
+    fn default() -> Self {
+        OqsKexAlg::MlKem768
+    }
+}
+
+impl OqsKexAlg {
+    /// Returns the nul terminated name liboqs uses for this algorithm.
+    fn method_name(&self) -> &'static [u8] {
+        use self::OqsKexAlg::*;
+        match *self {
+            MlKem512 => ffi::OQS_KEM_alg_ml_kem_512,
+            MlKem768 => ffi::OQS_KEM_alg_ml_kem_768,
+            MlKem1024 => ffi::OQS_KEM_alg_ml_kem_1024,
+            FrodoKem640Aes => ffi::OQS_KEM_alg_frodokem_640_aes,
+            FrodoKem640Shake => ffi::OQS_KEM_alg_frodokem_640_shake,
+            FrodoKem976Aes => ffi::OQS_KEM_alg_frodokem_976_aes,
+            FrodoKem976Shake => ffi::OQS_KEM_alg_frodokem_976_shake,
+            FrodoKem1344Aes => ffi::OQS_KEM_alg_frodokem_1344_aes,
+            FrodoKem1344Shake => ffi::OQS_KEM_alg_frodokem_1344_shake,
+            ClassicMcEliece348864 => ffi::OQS_KEM_alg_classic_mceliece_348864,
+            ClassicMcEliece460896 => ffi::OQS_KEM_alg_classic_mceliece_460896,
+            ClassicMcEliece6688128 => ffi::OQS_KEM_alg_classic_mceliece_6688128,
+            ClassicMcEliece6960119 => ffi::OQS_KEM_alg_classic_mceliece_6960119,
+            ClassicMcEliece8192128 => ffi::OQS_KEM_alg_classic_mceliece_8192128,
+            Hqc128 => ffi::OQS_KEM_alg_hqc_128,
+            Hqc192 => ffi::OQS_KEM_alg_hqc_192,
+            Hqc256 => ffi::OQS_KEM_alg_hqc_256,
+        }
+    }
+}
+
+
+/// The main key exchange struct. Used by both Alice and Bob to generate their respective public
+/// messages and the final [shared secret key].
+///
+/// # Usage
+///
+/// A full key exchange involves the following steps:
+///
+/// 1. Alice calls [`alice_0`]. This will create her [public message].
+/// 2. Alice sends her public message to Bob.
+/// 3. Bob calls [`bob`] with Alice's public message. This will create his public message, and
+///    the final shared key.
+/// 4. Bob sends his public message to Alice.
+/// 5. Alice calls [`alice_1`] with Bob's public message. This will create the same shared key as
+///    Bob got from [`bob`].
+///
+/// [`alice_0`]: #method.alice_0
+/// [`bob`]: #method.bob
+/// [`alice_1`]: struct.OqsKexAlice.html#method.alice_1
+/// [public message]: struct.OqsKexAlice.html#method.get_alice_msg
+/// [shared secret key]: struct.SharedKey.html
+pub struct OqsKex<'r> {
+    /// Never read. liboqs draws from its process wide entropy source, but borrowing the
+    /// `OqsRand` keeps that source selected for as long as this instance lives.
+    _rand: &'r OqsRand,
+    algorithm: OqsKexAlg,
+    oqs_kem: *mut ffi::OQS_KEM,
+}
+
+impl<'r> OqsKex<'r> {
+    /// Initializes and returns a new OQS key exchange instance.
+    ///
+    /// The operations don't draw entropy from `rand` directly, but from the process wide source
+    /// of liboqs. With the `std` feature, the borrow of `rand` guarantees that the source it
+    /// selected stays selected while this instance lives, see
+    /// [`OqsRand`](../rand/struct.OqsRand.html). Without `std` it guarantees nothing.
+    pub fn new(rand: &'r OqsRand, algorithm: OqsKexAlg) -> Result<Self> {
+        let method_name = algorithm.method_name();
+        let oqs_kem = unsafe { ffi::OQS_KEM_new(method_name.as_ptr().cast()) };
+        if oqs_kem != ptr::null_mut() {
+            Ok(OqsKex {
+                _rand: rand,
+                algorithm,
+                oqs_kem,
+            })
+        } else {
+            Err(Error)
+        }
+    }
+
+    /// Returns the key exchange algorithm used by this instance.
+    pub fn algorithm(&self) -> OqsKexAlg {
+        self.algorithm
+    }
+
+    /// Method for doing Alice's first step in the key exchange.
+    ///
+    /// If the operation is successful, an [`OqsKexAlice`] is returned. The returned struct can be
+    /// used to retreive [Alice's public message], and to perform the [finalizing step] of the key
+    /// exchange that will finally yield the [shared secret key].
+    ///
+    /// [`OqsKexAlice`]: struct.OqsKexAlice.html
+    /// [Alice's public message]: struct.AliceMsg.html
+    /// [finalizing step]: struct.OqsKexAlice.html#method.alice_1
+    /// [shared secret key]: struct.SharedKey.html
+    pub fn alice_0<'a>(&'a self) -> Result<OqsKexAlice<'a, 'r>> {
+        let mut public_key = Buf::zeroed(self.kem().length_public_key);
+        let mut secret_key = Buf::zeroed(self.kem().length_secret_key);
+        let result = rand::with_entropy_check(|| unsafe {
+            ffi::OQS_KEM_keypair(
+                self.oqs_kem,
+                public_key.as_mut_ptr(),
+                secret_key.as_mut_ptr(),
+            )
+        }).unwrap_or(OQS_STATUS::OQS_ERROR);
+        if result == OQS_STATUS::OQS_SUCCESS {
+            Ok(OqsKexAlice {
+                parent: self,
+                secret_key,
+                alice_msg: AliceMsg::new(self.algorithm, public_key),
+            })
+        } else {
+            Err(Error)
+        }
+    }
+
+    /// Key exchange method for Bob. When given [Alice's public message], this method computes
+    /// [Bob's public message] and the final [shared secret key].
+    ///
+    /// Returns an error if Alice's message was computed with another algorithm or parameter set
+    /// than this instance uses, or does not have the length expected by the algorithm.
+    ///
+    /// [Alice's public message]: struct.AliceMsg.html
+    /// [Bob's public message]: struct.BobMsg.html
+    /// [shared secret key]: struct.SharedKey.html
+    pub fn bob(&self, alice_msg: &AliceMsg) -> Result<(BobMsg, SharedKey)> {
+        if alice_msg.algorithm() != self.algorithm
+            || alice_msg.data().len() != self.kem().length_public_key
+        {
+            return Err(Error);
+        }
+        let mut ciphertext = Buf::zeroed(self.kem().length_ciphertext);
+        let mut key = Buf::zeroed(self.kem().length_shared_secret);
+        let result = rand::with_entropy_check(|| unsafe {
+            ffi::OQS_KEM_encaps(
+                self.oqs_kem,
+                ciphertext.as_mut_ptr(),
+                key.as_mut_ptr(),
+                alice_msg.data().as_ptr(),
+            )
+        }).unwrap_or(OQS_STATUS::OQS_ERROR);
+        if result == OQS_STATUS::OQS_SUCCESS {
+            Ok((
+                BobMsg::new(self.algorithm, ciphertext),
+                SharedKey::new(self.algorithm, key),
+            ))
+        } else {
+            Err(Error)
+        }
+    }
+
+    fn kem(&self) -> &ffi::OQS_KEM {
+        unsafe { &*self.oqs_kem }
+    }
+}
+
+impl<'r> Drop for OqsKex<'r> {
+    fn drop(&mut self) {
+        unsafe { ffi::OQS_KEM_free(self.oqs_kem) };
+    }
+}
+
+
+/// Struct representing the intermediate key exchange state for Alice. This struct contains the
+/// public message to send to Bob and the method used to finalize the key exchange.
+pub struct OqsKexAlice<'a, 'r>
+where
+    'r: 'a,
+{
+    parent: &'a OqsKex<'r>,
+    secret_key: Buf,
+    alice_msg: AliceMsg,
+}
+
+impl<'a, 'r> OqsKexAlice<'a, 'r> {
+    /// Method for doing Alice's second, and last, step in the key exchange. When given [Bob's
+    /// public message], this method computes the final [shared secret key].
+    ///
+    /// Returns an error if Bob's message was computed with another algorithm or parameter set
+    /// than Alice's, or does not have the length expected by the algorithm.
+    ///
+    /// [Bob's public message]: struct.BobMsg.html
+    /// [shared secret key]: struct.SharedKey.html
+    pub fn alice_1(self, bob_msg: &BobMsg) -> Result<SharedKey> {
+        let kem = self.parent.kem();
+        if bob_msg.algorithm() != self.parent.algorithm
+            || bob_msg.data().len() != kem.length_ciphertext
+        {
+            return Err(Error);
+        }
+        let mut key = Buf::zeroed(kem.length_shared_secret);
+        let result = unsafe {
+            ffi::OQS_KEM_decaps(
+                self.parent.oqs_kem,
+                key.as_mut_ptr(),
+                bob_msg.data().as_ptr(),
+                self.secret_key.data().as_ptr(),
+            )
+        };
+        if result == OQS_STATUS::OQS_SUCCESS {
+            Ok(SharedKey::new(self.parent.algorithm, key))
+        } else {
+            Err(Error)
+        }
+    }
+
+    /// Returns the key exchange algorithm used by this instance.
+    pub fn algorithm(&self) -> OqsKexAlg {
+        self.parent.algorithm
+    }
+
+    /// Recreates Alice's intermediate state from a detached `AliceState`. `parent` must use the
+    /// same algorithm as the one the state was created with.
+    #[cfg(feature = "std")]
+    pub(crate) fn attach(parent: &'a OqsKex<'r>, state: AliceState) -> Result<Self> {
+        if parent.algorithm == state.algorithm() {
+            Ok(OqsKexAlice {
+                parent,
+                secret_key: state.secret_key,
+                alice_msg: state.alice_msg,
+            })
+        } else {
+            Err(Error)
+        }
+    }
+
+    /// Detaches Alice's public message and secret key from the `OqsKex` they were created by.
+    #[cfg(feature = "std")]
+    pub(crate) fn detach(self) -> AliceState {
+        AliceState {
+            alice_msg: self.alice_msg,
+            secret_key: self.secret_key,
+        }
+    }
+
+    /// Return Alice's public message, the data that should be sent over to bob.
+    pub fn get_alice_msg(&self) -> &AliceMsg {
+        &self.alice_msg
+    }
+}
+
+
+/// Alice's intermediate key exchange state, detached from the [`OqsKex`] that created it. This
+/// allows the state to be moved between threads. Holds Alice's secret key, which is zeroed out
+/// when this struct is dropped.
+///
+/// Returned by the [`batch`] and `async_kex` APIs.
+///
+/// [`OqsKex`]: struct.OqsKex.html
+/// [`batch`]: batch/index.html
+#[cfg(feature = "std")]
+pub struct AliceState {
+    alice_msg: AliceMsg,
+    secret_key: Buf,
+}
+
+#[cfg(feature = "std")]
+impl AliceState {
+    /// Returns the key exchange algorithm used by this instance.
+    pub fn algorithm(&self) -> OqsKexAlg {
+        self.alice_msg.algorithm()
+    }
+
+    /// Return Alice's public message, the data that should be sent over to bob.
+    pub fn get_alice_msg(&self) -> &AliceMsg {
+        &self.alice_msg
+    }
+}
+
+
+/// Alice's message (the public key of her KEM key pair)
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct AliceMsg {
+    algorithm: OqsKexAlg,
+    data: Buf,
+}
+
+impl AliceMsg {
+    fn new(algorithm: OqsKexAlg, data: Buf) -> Self {
+        AliceMsg { algorithm, data }
+    }
+
+    /// Creates a message from its raw bytes, for example a message received in a custom wire
+    /// format. The data is not validated until the message is used in an exchange.
+    pub fn from_bytes(algorithm: OqsKexAlg, data: &[u8]) -> Self {
+        Self::new(algorithm, Buf::from_slice(data))
+    }
+
+    /// Returns the key exchange algorithm used to compute this message.
+    pub fn algorithm(&self) -> OqsKexAlg {
+        self.algorithm
+    }
+
+    /// Returns the data in this message as a slice.
+    pub fn data(&self) -> &[u8] {
+        self.data.data()
+    }
+}
+
+impl AsRef<[u8]> for AliceMsg {
+    fn as_ref(&self) -> &[u8] {
+        self.data.as_ref()
+    }
+}
+
+/// Bob's message (the ciphertext encapsulating the shared key)
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BobMsg {
+    algorithm: OqsKexAlg,
+    data: Buf,
+}
+
+impl BobMsg {
+    fn new(algorithm: OqsKexAlg, data: Buf) -> Self {
+        BobMsg { algorithm, data }
+    }
+
+    /// Creates a message from its raw bytes, for example a message received in a custom wire
+    /// format. The data is not validated until the message is used in an exchange.
+    pub fn from_bytes(algorithm: OqsKexAlg, data: &[u8]) -> Self {
+        Self::new(algorithm, Buf::from_slice(data))
+    }
+
+    /// Returns the key exchange algorithm used to compute this message.
+    pub fn algorithm(&self) -> OqsKexAlg {
+        self.algorithm
+    }
+
+    /// Returns the data in this message as a slice.
+    pub fn data(&self) -> &[u8] {
+        self.data.data()
+    }
+}
+
+impl AsRef<[u8]> for BobMsg {
+    fn as_ref(&self) -> &[u8] {
+        self.data.as_ref()
+    }
+}
+
+/// Shared key, the result of a completed key exchange.
+#[derive(Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SharedKey {
+    algorithm: OqsKexAlg,
+    data: Buf,
+}
+
+impl SharedKey {
+    fn new(algorithm: OqsKexAlg, data: Buf) -> Self {
+        SharedKey { algorithm, data }
+    }
+
+    /// Returns the key exchange algorithm used to compute this key.
+    pub fn algorithm(&self) -> OqsKexAlg {
+        self.algorithm
+    }
+
+    /// Returns the data in this key as a slice.
+    pub fn data(&self) -> &[u8] {
+        self.data.data()
+    }
+}
+
+impl AsRef<[u8]> for SharedKey {
+    fn as_ref(&self) -> &[u8] {
+        self.data.as_ref()
+    }
+}
+
+
+/// The local result alias for fallible operations in this module.
+pub type Result<T> = ::core::result::Result<T, Error>;
+
+/// Error representing a failure in any [`OqsKex`](struct.OqsRand.html) operation.
+#[derive(Debug, Copy, Clone, Hash)]
+pub struct Error;
+
+impl Error {
+    fn description(&self) -> &str {
+        "Key exchange operation failed"
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> ::core::result::Result<(), fmt::Error> {
+        self.description().fmt(f)
+    }
+}
+
+#[cfg(feature = "std")]
+impl ::std::error::Error for Error {
+    fn description(&self) -> &str {
+        Error::description(self)
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::OqsRandAlg;
+
+    macro_rules! test_full_kex {
+        ($name:ident, $algo:expr) => (
+            #[test]
+            fn $name() {
+                let rand_alice = OqsRand::new(OqsRandAlg::default()).unwrap();
+                let kex_alice = OqsKex::new(&rand_alice, $algo)
+                    .expect("Unable to create KEX");
+                let kex_alice_0 = kex_alice.alice_0().expect("Failed in alice_0");
+
+                let (bob_msg, key1) = helper_bob(kex_alice_0.get_alice_msg());
+
+                let key2 = kex_alice_0.alice_1(&bob_msg).expect("Failed in alice_1");
+
+                assert!(!key1.data().is_empty());
+                assert_eq!(key1, key2);
+            }
+        )
+    }
+
+    test_full_kex!(full_kex_ml_kem_512, OqsKexAlg::MlKem512);
+    test_full_kex!(full_kex_ml_kem_768, OqsKexAlg::MlKem768);
+    test_full_kex!(full_kex_ml_kem_1024, OqsKexAlg::MlKem1024);
+    test_full_kex!(full_kex_frodokem_640_aes, OqsKexAlg::FrodoKem640Aes);
+    test_full_kex!(full_kex_frodokem_640_shake, OqsKexAlg::FrodoKem640Shake);
+    test_full_kex!(full_kex_frodokem_976_aes, OqsKexAlg::FrodoKem976Aes);
+    test_full_kex!(full_kex_frodokem_976_shake, OqsKexAlg::FrodoKem976Shake);
+    test_full_kex!(full_kex_frodokem_1344_aes, OqsKexAlg::FrodoKem1344Aes);
+    test_full_kex!(full_kex_frodokem_1344_shake, OqsKexAlg::FrodoKem1344Shake);
+    test_full_kex!(
+        full_kex_classic_mceliece_348864,
+        OqsKexAlg::ClassicMcEliece348864
+    );
+    test_full_kex!(
+        full_kex_classic_mceliece_460896,
+        OqsKexAlg::ClassicMcEliece460896
+    );
+    test_full_kex!(
+        full_kex_classic_mceliece_6688128,
+        OqsKexAlg::ClassicMcEliece6688128
+    );
+    test_full_kex!(
+        full_kex_classic_mceliece_6960119,
+        OqsKexAlg::ClassicMcEliece6960119
+    );
+    test_full_kex!(
+        full_kex_classic_mceliece_8192128,
+        OqsKexAlg::ClassicMcEliece8192128
+    );
+    test_full_kex!(full_kex_hqc_128, OqsKexAlg::Hqc128);
+    test_full_kex!(full_kex_hqc_192, OqsKexAlg::Hqc192);
+    test_full_kex!(full_kex_hqc_256, OqsKexAlg::Hqc256);
+
+    #[test]
+    fn repeated_exchanges_differ() {
+        let rand = OqsRand::new(OqsRandAlg::default()).unwrap();
+        let kex_alice = OqsKex::new(&rand, OqsKexAlg::MlKem768).unwrap();
+        let kex_alice_0 = kex_alice.alice_0().unwrap();
+        let (bob_msg, key) = helper_bob(kex_alice_0.get_alice_msg());
+        let other_alice_0 = kex_alice.alice_0().unwrap();
+        let (other_bob_msg, other_key) = helper_bob(other_alice_0.get_alice_msg());
+
+        assert_ne!(kex_alice_0.get_alice_msg(), other_alice_0.get_alice_msg());
+        assert_ne!(bob_msg, other_bob_msg);
+        assert_ne!(key, other_key);
+        assert_eq!(other_alice_0.alice_1(&other_bob_msg).unwrap(), other_key);
+    }
+
+    #[test]
+    fn bob_rejects_invalid_length() {
+        let rand = OqsRand::new(OqsRandAlg::default()).unwrap();
+        let kex = OqsKex::new(&rand, OqsKexAlg::MlKem768).unwrap();
+        let alice_msg = AliceMsg::new(OqsKexAlg::MlKem768, Buf::zeroed(10));
+        assert!(kex.bob(&alice_msg).is_err());
+    }
+
+    #[test]
+    fn bob_rejects_other_parameter_set() {
+        let rand = OqsRand::new(OqsRandAlg::default()).unwrap();
+        let kex_alice = OqsKex::new(&rand, OqsKexAlg::FrodoKem640Aes).unwrap();
+        let kex_alice_0 = kex_alice.alice_0().unwrap();
+        let kex_bob = OqsKex::new(&rand, OqsKexAlg::FrodoKem640Shake).unwrap();
+        assert!(kex_bob.bob(kex_alice_0.get_alice_msg()).is_err());
+    }
+
+    #[test]
+    fn alice_1_rejects_other_parameter_set() {
+        let rand = OqsRand::new(OqsRandAlg::default()).unwrap();
+        let kex_alice = OqsKex::new(&rand, OqsKexAlg::FrodoKem640Aes).unwrap();
+        let kex_alice_0 = kex_alice.alice_0().unwrap();
+        let (bob_msg, _) = helper_bob(kex_alice_0.get_alice_msg());
+        let bob_msg = BobMsg::new(OqsKexAlg::FrodoKem640Shake, bob_msg.data);
+        assert!(kex_alice_0.alice_1(&bob_msg).is_err());
+    }
+
+    #[test]
+    fn alice_1_rejects_invalid_length() {
+        let rand = OqsRand::new(OqsRandAlg::default()).unwrap();
+        let kex = OqsKex::new(&rand, OqsKexAlg::MlKem768).unwrap();
+        let kex_alice_0 = kex.alice_0().unwrap();
+        let bob_msg = BobMsg::new(OqsKexAlg::MlKem768, Buf::zeroed(10));
+        assert!(kex_alice_0.alice_1(&bob_msg).is_err());
+    }
+
+    #[test]
+    fn messages_from_bytes() {
+        let rand = OqsRand::new(OqsRandAlg::default()).unwrap();
+        let kex = OqsKex::new(&rand, OqsKexAlg::MlKem768).unwrap();
+        let kex_alice_0 = kex.alice_0().unwrap();
+        let alice_msg = kex_alice_0.get_alice_msg();
+        let alice_msg = AliceMsg::from_bytes(alice_msg.algorithm(), alice_msg.data());
+        let (bob_msg, key1) = helper_bob(&alice_msg);
+        let bob_msg = BobMsg::from_bytes(bob_msg.algorithm(), bob_msg.data());
+        let key2 = kex_alice_0.alice_1(&bob_msg).unwrap();
+        assert_eq!(key1, key2);
+    }
+
+    fn helper_bob(alice_msg: &AliceMsg) -> (BobMsg, SharedKey) {
+        let rand = OqsRand::new(OqsRandAlg::default()).unwrap();
+        let (bob_msg, shared_key) = OqsKex::new(&rand, alice_msg.algorithm())
+            .unwrap()
+            .bob(alice_msg)
+            .unwrap();
+        (bob_msg, shared_key)
+    }
+}
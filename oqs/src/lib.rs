@@ -11,12 +11,23 @@
 //! See the [oqs-sys] crate for low level FFI bindings to [liboqs]. This crate abstracts over those
 //! bindings, to create a safe interface to the C library.
 //!
-//! This crate mostly focuses on exposing the PRNG and key exchange parts of [liboqs]. See the
-//! respective modules for more detailed documentation.
+//! This crate mostly focuses on exposing the PRNG, key exchange and signature parts of [liboqs].
+//! See the respective modules for more detailed documentation.
 //!
 //! See the [oqs-kex-rpc] crate for a client and server implementation that can perform full key
 //! exchanges over JSON-RPC 2.0 over HTTP.
 //!
+//! # `no_std` support
+//!
+//! This crate only needs `core` and `alloc`. The `std` feature is enabled by default and adds
+//! `std::error::Error` implementations and custom entropy sources. Build with
+//! `default-features = false` to use this crate without the Rust standard library.
+//!
+//! # Async key exchange
+//!
+//! With the `async` feature the [`async_kex`] module is available. It performs the key exchange
+//! operations on a thread pool and returns futures, so they don't block the calling event loop.
+//!
 //! # Example
 //!
 //! Here is a simple example how one can perform a key exchange operation. This code performs both
@@ -72,10 +83,16 @@
 //! [liboqs]: https://github.com/open-quantum-safe/liboqs
 //! [oqs-sys]: https://crates.io/crates/oqs-sys
 //! [oqs-kex-rpc]: https://crates.io/crates/oqs-kex-rpc
+//! [`async_kex`]: async_kex/index.html
 
 #![deny(missing_docs)]
+#![no_std]
 
-extern crate core;
+#[cfg(feature = "std")]
+#[macro_use]
+extern crate std;
+#[macro_use]
+extern crate alloc;
 extern crate libc;
 extern crate oqs_sys;
 
@@ -83,10 +100,22 @@ extern crate oqs_sys;
 #[cfg(feature = "serde")]
 extern crate serde;
 
+#[cfg(feature = "async")]
+extern crate futures;
+#[cfg(feature = "async")]
+extern crate futures_cpupool;
+
 /// The key exchange primitives.
 pub mod kex;
 
 /// The PRNG primitives.
 pub mod rand;
 
+/// The digital signature primitives.
+pub mod sig;
+
+/// Futures based key exchange running on a thread pool.
+#[cfg(feature = "async")]
+pub mod async_kex;
+
 mod buf;
@@ -6,31 +6,36 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use core::ptr;
-use std::fmt;
+use core::fmt;
 
 use oqs_sys::rand as ffi;
+use oqs_sys::common::OQS_STATUS;
 
-/// Enum representation of the supported PRNG algorithms. Used to select backing algorithm when
+/// Enum representation of the supported entropy sources. Used to select backing algorithm when
 /// creating [`OqsRand`](struct.OqsRand.html) instances.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum OqsRandAlg {
-    /// The default PRNG. Which algorithm that is the default is defined by `liboqs`.
+    /// The default entropy source. Same as `System`.
     Default,
-    /// ChaCha20 based PRNG.
-    UrandomChacha20,
-    /// AES-CTR based PRNG.
-    UrandomAesctr,
+    /// The operating system's entropy source, such as `getrandom` or `/dev/urandom`.
+    System,
+    /// OpenSSL's `RAND_bytes`. Only available if liboqs was built with OpenSSL support.
+    OpenSsl,
+    /// A user supplied entropy source. Only created by
+    /// [`OqsRand::from_fn`](struct.OqsRand.html#method.from_fn), passing it to `OqsRand::new`
+    /// results in an error. Requires the `std` feature.
+    Custom,
 }
 
-impl From<OqsRandAlg> for ffi::OQS_RAND_alg_name {
-    fn from(alg: OqsRandAlg) -> Self {
+impl OqsRandAlg {
+    /// Returns the nul terminated name liboqs uses for this algorithm, if it is built in.
+    fn name(&self) -> Option<&'static [u8]> {
         use self::OqsRandAlg::*;
-        match alg {
-            Default => ffi::OQS_RAND_alg_name::OQS_RAND_alg_default,
-            UrandomChacha20 => ffi::OQS_RAND_alg_name::OQS_RAND_alg_urandom_chacha20,
-            UrandomAesctr => ffi::OQS_RAND_alg_name::OQS_RAND_alg_urandom_aesctr,
+        match *self {
+            Default | System => Some(ffi::OQS_RAND_alg_system),
+            OpenSsl => Some(ffi::OQS_RAND_alg_openssl),
+            Custom => None,
         }
     }
 }
@@ -42,23 +47,54 @@ impl Default for OqsRandAlg {
 }
 
 /// The PRNG structure.
+///
+/// liboqs has a single process wide entropy source that all its cryptographic operations draw
+/// from, so which source an `OqsRand` selects is process global as well.
+///
+/// With the `std` feature the selection is reference counted. It stays active for as long as any
+/// `OqsRand` created with it is alive, and creating an `OqsRand` with another source fails until
+/// all of them have been dropped. A source registered with [`from_fn`] is never shared, so no
+/// other `OqsRand` can be created while it is alive. Without `std` the selection is not tracked,
+/// and the most recently created `OqsRand` decides which source liboqs uses.
+///
+/// [`from_fn`]: #method.from_fn
 pub struct OqsRand {
     algorithm: OqsRandAlg,
-    pub(crate) oqs_rand: *mut ffi::OQS_RAND,
 }
 
 impl OqsRand {
-    /// Initializes and returns a new PRNG based on the given algorithm.
+    /// Initializes and returns a new PRNG based on the given algorithm. Fails if another
+    /// algorithm is selected by a live `OqsRand`, see above.
     pub fn new(algorithm: OqsRandAlg) -> Result<Self> {
-        let oqs_rand = unsafe { ffi::OQS_RAND_new(ffi::OQS_RAND_alg_name::from(algorithm)) };
-        if oqs_rand != ptr::null_mut() {
-            Ok(OqsRand {
-                algorithm,
-                oqs_rand,
-            })
-        } else {
-            Err(Error)
-        }
+        let name = algorithm.name().ok_or(Error)?;
+        select(algorithm, || switch_algorithm(name))?;
+        Ok(OqsRand { algorithm })
+    }
+
+    /// Registers `fill` as the entropy source of liboqs and returns a PRNG backed by it. Can be
+    /// used to feed liboqs from a hardware entropy source, or to inject failures in tests.
+    ///
+    /// `fill` is called every time liboqs needs random data and must fill the entire buffer it is
+    /// given. If it returns an error, or panics, the buffer is zeroed and the operation that
+    /// requested the entropy fails. `fill` must not call into liboqs itself.
+    ///
+    /// Just like with `new`, the entropy source is process wide. It stays registered until the
+    /// returned `OqsRand` is dropped, after which liboqs falls back to the `System` source. Fails
+    /// if any other `OqsRand` is alive.
+    ///
+    /// Only available with the `std` feature.
+    #[cfg(feature = "std")]
+    pub fn from_fn<F>(fill: F) -> Result<Self>
+    where
+        F: FnMut(&mut [u8]) -> Result<()> + Send + 'static,
+    {
+        select(OqsRandAlg::Custom, || {
+            custom::register(fill);
+            true
+        })?;
+        Ok(OqsRand {
+            algorithm: OqsRandAlg::Custom,
+        })
     }
 
     /// Returns the algorithm backing this PRNG.
@@ -67,48 +103,230 @@ impl OqsRand {
     }
 
     /// Returns an 8-bit random unsigned integer
-    pub fn rand_8(&self) -> u8 {
-        unsafe { ffi::OQS_RAND_8(self.oqs_rand) }
+    pub fn rand_8(&self) -> Result<u8> {
+        let mut buffer = [0; 1];
+        self.rand_n(&mut buffer)?;
+        Ok(buffer[0])
     }
 
     /// Returns an 32-bit random unsigned integer
-    pub fn rand_32(&self) -> u32 {
-        unsafe { ffi::OQS_RAND_32(self.oqs_rand) }
+    pub fn rand_32(&self) -> Result<u32> {
+        let mut buffer = [0; 4];
+        self.rand_n(&mut buffer)?;
+        Ok(u32::from_le_bytes(buffer))
     }
 
     /// Returns an 64-bit random unsigned integer
-    pub fn rand_64(&self) -> u64 {
-        unsafe { ffi::OQS_RAND_64(self.oqs_rand) }
+    pub fn rand_64(&self) -> Result<u64> {
+        let mut buffer = [0; 8];
+        self.rand_n(&mut buffer)?;
+        Ok(u64::from_le_bytes(buffer))
     }
 
-    /// Fills the given buffer with random data
-    pub fn rand_n(&self, buffer: &mut [u8]) {
-        unsafe { ffi::OQS_RAND_n(self.oqs_rand, buffer.as_mut_ptr(), buffer.len()) }
+    /// Fills the given buffer with random data. Fails if a custom entropy source fails to
+    /// deliver the data, in which case the buffer must not be used.
+    pub fn rand_n(&self, buffer: &mut [u8]) -> Result<()> {
+        with_entropy_check(|| unsafe { ffi::OQS_randombytes(buffer.as_mut_ptr(), buffer.len()) })
     }
 }
 
+#[cfg(feature = "std")]
 impl Drop for OqsRand {
     fn drop(&mut self) {
-        unsafe { ffi::OQS_RAND_free(self.oqs_rand) };
+        selection::release(|algorithm| {
+            if algorithm == OqsRandAlg::Custom {
+                custom::unregister();
+                switch_algorithm(OqsRandAlg::System.name().unwrap());
+            }
+        });
     }
 }
 
+fn switch_algorithm(name: &[u8]) -> bool {
+    let result =
+        unsafe { ffi::OQS_randombytes_switch_algorithm(name.as_ptr().cast()) };
+    result == OQS_STATUS::OQS_SUCCESS
+}
+
+#[cfg(feature = "std")]
+use self::selection::acquire as select;
+
+/// Selects the entropy source of liboqs by calling `switch`. Without `std` there is no lock to
+/// track the selection with, so this always switches.
+#[cfg(not(feature = "std"))]
+fn select<F: FnOnce() -> bool>(_algorithm: OqsRandAlg, switch: F) -> Result<()> {
+    if switch() {
+        Ok(())
+    } else {
+        Err(Error)
+    }
+}
+
+
+#[cfg(feature = "std")]
+pub(crate) use self::custom::with_entropy_check;
+
+/// Runs `operation`, that draws entropy from liboqs, and returns its result. Custom entropy
+/// sources are not available without `std`, so this never fails.
+#[cfg(not(feature = "std"))]
+pub(crate) fn with_entropy_check<T, F: FnOnce() -> T>(operation: F) -> Result<T> {
+    Ok(operation())
+}
+
 /// The local result alias.
-pub type Result<T> = ::std::result::Result<T, Error>;
+pub type Result<T> = ::core::result::Result<T, Error>;
 
-/// Error representing a failure to initialize an [`OqsRand`](struct.OqsRand.html).
+/// Error representing a failure to initialize an [`OqsRand`](struct.OqsRand.html), or a failure
+/// in a custom entropy source.
 #[derive(Debug, Copy, Clone, Hash)]
 pub struct Error;
 
+impl Error {
+    fn description(&self) -> &str {
+        "Error in PRNG"
+    }
+}
+
 impl fmt::Display for Error {
-    fn fmt(&self, f: &mut fmt::Formatter) -> ::std::result::Result<(), fmt::Error> {
-        use std::error::Error;
+    fn fmt(&self, f: &mut fmt::Formatter) -> ::core::result::Result<(), fmt::Error> {
         self.description().fmt(f)
     }
 }
 
+#[cfg(feature = "std")]
 impl ::std::error::Error for Error {
     fn description(&self) -> &str {
-        "Error during PRNG initialization"
+        Error::description(self)
+    }
+}
+
+
+/// Support for user supplied entropy sources. Registering a callback in liboqs requires global
+/// state behind a lock, so this is only available with `std`.
+#[cfg(feature = "std")]
+mod custom {
+    use super::{Error, Result};
+    use oqs_sys::rand as ffi;
+
+    use alloc::boxed::Box;
+    use core::cell::Cell;
+    use core::slice;
+    use std::panic::{self, AssertUnwindSafe};
+    use std::sync::{Mutex, MutexGuard, PoisonError};
+
+    type FillFn = dyn FnMut(&mut [u8]) -> Result<()> + Send;
+
+    static CUSTOM_SOURCE: Mutex<Option<Box<FillFn>>> = Mutex::new(None);
+
+    thread_local! {
+        /// Set when a custom entropy source fails to deliver data requested on this thread.
+        static ENTROPY_FAILURE: Cell<bool> = const { Cell::new(false) };
+    }
+
+    /// Registers `fill` as the entropy source of liboqs.
+    pub fn register<F>(fill: F)
+    where
+        F: FnMut(&mut [u8]) -> Result<()> + Send + 'static,
+    {
+        *lock_custom_source() = Some(Box::new(fill));
+        unsafe { ffi::OQS_randombytes_custom_algorithm(Some(custom_randombytes)) };
+    }
+
+    /// Unregisters the custom entropy source. liboqs requests made after this fail until another
+    /// source is selected.
+    pub fn unregister() {
+        *lock_custom_source() = None;
+    }
+
+    /// Runs `operation`, that draws entropy from liboqs, and returns its result. Returns an
+    /// error instead if a custom entropy source failed during the operation.
+    pub fn with_entropy_check<T, F: FnOnce() -> T>(operation: F) -> Result<T> {
+        ENTROPY_FAILURE.with(|failure| failure.set(false));
+        let result = operation();
+        if ENTROPY_FAILURE.with(|failure| failure.replace(false)) {
+            Err(Error)
+        } else {
+            Ok(result)
+        }
+    }
+
+    fn lock_custom_source() -> MutexGuard<'static, Option<Box<FillFn>>> {
+        CUSTOM_SOURCE.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// The callback registered in liboqs for custom entropy sources. Forwards the request to
+    /// the currently registered source.
+    unsafe extern "C" fn custom_randombytes(random_array: *mut u8, bytes_to_read: usize) {
+        let buffer = slice::from_raw_parts_mut(random_array, bytes_to_read);
+        let result = panic::catch_unwind(AssertUnwindSafe(|| match *lock_custom_source() {
+            Some(ref mut fill) => fill(buffer),
+            None => Err(Error),
+        }));
+        if !result.map(|fill_result| fill_result.is_ok()).unwrap_or(false) {
+            for byte in buffer.iter_mut() {
+                *byte = 0;
+            }
+            ENTROPY_FAILURE.with(|failure| failure.set(true));
+        }
+    }
+}
+
+
+/// Reference counting of the process global entropy source selection.
+#[cfg(feature = "std")]
+mod selection {
+    use super::{Error, OqsRandAlg, Result};
+
+    use std::sync::{Mutex, MutexGuard, PoisonError};
+
+    /// The selected entropy source and the number of live `OqsRand` instances using it.
+    struct Selection {
+        algorithm: OqsRandAlg,
+        users: usize,
+    }
+
+    static SELECTION: Mutex<Selection> = Mutex::new(Selection {
+        algorithm: OqsRandAlg::System,
+        users: 0,
+    });
+
+    /// Registers one more user of `algorithm`. If nothing is selected, `switch` is called to
+    /// select it in liboqs. Fails if another algorithm, or a custom source, is in use.
+    pub fn acquire<F: FnOnce() -> bool>(algorithm: OqsRandAlg, switch: F) -> Result<()> {
+        let algorithm = normalize(algorithm);
+        let mut selection = lock_selection();
+        if selection.users > 0 {
+            if algorithm == OqsRandAlg::Custom || selection.algorithm != algorithm {
+                return Err(Error);
+            }
+        } else if switch() {
+            selection.algorithm = algorithm;
+        } else {
+            return Err(Error);
+        }
+        selection.users += 1;
+        Ok(())
+    }
+
+    /// Unregisters one user of the selected algorithm. Calls `on_last` with the algorithm if it
+    /// was the last user.
+    pub fn release<F: FnOnce(OqsRandAlg)>(on_last: F) {
+        let mut selection = lock_selection();
+        selection.users -= 1;
+        if selection.users == 0 {
+            on_last(selection.algorithm);
+        }
+    }
+
+    /// `Default` and `System` select the same source.
+    fn normalize(algorithm: OqsRandAlg) -> OqsRandAlg {
+        match algorithm {
+            OqsRandAlg::Default => OqsRandAlg::System,
+            algorithm => algorithm,
+        }
+    }
+
+    fn lock_selection() -> MutexGuard<'static, Selection> {
+        SELECTION.lock().unwrap_or_else(PoisonError::into_inner)
     }
 }
@@ -0,0 +1,408 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//!
+//! This module has the types used to create and verify post-quantum digital signatures. They are
+//! backed by the signature schemes in [liboqs].
+//!
+//! A signer generates a long-term key pair once with [`OqsSig::keypair`], distributes the
+//! [`PublicKey`] to the verifiers and keeps the [`SecretKey`] to sign messages with.
+//!
+//! [liboqs]: https://github.com/open-quantum-safe/liboqs
+//! [`OqsSig::keypair`]: struct.OqsSig.html#method.keypair
+//! [`PublicKey`]: struct.PublicKey.html
+//! [`SecretKey`]: struct.SecretKey.html
+
+use core::fmt;
+
+use oqs_sys::sig as ffi;
+use oqs_sys::common::OQS_STATUS;
+use rand::{self, OqsRand};
+use buf::Buf;
+
+
+/// Enum representation of the supported signature algorithms. Used to select backing algorithm
+/// when creating [`OqsSig`](struct.OqsSig.html) instances.
+///
+/// The number in each variant name is the parameter set as named in [liboqs]. Which of the
+/// algorithms are available depends on which ones liboqs was built with.
+///
+/// [liboqs]: https://github.com/open-quantum-safe/liboqs
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[allow(missing_docs)]
+pub enum OqsSigAlg {
+    /// ML-DSA, the module lattice based signature scheme standardized in FIPS 204.
+    MlDsa44,
+    #[default]
+    MlDsa65,
+    MlDsa87,
+    /// Falcon, a lattice based signature scheme with compact signatures.
+    Falcon512,
+    Falcon1024,
+}
+
+impl OqsSigAlg {
+    fn method_name(&self) -> &'static [u8] {
+        use self::OqsSigAlg::*;
+        match *self {
+            MlDsa44 => ffi::OQS_SIG_alg_ml_dsa_44,
+            MlDsa65 => ffi::OQS_SIG_alg_ml_dsa_65,
+            MlDsa87 => ffi::OQS_SIG_alg_ml_dsa_87,
+            Falcon512 => ffi::OQS_SIG_alg_falcon_512,
+            Falcon1024 => ffi::OQS_SIG_alg_falcon_1024,
+        }
+    }
+}
+
+
+/// The main signature struct. Used both to generate key pairs, to sign messages and to verify
+/// signatures.
+pub struct OqsSig<'r> {
+    /// Never read. liboqs draws from its process wide entropy source, but borrowing the
+    /// `OqsRand` keeps that source selected for as long as this instance lives.
+    _rand: &'r OqsRand,
+    algorithm: OqsSigAlg,
+    oqs_sig: *mut ffi::OQS_SIG,
+}
+
+impl<'r> OqsSig<'r> {
+    /// Initializes and returns a new OQS signature instance. Borrows `rand` like
+    /// [`OqsKex::new`](../kex/struct.OqsKex.html#method.new) does.
+    pub fn new(rand: &'r OqsRand, algorithm: OqsSigAlg) -> Result<Self> {
+        let method_name = algorithm.method_name();
+        let oqs_sig = unsafe { ffi::OQS_SIG_new(method_name.as_ptr().cast()) };
+        if !oqs_sig.is_null() {
+            Ok(OqsSig {
+                _rand: rand,
+                algorithm,
+                oqs_sig,
+            })
+        } else {
+            Err(Error)
+        }
+    }
+
+    /// Returns the signature algorithm used by this instance.
+    pub fn algorithm(&self) -> OqsSigAlg {
+        self.algorithm
+    }
+
+    /// Generates a new key pair.
+    pub fn keypair(&self) -> Result<(PublicKey, SecretKey)> {
+        let mut public_key = Buf::zeroed(self.sig().length_public_key);
+        let mut secret_key = Buf::zeroed(self.sig().length_secret_key);
+        let result = rand::with_entropy_check(|| unsafe {
+            ffi::OQS_SIG_keypair(
+                self.oqs_sig,
+                public_key.as_mut_ptr(),
+                secret_key.as_mut_ptr(),
+            )
+        }).unwrap_or(OQS_STATUS::OQS_ERROR);
+        if result == OQS_STATUS::OQS_SUCCESS {
+            Ok((
+                PublicKey::new(self.algorithm, public_key),
+                SecretKey::new(self.algorithm, secret_key),
+            ))
+        } else {
+            Err(Error)
+        }
+    }
+
+    /// Signs `message` with `secret_key`.
+    ///
+    /// Returns an error if the key is for another algorithm than this instance uses, or does
+    /// not have the length expected by the algorithm.
+    pub fn sign(&self, secret_key: &SecretKey, message: &[u8]) -> Result<Signature> {
+        if secret_key.algorithm() != self.algorithm
+            || secret_key.data().len() != self.sig().length_secret_key
+        {
+            return Err(Error);
+        }
+        let mut signature = Buf::zeroed(self.sig().length_signature);
+        let mut signature_len = 0;
+        let result = rand::with_entropy_check(|| unsafe {
+            ffi::OQS_SIG_sign(
+                self.oqs_sig,
+                signature.as_mut_ptr(),
+                &mut signature_len,
+                message.as_ptr(),
+                message.len(),
+                secret_key.data().as_ptr(),
+            )
+        }).unwrap_or(OQS_STATUS::OQS_ERROR);
+        if result == OQS_STATUS::OQS_SUCCESS {
+            // Some algorithms produce signatures shorter than the maximum length.
+            let signature = Buf::from_slice(&signature.data()[..signature_len]);
+            Ok(Signature::new(self.algorithm, signature))
+        } else {
+            Err(Error)
+        }
+    }
+
+    /// Verifies that `signature` is a signature over `message` by the secret key belonging to
+    /// `public_key`.
+    ///
+    /// Returns an error if the signature is not valid, or if the key or signature is for another
+    /// algorithm than this instance uses.
+    pub fn verify(
+        &self,
+        public_key: &PublicKey,
+        message: &[u8],
+        signature: &Signature,
+    ) -> Result<()> {
+        if public_key.algorithm() != self.algorithm || signature.algorithm() != self.algorithm
+            || public_key.data().len() != self.sig().length_public_key
+            || signature.data().len() > self.sig().length_signature
+        {
+            return Err(Error);
+        }
+        let result = unsafe {
+            ffi::OQS_SIG_verify(
+                self.oqs_sig,
+                message.as_ptr(),
+                message.len(),
+                signature.data().as_ptr(),
+                signature.data().len(),
+                public_key.data().as_ptr(),
+            )
+        };
+        if result == OQS_STATUS::OQS_SUCCESS {
+            Ok(())
+        } else {
+            Err(Error)
+        }
+    }
+
+    fn sig(&self) -> &ffi::OQS_SIG {
+        unsafe { &*self.oqs_sig }
+    }
+}
+
+impl<'r> Drop for OqsSig<'r> {
+    fn drop(&mut self) {
+        unsafe { ffi::OQS_SIG_free(self.oqs_sig) };
+    }
+}
+
+
+/// The public half of a signature key pair, used to verify signatures.
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PublicKey {
+    algorithm: OqsSigAlg,
+    data: Buf,
+}
+
+impl PublicKey {
+    fn new(algorithm: OqsSigAlg, data: Buf) -> Self {
+        PublicKey { algorithm, data }
+    }
+
+    /// Creates a public key from its raw bytes, for example a key loaded from storage.
+    pub fn from_bytes(algorithm: OqsSigAlg, data: &[u8]) -> Self {
+        Self::new(algorithm, Buf::from_slice(data))
+    }
+
+    /// Returns the signature algorithm this key is for.
+    pub fn algorithm(&self) -> OqsSigAlg {
+        self.algorithm
+    }
+
+    /// Returns the data in this key as a slice.
+    pub fn data(&self) -> &[u8] {
+        self.data.data()
+    }
+}
+
+impl AsRef<[u8]> for PublicKey {
+    fn as_ref(&self) -> &[u8] {
+        self.data.as_ref()
+    }
+}
+
+/// The secret half of a signature key pair, used to sign messages. Zeroed out when dropped. The
+/// `Debug` output only shows the algorithm, never the key itself.
+#[derive(Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SecretKey {
+    algorithm: OqsSigAlg,
+    data: Buf,
+}
+
+impl SecretKey {
+    fn new(algorithm: OqsSigAlg, data: Buf) -> Self {
+        SecretKey { algorithm, data }
+    }
+
+    /// Creates a secret key from its raw bytes, for example a key loaded from storage.
+    pub fn from_bytes(algorithm: OqsSigAlg, data: &[u8]) -> Self {
+        Self::new(algorithm, Buf::from_slice(data))
+    }
+
+    /// Returns the signature algorithm this key is for.
+    pub fn algorithm(&self) -> OqsSigAlg {
+        self.algorithm
+    }
+
+    /// Returns the data in this key as a slice.
+    pub fn data(&self) -> &[u8] {
+        self.data.data()
+    }
+}
+
+impl fmt::Debug for SecretKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> ::core::result::Result<(), fmt::Error> {
+        f.debug_struct("SecretKey")
+            .field("algorithm", &self.algorithm)
+            .finish_non_exhaustive()
+    }
+}
+
+impl AsRef<[u8]> for SecretKey {
+    fn as_ref(&self) -> &[u8] {
+        self.data.as_ref()
+    }
+}
+
+/// A signature over a message.
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Signature {
+    algorithm: OqsSigAlg,
+    data: Buf,
+}
+
+impl Signature {
+    fn new(algorithm: OqsSigAlg, data: Buf) -> Self {
+        Signature { algorithm, data }
+    }
+
+    /// Creates a signature from its raw bytes, for example a signature received in a custom wire
+    /// format.
+    pub fn from_bytes(algorithm: OqsSigAlg, data: &[u8]) -> Self {
+        Self::new(algorithm, Buf::from_slice(data))
+    }
+
+    /// Returns the signature algorithm used to compute this signature.
+    pub fn algorithm(&self) -> OqsSigAlg {
+        self.algorithm
+    }
+
+    /// Returns the data in this signature as a slice.
+    pub fn data(&self) -> &[u8] {
+        self.data.data()
+    }
+}
+
+impl AsRef<[u8]> for Signature {
+    fn as_ref(&self) -> &[u8] {
+        self.data.as_ref()
+    }
+}
+
+
+/// The local result alias for fallible operations in this module.
+pub type Result<T> = ::core::result::Result<T, Error>;
+
+/// Error representing a failure in any [`OqsSig`](struct.OqsSig.html) operation, including
+/// signatures that do not verify.
+#[derive(Debug, Copy, Clone, Hash)]
+pub struct Error;
+
+impl Error {
+    fn description(&self) -> &str {
+        "Signature operation failed"
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> ::core::result::Result<(), fmt::Error> {
+        self.description().fmt(f)
+    }
+}
+
+#[cfg(feature = "std")]
+impl ::std::error::Error for Error {
+    fn description(&self) -> &str {
+        Error::description(self)
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::OqsRandAlg;
+
+    macro_rules! test_sign_verify {
+        ($name:ident, $algo:expr) => (
+            #[test]
+            fn $name() {
+                let rand = OqsRand::new(OqsRandAlg::default()).unwrap();
+                let sig = OqsSig::new(&rand, $algo).expect("Unable to create signer");
+                let (public_key, secret_key) = sig.keypair().expect("Unable to create key pair");
+
+                let signature = sig.sign(&secret_key, b"message").expect("Unable to sign");
+                assert_eq!(signature.algorithm(), $algo);
+                sig.verify(&public_key, b"message", &signature)
+                    .expect("Valid signature did not verify");
+                assert!(sig.verify(&public_key, b"massage", &signature).is_err());
+            }
+        )
+    }
+
+    test_sign_verify!(sign_verify_ml_dsa_44, OqsSigAlg::MlDsa44);
+    test_sign_verify!(sign_verify_ml_dsa_65, OqsSigAlg::MlDsa65);
+    test_sign_verify!(sign_verify_ml_dsa_87, OqsSigAlg::MlDsa87);
+    test_sign_verify!(sign_verify_falcon_512, OqsSigAlg::Falcon512);
+    test_sign_verify!(sign_verify_falcon_1024, OqsSigAlg::Falcon1024);
+
+    #[test]
+    fn verify_rejects_other_key() {
+        let rand = OqsRand::new(OqsRandAlg::default()).unwrap();
+        let sig = OqsSig::new(&rand, OqsSigAlg::default()).unwrap();
+        let (_, secret_key) = sig.keypair().unwrap();
+        let (other_public_key, _) = sig.keypair().unwrap();
+
+        let signature = sig.sign(&secret_key, b"message").unwrap();
+        assert!(sig.verify(&other_public_key, b"message", &signature).is_err());
+    }
+
+    #[test]
+    fn sign_rejects_other_algorithm() {
+        let rand = OqsRand::new(OqsRandAlg::default()).unwrap();
+        let sig_ml_dsa = OqsSig::new(&rand, OqsSigAlg::MlDsa44).unwrap();
+        let sig_falcon = OqsSig::new(&rand, OqsSigAlg::Falcon512).unwrap();
+        let (_, secret_key) = sig_ml_dsa.keypair().unwrap();
+
+        assert!(sig_falcon.sign(&secret_key, b"message").is_err());
+    }
+
+    #[test]
+    fn secret_key_debug_hides_key() {
+        let secret_key = SecretKey::from_bytes(OqsSigAlg::MlDsa44, &[0xab; 16]);
+        let debug = format!("{:?}", secret_key);
+        assert_eq!(debug, "SecretKey { algorithm: MlDsa44, .. }");
+    }
+
+    #[test]
+    fn key_from_bytes() {
+        let rand = OqsRand::new(OqsRandAlg::default()).unwrap();
+        let sig = OqsSig::new(&rand, OqsSigAlg::default()).unwrap();
+        let (public_key, secret_key) = sig.keypair().unwrap();
+
+        let secret_key = SecretKey::from_bytes(secret_key.algorithm(), secret_key.data());
+        let signature = sig.sign(&secret_key, b"message").unwrap();
+        let signature = Signature::from_bytes(signature.algorithm(), signature.data());
+        let public_key = PublicKey::from_bytes(public_key.algorithm(), public_key.data());
+        assert!(sig.verify(&public_key, b"message", &signature).is_ok());
+    }
+}
@@ -0,0 +1,51 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+#[cfg(feature = "std")]
+mod custom_rand {
+    extern crate oqs;
+
+    use self::oqs::kex::{OqsKex, OqsKexAlg};
+    use self::oqs::rand::{self, OqsRand, OqsRandAlg};
+
+    // The entropy source in liboqs is process wide. So everything touching custom entropy
+    // sources is done sequentially in a single test, to not have parallel tests interfere.
+    #[test]
+    fn custom_rand() {
+        assert!(OqsRand::new(OqsRandAlg::Custom).is_err());
+
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let xorshift_rand = OqsRand::from_fn(move |buffer| {
+            for byte in buffer.iter_mut() {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                *byte = state as u8;
+            }
+            Ok(())
+        }).unwrap();
+        assert_eq!(xorshift_rand.algorithm(), OqsRandAlg::Custom);
+
+        let kex_alice = OqsKex::new(&xorshift_rand, OqsKexAlg::MlKem768).unwrap();
+        let kex_alice_0 = kex_alice.alice_0().unwrap();
+        let kex_bob = OqsKex::new(&xorshift_rand, OqsKexAlg::MlKem768).unwrap();
+        let (bob_msg, key_bob) = kex_bob.bob(kex_alice_0.get_alice_msg()).unwrap();
+        let key_alice = kex_alice_0.alice_1(&bob_msg).unwrap();
+        assert_eq!(key_alice, key_bob);
+        drop(kex_bob);
+        drop(kex_alice);
+        drop(xorshift_rand);
+
+        let failing_rand = OqsRand::from_fn(|_| Err(rand::Error)).unwrap();
+        let kex = OqsKex::new(&failing_rand, OqsKexAlg::MlKem768).unwrap();
+        assert!(kex.alice_0().is_err());
+        assert!(failing_rand.rand_64().is_err());
+        let mut buffer = [0; 16];
+        assert!(failing_rand.rand_n(&mut buffer).is_err());
+    }
+}
@@ -0,0 +1,56 @@
+// Copyright 2017 Amagicom AB.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+#[cfg(feature = "std")]
+mod rand_selection {
+    extern crate oqs;
+
+    use self::oqs::kex::{OqsKex, OqsKexAlg};
+    use self::oqs::rand::{OqsRand, OqsRandAlg};
+
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // The entropy source selection is process wide, so it is tested sequentially in one test.
+    #[test]
+    fn two_live_instances() {
+        // Two instances sharing the system source. Default and System are the same source.
+        let rand1 = OqsRand::new(OqsRandAlg::System).unwrap();
+        let rand2 = OqsRand::new(OqsRandAlg::Default).unwrap();
+        assert!(OqsRand::from_fn(|_| Ok(())).is_err());
+
+        // Dropping one of them keeps the selection for the other.
+        drop(rand1);
+        assert!(OqsRand::from_fn(|_| Ok(())).is_err());
+        let kex = OqsKex::new(&rand2, OqsKexAlg::MlKem768).unwrap();
+        assert!(kex.alice_0().is_ok());
+        drop(kex);
+        drop(rand2);
+
+        // A live custom source can't be overridden, and stays in use.
+        let calls = Arc::new(AtomicUsize::new(0));
+        let custom_calls = calls.clone();
+        let custom_rand = OqsRand::from_fn(move |buffer| {
+            custom_calls.fetch_add(1, Ordering::SeqCst);
+            for byte in buffer.iter_mut() {
+                *byte = 0x42;
+            }
+            Ok(())
+        }).unwrap();
+        assert!(OqsRand::new(OqsRandAlg::System).is_err());
+        assert!(OqsRand::from_fn(|_| Ok(())).is_err());
+        assert_eq!(custom_rand.rand_8().unwrap(), 0x42);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // Once the custom source is dropped, other sources can be selected again.
+        drop(custom_rand);
+        let rand = OqsRand::new(OqsRandAlg::System).unwrap();
+        rand.rand_64().unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+}
@@ -17,7 +17,7 @@ mod serialize {
     #[test]
     fn serialize_alice_msg() {
         let rand = OqsRand::new(OqsRandAlg::default()).unwrap();
-        let kex_alice = OqsKex::new(&rand, OqsKexAlg::RlweNewhope).unwrap();
+        let kex_alice = OqsKex::new(&rand, OqsKexAlg::MlKem768).unwrap();
         let kex_alice_0 = kex_alice.alice_0().unwrap();
         let alice_msg = kex_alice_0.get_alice_msg();
 
@@ -29,7 +29,7 @@ mod serialize {
             .expect("Expected field with name \"algorithm\"")
             .as_str()
             .unwrap();
-        assert_eq!(algorithm, "RlweNewhope");
+        assert_eq!(algorithm, "MlKem768");
 
         let data = json_object.get("data").unwrap().as_array().unwrap();
         assert!(!data.is_empty());
@@ -39,10 +39,20 @@ mod serialize {
         }
     }
 
+    #[test]
+    fn serialize_parameter_set() {
+        let algorithm = OqsKexAlg::FrodoKem976Shake;
+        let json_string = serde_json::to_string(&algorithm).unwrap();
+        assert_eq!(json_string, "\"FrodoKem976Shake\"");
+
+        let deserialized_algorithm: OqsKexAlg = serde_json::from_str(&json_string).unwrap();
+        assert_eq!(deserialized_algorithm, algorithm);
+    }
+
     #[test]
     fn serialize_to_string() {
         let rand = OqsRand::new(OqsRandAlg::default()).unwrap();
-        let kex_alice = OqsKex::new(&rand, OqsKexAlg::Default).unwrap();
+        let kex_alice = OqsKex::new(&rand, OqsKexAlg::default()).unwrap();
         let kex_alice_0 = kex_alice.alice_0().unwrap();
         let alice_msg = kex_alice_0.get_alice_msg();
 